@@ -0,0 +1,214 @@
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::sync::mpsc::{RecvError, SendError, TryRecvError};
+
+use serde::{Deserialize, Serialize};
+
+use crate::DequeUncached;
+
+struct Shared<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> {
+    state: Mutex<State<T, CHUNK_ELEMS, COMPRESSION_LEVEL>>,
+    /// Signalled when an item is sent or the last sender is dropped
+    available: Condvar,
+}
+
+struct State<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> {
+    queue: DequeUncached<T, CHUNK_ELEMS, COMPRESSION_LEVEL>,
+    senders: usize,
+    receiver: bool,
+}
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Shared<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+    /// A panic while (de)compressing leaves the queue as it was, so a poisoned lock is still usable.
+    fn lock(&self) -> MutexGuard<'_, State<T, CHUNK_ELEMS, COMPRESSION_LEVEL>> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Creates a multi-producer, single-consumer channel whose backlog is held in a compressed [`Deque`](crate::Deque)
+///
+/// Items sent but not yet received are compressed `CHUNK_ELEMS` at a time, which bounds the memory a deep backlog
+/// between fast producers and a slow consumer takes. The errors are those of [`std::sync::mpsc`].
+///
+/// # Examples
+///
+/// ```
+/// use compressed_collections::cchannel;
+///
+/// let (sender, receiver) = cchannel::<u32, 1024, 0>();
+/// std::thread::scope(|s| {
+///     for t in 0..4 {
+///         let sender = sender.clone();
+///         s.spawn(move || {
+///             for i in 0..(16 * 1024) {
+///                 sender.send(t * 16 * 1024 + i).unwrap();
+///             }
+///         });
+///     }
+/// });
+/// drop(sender);
+/// let mut received: Vec<u32> = receiver.iter().collect();
+/// received.sort();
+/// assert!((0..(64 * 1024)).eq(received));
+/// ```
+pub fn cchannel<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>() -> (CSender<T, CHUNK_ELEMS, COMPRESSION_LEVEL>, CReceiver<T, CHUNK_ELEMS, COMPRESSION_LEVEL>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State { queue: DequeUncached::new::<CHUNK_ELEMS, COMPRESSION_LEVEL>(), senders: 1, receiver: true }),
+        available: Condvar::new(),
+    });
+    (CSender(Arc::clone(&shared)), CReceiver(shared))
+}
+
+/// The sending half of a [`cchannel`], can be cloned to send from several threads
+pub struct CSender<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0>(Arc<Shared<T, CHUNK_ELEMS, COMPRESSION_LEVEL>>);
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CSender<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+    /// Queues `value` for the receiver, failing only if the receiver has been dropped.
+    ///
+    /// Never blocks for the receiver, but the sender which fills a chunk compresses it.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> where T: Serialize {
+        let mut state = self.0.lock();
+        if !state.receiver {
+            return Err(SendError(value));
+        }
+        state.queue.push_back(value);
+        drop(state);
+        self.0.available.notify_one();
+        Ok(())
+    }
+}
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Clone for CSender<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+    fn clone(&self) -> Self {
+        self.0.lock().senders += 1;
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Drop for CSender<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            self.0.available.notify_all();
+        }
+    }
+}
+
+/// The receiving half of a [`cchannel`]
+pub struct CReceiver<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0>(Arc<Shared<T, CHUNK_ELEMS, COMPRESSION_LEVEL>>);
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CReceiver<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+    /// Waits for the next item, failing once the queue is empty and all senders have been dropped.
+    pub fn recv(&self) -> Result<T, RecvError> where T: for<'a> Deserialize<'a> {
+        let mut state = self.0.lock();
+        loop {
+            if let Some(value) = state.queue.pop_front() {
+                return Ok(value);
+            }
+            if state.senders == 0 {
+                return Err(RecvError);
+            }
+            state = self.0.available.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+    /// Returns the next item if there is one, without waiting.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> where T: for<'a> Deserialize<'a> {
+        let mut state = self.0.lock();
+        match state.queue.pop_front() {
+            Some(value) => Ok(value),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+    /// The number of items waiting to be received
+    pub fn len(&self) -> usize {
+        self.0.lock().queue.len()
+    }
+    /// Whether no items are waiting to be received
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns an iterator which waits for items, and ends once the queue is empty and all senders have been dropped.
+    pub fn iter(&self) -> CRecvIter<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        CRecvIter(self)
+    }
+}
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Drop for CReceiver<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.receiver = false;
+        // Nothing will receive the backlog, so free it straight away
+        let backlog = std::mem::take(&mut state.queue);
+        drop(state);
+        drop(backlog);
+    }
+}
+
+/// Iterator over the items received on a [`cchannel`], returned by `CReceiver::iter`
+pub struct CRecvIter<'r, T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0>(&'r CReceiver<T, CHUNK_ELEMS, COMPRESSION_LEVEL>);
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Iterator for CRecvIter<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.recv().ok()
+    }
+}
+
+impl<'r, T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> IntoIterator for &'r CReceiver<T, CHUNK_ELEMS, COMPRESSION_LEVEL>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    type Item = T;
+    type IntoIter = CRecvIter<'r, T, CHUNK_ELEMS, COMPRESSION_LEVEL>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{SendError, TryRecvError};
+
+    use super::cchannel;
+
+    #[test]
+    fn channel_test() {
+        let (sender, receiver) = cchannel::<u32, 100, 0>();
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+        for i in 0..1050 {
+            sender.send(i).unwrap();
+        }
+        assert_eq!(receiver.len(), 1050);
+        let other = sender.clone();
+        drop(sender);
+        assert!((0..1000).eq(receiver.iter().take(1000)));
+        other.send(1050).unwrap();
+        drop(other);
+        assert!((1000..1051).eq(&receiver));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+        assert!(receiver.recv().is_err());
+    }
+    #[test]
+    fn channel_threads_test() {
+        let (sender, receiver) = cchannel::<u64, 100, 0>();
+        let consumer = std::thread::spawn(move || receiver.iter().sum::<u64>());
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                let sender = sender.clone();
+                s.spawn(move || (0..10_000).for_each(|i| sender.send(i).unwrap()));
+            }
+        });
+        drop(sender);
+        assert_eq!(consumer.join().unwrap(), 4 * (0..10_000).sum::<u64>());
+        let (sender, receiver) = cchannel::<u64, 100, 0>();
+        drop(receiver);
+        assert_eq!(sender.send(1), Err(SendError(1)));
+    }
+}
@@ -1,32 +1,215 @@
 use brotli::enc::BrotliEncoderParams;
 use brotli::CompressorWriter;
 use brotli::DecompressorWriter;
+use serde::de::{DeserializeSeed, SeqAccess, Visitor};
 use serde::Deserialize;
+use serde::Deserializer;
 use serde::Serialize;
+use std::cell::RefCell;
 use std::io::Write;
 
+use crate::Error;
+
+/// Serialized elements shorter than this are stored in an uncompressed brotli meta-block rather than compressed,
+/// as running the encoder costs more than the few bytes it could save
+pub const STORE_THRESHOLD: usize = 512;
+
+thread_local! {
+    /// The bytes of the chunk decompressed last on this thread, kept so that their allocation is reused for the
+    /// next one
+    static SCRATCH: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
 pub fn compress<T>(x: &T, compression_level: i32) -> Box<[u8]>
 where
-    T: Serialize,
+    T: Serialize + ?Sized,
+{
+    try_compress(x, compression_level).unwrap() // Only errors on OOM or incorrect `serialize` implementation
+}
+
+/// Like [`compress`], but returns an error for elements which cannot be serialized.
+pub fn try_compress<T>(x: &T, compression_level: i32) -> Result<Box<[u8]>, Error>
+where
+    T: Serialize + ?Sized,
 {
-    let serialized = postcard::to_stdvec(x).unwrap(); // Only errors on OOM
+    let serialized = postcard::to_stdvec(x).map_err(Error::Serialize)?;
+    Ok(compress_bytes(&serialized, compression_level))
+}
+
+/// The number of bytes `x` serializes to, 0 if it fails to serialize (which is reported when it is compressed).
+pub fn serialized_size<T>(x: &T) -> usize
+where
+    T: Serialize + ?Sized,
+{
+    postcard::serialize_with_flavor(x, postcard::ser_flavors::Size::default()).unwrap_or(0)
+}
+/// Compresses already serialized elements, the second half of [`compress`]. Fewer than [`STORE_THRESHOLD`] bytes
+/// are only [`store`]d.
+pub fn compress_bytes(serialized: &[u8], compression_level: i32) -> Box<[u8]> {
+    if serialized.len() < STORE_THRESHOLD {
+        return store(serialized);
+    }
+    compress_bytes_fully(serialized, compression_level)
+}
+
+/// Like [`compress_bytes`], but runs the encoder however few bytes there are, for when the time is worth the bytes
+/// saved (e.g. maintenance).
+pub fn compress_bytes_fully(serialized: &[u8], compression_level: i32) -> Box<[u8]> {
     let params = BrotliEncoderParams {
         quality: compression_level,
         ..Default::default()
     };
     let mut compressed_writer = CompressorWriter::with_params(Vec::new(), 4096, &params);
-    compressed_writer.write_all(&serialized).unwrap(); // Cannot error because we're writing to a Vec
+    compressed_writer.write_all(serialized).unwrap(); // Cannot error because we're writing to a Vec
     compressed_writer.flush().unwrap(); // Cannot error because we're writing to a Vec
     compressed_writer.into_inner().into_boxed_slice()
 }
 
+/// Wraps bytes in a brotli stream without compressing them, as a single uncompressed meta-block (RFC 7932,
+/// section 9.2), so that they are read back like any other chunk. Only used for less than 64 KiB.
+fn store(bytes: &[u8]) -> Box<[u8]> {
+    let Some(last) = bytes.len().checked_sub(1) else {
+        // The window size bit, then ISLAST = 1 and ISLASTEMPTY = 1
+        return Box::new([0b110]);
+    };
+    debug_assert!(last < 1 << 16);
+    // The window size bit (0 for a 64 KiB window), ISLAST = 0, MNIBBLES = 0 (4 nibbles), MLEN - 1 and
+    // ISUNCOMPRESSED = 1, padded to the next byte
+    let header = (last as u32) << 4 | 1 << 20;
+    let mut stored = Vec::with_capacity(bytes.len() + 4);
+    stored.extend_from_slice(&header.to_le_bytes()[..3]);
+    stored.extend_from_slice(bytes);
+    // ISLAST = 1, ISLASTEMPTY = 1
+    stored.push(0b11);
+    stored.into_boxed_slice()
+}
+
 pub fn decompress<T>(x: &[u8]) -> T
 where
     T: for<'a> Deserialize<'a>,
 {
-    let mut decompressor_writer = DecompressorWriter::new(Vec::new(), 4096);
-    decompressor_writer.write_all(x).unwrap(); // Cannot error because we're writing to a Vec
-    decompressor_writer.flush().unwrap(); // Cannot error because we're writing to a Vec
-    let decompressed = decompressor_writer.into_inner().unwrap(); // Cannot error because we're writing to a Vec
-    postcard::from_bytes(&decompressed).unwrap() // Only errors on OOM or incorrect `serialize`/`deserialize` implementation
+    with_decompressed(x, deserialize).unwrap() // Only errors if the bytes were damaged after being compressed
+}
+
+/// Like [`decompress`] for a chunk of elements, but replaces the contents of `values` so that its allocation is
+/// reused.
+pub fn decompress_into<T>(x: &[u8], values: &mut Vec<T>)
+where
+    T: for<'a> Deserialize<'a>,
+{
+    values.clear();
+    with_decompressed(x, |decompressed| {
+        let mut deserializer = postcard::Deserializer::from_bytes(decompressed);
+        AppendSeq(values).deserialize(&mut deserializer).unwrap() // Only errors on OOM or incorrect `serialize`/`deserialize` implementation
+    })
+    .unwrap() // Only errors if the bytes were damaged after being compressed
+}
+
+/// Like [`decompress`] for a chunk of elements, but returns only its `n`th element: the elements after it are not
+/// deserialized, and the ones before it are dropped as they are, rather than all collected into a `Vec`.
+pub fn decompress_nth<T>(x: &[u8], n: usize) -> T
+where
+    T: for<'a> Deserialize<'a>,
+{
+    with_decompressed(x, |decompressed| {
+        // A chunk is a sequence: its length, then its elements one after the other
+        let (len, mut rest) = take_element::<usize>(decompressed);
+        assert!(n < len, "element {n} is not in a chunk of {len} elements");
+        for _ in 0..n {
+            rest = take_element::<T>(rest).1;
+        }
+        take_element::<T>(rest).0
+    })
+    .unwrap() // Only errors if the bytes were damaged after being compressed
+}
+
+/// Deserializes the value at the start of decompressed bytes, returning it and the bytes after it.
+pub fn take_element<T>(decompressed: &[u8]) -> (T, &[u8])
+where
+    T: for<'a> Deserialize<'a>,
+{
+    postcard::take_from_bytes(decompressed).unwrap() // Only errors on OOM or incorrect `serialize`/`deserialize` implementation
+}
+
+/// Like [`decompress`], but returns an error for bytes which were damaged or do not hold a `T`.
+pub fn try_decompress<T>(x: &[u8]) -> Result<T, Error>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    with_decompressed(x, try_deserialize).map_err(Error::Decompress)?.map_err(Error::Deserialize)
+}
+
+/// Runs `f` on the decompressed bytes of `x`, which are held in a buffer reused by the calls on this thread.
+pub fn with_decompressed<R>(x: &[u8], f: impl FnOnce(&[u8]) -> R) -> std::io::Result<R> {
+    SCRATCH.with(|scratch| match scratch.try_borrow_mut() {
+        Ok(mut decompressed) => {
+            try_decompress_bytes_into(x, &mut decompressed)?;
+            Ok(f(&decompressed))
+        }
+        // Deserializing an element decompressed another chunk, e.g. of a nested collection
+        Err(_) => Ok(f(&try_decompress_bytes(x)?)),
+    })
+}
+
+/// Deserializes a sequence by appending its elements to a vector.
+struct AppendSeq<'v, T>(&'v mut Vec<T>);
+
+impl<'de, T: Deserialize<'de>> DeserializeSeed<'de> for AppendSeq<'_, T> {
+    type Value = ();
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for AppendSeq<'_, T> {
+    type Value = ();
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a sequence")
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        // The length comes from the data, so like `Vec`'s own implementation only trust it up to a megabyte
+        let cautious = 1024 * 1024 / std::mem::size_of::<T>().max(1);
+        self.0.reserve(seq.size_hint().unwrap_or(0).min(cautious));
+        while let Some(value) = seq.next_element()? {
+            self.0.push(value);
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes the output of [`decompress_bytes`], the second half of [`decompress`].
+pub fn deserialize<T>(decompressed: &[u8]) -> T
+where
+    T: for<'a> Deserialize<'a>,
+{
+    postcard::from_bytes(decompressed).unwrap() // Only errors on OOM or incorrect `serialize`/`deserialize` implementation
+}
+
+/// Like [`deserialize`], but returns an error for bytes which do not hold a `T` (e.g. from a damaged chunk).
+pub fn try_deserialize<T>(decompressed: &[u8]) -> postcard::Result<T>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    postcard::from_bytes(decompressed)
+}
+
+pub fn decompress_bytes(x: &[u8]) -> Vec<u8> {
+    try_decompress_bytes(x).unwrap() // Only errors if the bytes were damaged after being compressed
+}
+
+/// Like [`decompress_bytes`], but returns an error for bytes which are not a whole brotli stream.
+pub fn try_decompress_bytes(x: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    try_decompress_bytes_into(x, &mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// Like [`try_decompress_bytes`], but replaces the contents of `decompressed` so that its allocation is reused.
+fn try_decompress_bytes_into(x: &[u8], decompressed: &mut Vec<u8>) -> std::io::Result<()> {
+    decompressed.clear();
+    let mut decompressor_writer = DecompressorWriter::new(decompressed, 4096);
+    decompressor_writer.write_all(x)?;
+    decompressor_writer.flush()?;
+    decompressor_writer.into_inner().map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "brotli stream is cut short"))?;
+    Ok(())
 }
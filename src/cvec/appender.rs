@@ -0,0 +1,118 @@
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use super::cache::{Cache, Uncached};
+use super::CVecInner;
+
+type Shard<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> = CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, Uncached>;
+
+/// Builds a CVec from many threads at once, each pushing into a shard of its own
+///
+/// Pushing into a [`CVecShard`] takes no lock, so threads only contend when they take or hand back a shard. The
+/// shards are merged by [`finish`](Self::finish), which moves their chunks over without recompressing them. The
+/// elements of each shard stay in the order they were pushed, but the shards may end up in any order.
+///
+/// # Examples
+///
+/// ```
+/// use compressed_collections::{CVec, CVecAppender};
+///
+/// let appender = CVecAppender::<u32>::new();
+/// std::thread::scope(|s| {
+///     for t in 0..4 {
+///         let appender = &appender;
+///         s.spawn(move || {
+///             let mut shard = appender.shard();
+///             for i in 0..(16 * 1024) {
+///                 shard.push(t * 16 * 1024 + i);
+///             }
+///         });
+///     }
+/// });
+/// let compressed_stack: CVec<u32> = appender.finish();
+/// let mut values: Vec<u32> = compressed_stack.into_vec();
+/// values.sort();
+/// assert!((0..(64 * 1024)).eq(values));
+/// ```
+pub struct CVecAppender<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> {
+    /// The shards which were handed back
+    shards: Mutex<Vec<Shard<T, CHUNK_ELEMS, COMPRESSION_LEVEL>>>,
+}
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Default for CVecAppender<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+    fn default() -> Self {
+        Self { shards: Mutex::new(Vec::new()) }
+    }
+}
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecAppender<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+    /// Creates an appender without any shards.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns a new, empty shard, whose elements are handed back to the appender when it is dropped.
+    pub fn shard(&self) -> CVecShard<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        CVecShard { appender: self, values: Shard::default() }
+    }
+    /// Merges the shards into a single collection.
+    ///
+    /// The chunks of every shard are moved over as they are, only the partially filled uncompressed buffers of the
+    /// shards are pushed again, after all the chunks.
+    pub fn finish<C: Cache>(self) -> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        let shards = self.shards.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut merged = CVecInner::default();
+        let mut tails = Vec::new();
+        for mut shard in shards {
+            tails.push(std::mem::take(&mut shard.uncompressed_buffer));
+            merged.append(&mut shard);
+        }
+        merged.extend(tails.into_iter().flatten());
+        merged
+    }
+}
+
+/// A shard of a [`CVecAppender`], returned by `CVecAppender::shard`
+pub struct CVecShard<'a, T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> {
+    appender: &'a CVecAppender<T, CHUNK_ELEMS, COMPRESSION_LEVEL>,
+    values: Shard<T, CHUNK_ELEMS, COMPRESSION_LEVEL>,
+}
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecShard<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+    /// Appends an element to the shard, compressing its chunk once it is filled.
+    pub fn push(&mut self, value: T)
+    where
+        T: Serialize,
+    {
+        self.values.push(value);
+    }
+    /// The number of elements pushed to this shard
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+    /// Whether no elements were pushed to this shard
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Extend<T> for CVecShard<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL>
+where
+    T: Serialize,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.values.extend(iter);
+    }
+}
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Drop for CVecShard<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+    fn drop(&mut self) {
+        if !self.values.is_empty() {
+            let values = std::mem::take(&mut self.values);
+            self.appender.shards.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(values);
+        }
+    }
+}
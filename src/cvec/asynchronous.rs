@@ -0,0 +1,188 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+use serde::{Deserialize, Serialize};
+use tokio::task::{JoinError, JoinHandle};
+
+use super::cache::{Cache, Cached};
+use super::inner::Chunk;
+use super::CVecInner;
+use crate::compression::{compress_bytes, decompress_bytes, deserialize};
+
+/// Compresses `values` on tokio's blocking pool, only serializing them on the calling thread.
+fn compress_blocking<T: Serialize>(values: &[T], compression_level: i32) -> impl Future<Output = Box<[u8]>> + 'static {
+    let serialized = postcard::to_stdvec(values).unwrap(); // Only errors on OOM
+    let task = tokio::task::spawn_blocking(move || compress_bytes(&serialized, compression_level));
+    async move { join(task.await) }
+}
+
+/// Decompresses a chunk which is not raw on tokio's blocking pool (reading it back from disk there if it was
+/// spilled), the bytes are left to be deserialized on the calling thread.
+fn decompress_blocking<T>(chunk: &Chunk<T>) -> Option<JoinHandle<Vec<u8>>> {
+    match chunk {
+        Chunk::Compressed(data) => {
+            let data = data.to_vec();
+            Some(tokio::task::spawn_blocking(move || decompress_bytes(&data)))
+        }
+        Chunk::Spilled(spilled) => {
+            let spilled = spilled.clone();
+            Some(tokio::task::spawn_blocking(move || decompress_bytes(&spilled.load())))
+        }
+        Chunk::Raw(_) => None,
+    }
+}
+
+/// Passes on a panic of the blocking task.
+fn join<R>(result: Result<R, JoinError>) -> R {
+    match result.map_err(JoinError::try_into_panic) {
+        Ok(value) => value,
+        Err(Ok(panic)) => std::panic::resume_unwind(panic),
+        Err(Err(err)) => panic!("blocking task was cancelled: {err}"),
+    }
+}
+
+/// Async versions of the methods which (de)compress a chunk, for use from a tokio runtime.
+///
+/// Brotli runs on tokio's blocking pool via `spawn_blocking` so a chunk boundary does not stall the reactor, only
+/// the (much cheaper) serialization happens on the calling task. They must be called from within a tokio runtime.
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// Like [`push`](Self::push), but a filled chunk is compressed on the blocking pool.
+    ///
+    /// Cancel safe: `value` is pushed even if the future is dropped early, the chunk it filled is then held
+    /// uncompressed until the next `push_async` which fills a chunk compresses it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    /// runtime.block_on(async {
+    ///     let mut compressed_stack = CVec::new::<1024, 0>();
+    ///     for i in 0..(16 * 1024) {
+    ///         compressed_stack.push_async(i).await;
+    ///     }
+    ///     assert_eq!(compressed_stack.pop_async().await, Some(16 * 1024 - 1));
+    /// });
+    /// ```
+    pub async fn push_async(&mut self, value: T)
+    where
+        T: Serialize,
+    {
+        self.uncompressed_buffer.push(value);
+        if self.buffer_full() {
+            self.compress_buffer_async().await;
+        }
+    }
+    /// Like [`pop`](Self::pop), but the last chunk is decompressed on the blocking pool once the uncompressed
+    /// buffer is empty.
+    ///
+    /// Cancel safe: the collection is only changed once the chunk has been decompressed.
+    pub async fn pop_async(&mut self) -> Option<T>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        if self.uncompressed_buffer.is_empty() {
+            match self.compressed_storage.last().map(decompress_blocking) {
+                Some(Some(task)) => {
+                    let decompressed = join(task.await);
+                    self.pop_last_chunk();
+                    self.uncompressed_buffer = deserialize(&decompressed);
+                }
+                // A raw chunk, reopened (e.g. by `peek`) or in the raw window, has nothing to decompress
+                Some(None) => self.uncompressed_buffer = self.pop_last_chunk().map(Chunk::into_values).unwrap_or_default(),
+                None => {}
+            }
+        }
+        self.uncompressed_buffer.pop()
+    }
+    /// Returns a [`Stream`] over clones of the elements, bottom to top like [`iter`](Self::iter), which
+    /// decompresses each chunk on the blocking pool.
+    pub fn stream_async(&self) -> CVecStream<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+        CVecStream { inner: self, next_chunk: 0, values: Vec::new().into_iter(), pending: None }
+    }
+    /// Like `compress_buffer`, compressing on the blocking pool.
+    ///
+    /// The buffer is moved into a raw chunk before anything is awaited, so a dropped future leaves that chunk raw
+    /// rather than the buffer full. It is compressed by the next call, which goes on to the raw chunks below the
+    /// window until it reaches a compressed one.
+    async fn compress_buffer_async(&mut self)
+    where
+        T: Serialize,
+    {
+        let len = self.uncompressed_buffer.len();
+        let buffer = Vec::with_capacity(self.chunk_elems());
+        let values = std::mem::replace(&mut self.uncompressed_buffer, buffer);
+        self.push_chunk(Chunk::Raw(values), len);
+        // Same as `compress_window`
+        let window_start = self.compressed_storage.len().saturating_sub(self.raw_window);
+        for chunk_idx in (0..window_start).rev() {
+            match &self.compressed_storage[chunk_idx] {
+                Chunk::Compressed(_) | Chunk::Spilled(_) => break,
+                _ if self.open_chunk == Some(chunk_idx) => {}
+                Chunk::Raw(values) => {
+                    let compressed = compress_blocking(values, COMPRESSION_LEVEL).await;
+                    let bytes = compressed.len();
+                    self.compressed_storage[chunk_idx] = Chunk::Compressed(compressed);
+                    self.spill_pushed(bytes);
+                }
+            }
+        }
+    }
+}
+
+/// Stream over clones of the elements of a CVec, returned by `CVec::stream_async`
+///
+/// Each compressed chunk is decompressed on tokio's blocking pool once the elements of the previous one have been
+/// yielded.
+pub struct CVecStream<'s, T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0, C: Cache = Cached<T, CHUNK_ELEMS>> {
+    inner: &'s CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>,
+    /// The next chunk to read, where the uncompressed buffer counts as the last chunk
+    next_chunk: usize,
+    values: std::vec::IntoIter<T>,
+    /// The chunk being decompressed on the blocking pool
+    pending: Option<JoinHandle<Vec<u8>>>,
+}
+
+// Nothing is pinned in place, the blocking task is polled through its own `Unpin` handle
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Unpin for CVecStream<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Stream for CVecStream<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Clone + for<'a> Deserialize<'a>,
+{
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(value) = this.values.next() {
+                return Poll::Ready(Some(value));
+            }
+            if let Some(pending) = &mut this.pending {
+                let decompressed = join(ready!(Pin::new(pending).poll(cx)));
+                this.pending = None;
+                this.values = deserialize::<Vec<T>>(&decompressed).into_iter();
+                continue;
+            }
+            let inner = this.inner;
+            match inner.compressed_storage.get(this.next_chunk) {
+                Some(Chunk::Raw(values)) => this.values = values.clone().into_iter(),
+                Some(chunk) => this.pending = decompress_blocking(chunk),
+                None if this.next_chunk == inner.compressed_storage.len() => this.values = inner.uncompressed_buffer.clone().into_iter(),
+                None => return Poll::Ready(None),
+            }
+            this.next_chunk += 1;
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let inner = self.inner;
+        let end = |chunks: usize| chunks.checked_sub(1).map_or(0, |last| inner.chunk_ends.get(last).copied().unwrap_or(inner.len()));
+        // The elements of a chunk being decompressed are still to come
+        let read = end(self.next_chunk - usize::from(self.pending.is_some()));
+        let remaining = self.inner.len() - read + self.values.len();
+        (remaining, Some(remaining))
+    }
+}
@@ -0,0 +1,159 @@
+use std::path::PathBuf;
+
+use super::cache::Cache;
+use super::inner::Chunk;
+use super::spill::{spill_chunks, SpillTarget};
+use super::CVecInner;
+
+/// What a CVec does once it uses more memory than its budget, see `CVec::set_memory_budget`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// [`try_push`](crate::CVec::try_push) hands the element back instead of pushing it
+    Reject,
+    /// The decompressed chunks held by the cache are dropped
+    EvictCache,
+    /// Compressed chunks are spilled to a temporary file in this directory, from the bottom up until at most half
+    /// the budget is used, like [`set_spill_budget`](crate::CVec::set_spill_budget) does
+    Spill(PathBuf),
+    /// Compressed chunks are recompressed at this level, the most recently pushed first, like
+    /// [`maintain`](crate::CVec::maintain) does
+    Recompress(i32),
+}
+
+/// The memory budget of a collection, it is not serialized and clones share the spill file
+#[derive(Clone)]
+pub(super) struct MemoryBudget {
+    bytes: usize,
+    policy: OverflowPolicy,
+    /// The memory used by the chunks and the cache when it was last counted, plus the chunks pushed since
+    estimate: usize,
+    /// The policy is applied once `estimate` exceeds this, which is above the budget if applying it did not help
+    threshold: usize,
+    /// Set on the first spill for [`OverflowPolicy::Spill`]
+    spill: Option<SpillTarget>,
+}
+
+/// The memory held by a chunk, see `CVec::memory_bytes`
+pub(super) fn chunk_memory_bytes<T>(chunk: &Chunk<T>) -> usize {
+    match chunk {
+        Chunk::Compressed(data) => data.len(),
+        Chunk::Raw(values) => std::mem::size_of_val(values.as_slice()),
+        Chunk::Spilled(_) => 0,
+    }
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// The memory budget in bytes and what happens once it is exceeded, see
+    /// [`set_memory_budget`](Self::set_memory_budget).
+    pub fn memory_budget(&self) -> Option<(usize, &OverflowPolicy)> {
+        self.budget.as_ref().map(|budget| (budget.bytes, &budget.policy))
+    }
+    /// Sets a budget on the memory used by the collection, as counted by [`memory_bytes`](Self::memory_bytes), and
+    /// what happens once it is exceeded. Without a budget the collection grows until the process runs out of memory.
+    ///
+    /// The budget is checked whenever a chunk is pushed, the policy is then applied until the collection fits
+    /// again or the policy cannot free any more memory. In the latter case it is only applied again once the
+    /// collection has grown by another half of the budget. With [`OverflowPolicy::Reject`] it is checked by every
+    /// [`try_push`](Self::try_push) instead, other ways of adding elements (e.g. `push` or `extend`) are not
+    /// limited.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the policy is [`OverflowPolicy::Recompress`] with a level which is not between 0 and 11.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::{CVec, OverflowPolicy};
+    ///
+    /// let mut compressed_stack = CVec::new::<1024, 0>();
+    /// compressed_stack.set_memory_budget(64 * 1024, OverflowPolicy::Reject);
+    /// let mut pushed = 0u32;
+    /// while compressed_stack.try_push(rand_ish(pushed)).is_ok() {
+    ///     pushed += 1;
+    /// }
+    /// assert!(compressed_stack.memory_bytes() <= 64 * 1024);
+    /// assert_eq!(compressed_stack.len(), pushed as usize);
+    /// # fn rand_ish(x: u32) -> u32 { x.wrapping_mul(2654435761) }
+    /// ```
+    pub fn set_memory_budget(&mut self, bytes: usize, policy: OverflowPolicy) {
+        if let OverflowPolicy::Recompress(level) = policy {
+            assert!((0..=11).contains(&level), "Compression level must be between 0 and 11");
+        }
+        let spill = self.budget.take().filter(|budget| budget.policy == policy).and_then(|budget| budget.spill);
+        self.budget = Some(MemoryBudget { bytes, policy, estimate: 0, threshold: bytes, spill });
+        self.enforce_budget(true);
+    }
+    /// Removes the memory budget.
+    pub fn clear_memory_budget(&mut self) {
+        self.budget = None;
+    }
+    /// An estimate of the memory used by the collection in bytes: the compressed chunks held in memory, plus the
+    /// inline size (`size_of::<T>()`) of every element held uncompressed, in the uncompressed buffer, in raw chunks
+    /// and in the cache. Memory which the elements own on the heap is not counted.
+    pub fn memory_bytes(&self) -> usize {
+        self.chunk_memory_bytes() + self.cache.cached_bytes() + std::mem::size_of_val(self.uncompressed_buffer.as_slice())
+    }
+    /// Whether the collection has a budget with [`OverflowPolicy::Reject`] which pushing an element would exceed.
+    pub(super) fn rejects_push(&mut self) -> bool {
+        let Some(budget) = &self.budget else { return false };
+        if budget.policy != OverflowPolicy::Reject {
+            return false;
+        }
+        let pushed = std::mem::size_of_val(self.uncompressed_buffer.as_slice()) + std::mem::size_of::<T>();
+        if budget.estimate + pushed <= budget.bytes {
+            return false;
+        }
+        let estimate = self.chunk_memory_bytes() + self.cache.cached_bytes();
+        let Some(budget) = &mut self.budget else { return false };
+        budget.estimate = estimate;
+        estimate + pushed > budget.bytes
+    }
+    /// The memory used by the chunks, see [`memory_bytes`](Self::memory_bytes).
+    fn chunk_memory_bytes(&self) -> usize {
+        self.compressed_storage.iter().map(chunk_memory_bytes).sum()
+    }
+    /// Counts a newly pushed chunk towards the memory budget, applying the policy if the budget is exceeded.
+    pub(super) fn budget_pushed(&mut self, chunk_bytes: usize) {
+        if let Some(budget) = &mut self.budget {
+            budget.estimate += chunk_bytes;
+            self.enforce_budget(false);
+        }
+    }
+    /// Applies the policy if the budget (or if `recount`, the memory counted again first) exceeds the threshold.
+    fn enforce_budget(&mut self, recount: bool) {
+        let buffer_bytes = std::mem::size_of_val(self.uncompressed_buffer.as_slice());
+        match &self.budget {
+            Some(budget) if budget.policy == OverflowPolicy::Reject => return,
+            Some(budget) if recount || budget.estimate + buffer_bytes > budget.threshold => {}
+            _ => return,
+        }
+        let mut used = self.memory_bytes();
+        let Some(budget) = &mut self.budget else { return };
+        let bytes = budget.bytes;
+        if used > bytes {
+            match budget.policy.clone() {
+                OverflowPolicy::Reject => {}
+                OverflowPolicy::EvictCache => self.cache.kill_all(),
+                OverflowPolicy::Spill(dir) => {
+                    let target = budget.spill.get_or_insert_with(|| SpillTarget::dir(dir));
+                    spill_chunks(&mut self.compressed_storage, target, used - bytes / 2);
+                }
+                OverflowPolicy::Recompress(level) => {
+                    for chunk_idx in (0..self.compressed_storage.len()).rev() {
+                        if used <= bytes {
+                            break;
+                        }
+                        let before = chunk_memory_bytes(&self.compressed_storage[chunk_idx]);
+                        self.recompress_step(chunk_idx, level);
+                        used -= before - chunk_memory_bytes(&self.compressed_storage[chunk_idx]);
+                    }
+                }
+            }
+            used = self.memory_bytes();
+        }
+        let Some(budget) = &mut self.budget else { return };
+        budget.estimate = used - buffer_bytes;
+        budget.threshold = if used > budget.bytes { used + budget.bytes / 2 } else { budget.bytes };
+    }
+}
@@ -1,11 +1,55 @@
-use std::cell::{RefCell, Ref};
+use std::cell::{OnceCell, RefCell, Ref};
+use std::collections::{HashMap, HashSet};
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex, RwLock, RwLockWriteGuard};
 
 use serde::{Deserialize, de::Error};
 
-use crate::compression::decompress;
+use crate::compression::{decompress, decompress_into};
+use super::eviction::{EvictionPolicy, Lru};
+use super::spill::SpilledChunk;
+
+/// The compressed bytes of a chunk handed to a cache. The bytes of a spilled chunk are only read back from disk
+/// once the cache dereferences them, on a miss: whether it misses can only be known by the cache itself, as other
+/// threads may evict its lines at any time.
+pub struct ChunkBytes<'a> {
+    data: Result<&'a [u8], &'a SpilledChunk>,
+    loaded: OnceCell<Box<[u8]>>,
+}
+
+impl<'a> ChunkBytes<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data: Ok(data), loaded: OnceCell::new() }
+    }
+    pub(super) fn spilled(spilled: &'a SpilledChunk) -> Self {
+        Self { data: Err(spilled), loaded: OnceCell::new() }
+    }
+}
+
+impl std::ops::Deref for ChunkBytes<'_> {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        match self.data {
+            Ok(data) => data,
+            Err(spilled) => self.loaded.get_or_init(|| spilled.load()),
+        }
+    }
+}
 
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
-pub(super) struct CacheLine<T, const CHUNK_ELEMS: usize>(Box<[T; CHUNK_ELEMS]>);
+pub(super) struct CacheLine<T, const CHUNK_ELEMS: usize>(Vec<T>);
+
+impl<T, const CHUNK_ELEMS: usize> CacheLine<T, CHUNK_ELEMS> {
+    /// Checks the number of elements of a decompressed chunk.
+    fn check_len(len: usize) -> Result<(), String> {
+        // A chunk size of 0 means it is chosen at runtime, and not known here
+        if len == 0 || (CHUNK_ELEMS != 0 && len > CHUNK_ELEMS) {
+            return Err(format!("between 1 and {} elements", CHUNK_ELEMS));
+        }
+        Ok(())
+    }
+}
 
 #[derive(Default)]
 pub struct Uncached;
@@ -22,9 +66,15 @@ impl<T, const CHUNK_ELEMS: usize> Default for Cached<T, CHUNK_ELEMS> {
 }
 
 impl<T, const CHUNK_ELEMS: usize> Cached<T, CHUNK_ELEMS> {
+    /// Decompresses the chunk at `index` into the line, reusing the allocation of the chunk held before.
     pub fn fill_cache(&mut self, index: usize, data: &[u8]) where for<'a> T: Deserialize<'a> {
+        let mut values = self.data.take().map(|line| line.0).unwrap_or_default();
+        decompress_into(data, &mut values);
+        if let Err(expected) = CacheLine::<T, CHUNK_ELEMS>::check_len(values.len()) {
+            panic!("decompressed chunk holds {} elements rather than {expected}", values.len());
+        }
         self.index = index;
-        self.data = Some(decompress(data));
+        self.data = Some(CacheLine(values));
     }
 }
 
@@ -36,29 +86,295 @@ impl<T, const CHUNK_ELEMS: usize> Default for RcCached<T, CHUNK_ELEMS> {
     }
 }
 
+/// Keeps the chunk read last decompressed behind a lock, so that the collection can be read through a shared
+/// reference from several threads at once.
+pub struct SyncCached<T, const CHUNK_ELEMS: usize>(RwLock<Cached<T, CHUNK_ELEMS>>);
+
+impl<T, const CHUNK_ELEMS: usize> Default for SyncCached<T, CHUNK_ELEMS> {
+    fn default() -> Self {
+        Self(RwLock::new(Default::default()))
+    }
+}
+
+impl<T, const CHUNK_ELEMS: usize> SyncCached<T, CHUNK_ELEMS> {
+    /// A thread which panicked while filling the cache may have left it inconsistent, so it is emptied.
+    fn write(&self) -> RwLockWriteGuard<'_, Cached<T, CHUNK_ELEMS>> {
+        self.0.write().unwrap_or_else(|poisoned| {
+            self.0.clear_poison();
+            let mut cache = poisoned.into_inner();
+            cache.kill_all();
+            cache
+        })
+    }
+}
+
+/// Keeps the chunk read last decompressed, and once chunks are read one after the other decompresses the next one on
+/// a background thread so that scans do not stall on decompression.
+///
+/// The thread is started by the first prefetch and lives as long as the cache, chunks are sent to it one at a time.
+pub struct Prefetching<T, const CHUNK_ELEMS: usize> {
+    line: Cached<T, CHUNK_ELEMS>,
+    /// Sends chunks to the background thread, which exits once this is dropped
+    pub(super) worker: Option<Sender<PrefetchRequest<T, CHUNK_ELEMS>>>,
+    /// The chunk being decompressed in the background
+    pub(super) pending: Option<(usize, Arc<PrefetchSlot<T, CHUNK_ELEMS>>)>,
+}
+
+impl<T, const CHUNK_ELEMS: usize> Default for Prefetching<T, CHUNK_ELEMS> {
+    fn default() -> Self {
+        Self { line: Default::default(), worker: None, pending: None }
+    }
+}
+
+/// The compressed bytes of a chunk to prefetch, and where to put it once decompressed
+type PrefetchRequest<T, const CHUNK_ELEMS: usize> = (Box<[u8]>, Arc<PrefetchSlot<T, CHUNK_ELEMS>>);
+
+/// A chunk being decompressed by the background thread of a [`Prefetching`] cache, it is an error if that panicked
+pub(super) struct PrefetchSlot<T, const CHUNK_ELEMS: usize> {
+    line: Mutex<Option<std::thread::Result<CacheLine<T, CHUNK_ELEMS>>>>,
+    done: Condvar,
+}
+
+impl<T, const CHUNK_ELEMS: usize> PrefetchSlot<T, CHUNK_ELEMS> {
+    /// Waits for the background thread to get to this chunk.
+    fn wait(&self) -> std::thread::Result<CacheLine<T, CHUNK_ELEMS>> {
+        let line = self.line.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut line = self.done.wait_while(line, |line| line.is_none()).unwrap_or_else(|poisoned| poisoned.into_inner());
+        line.take().unwrap()
+    }
+}
+
+impl<T: Send + 'static, const CHUNK_ELEMS: usize> Prefetching<T, CHUNK_ELEMS>
+where for<'a> T: Deserialize<'a>
+{
+    /// Starts the background thread, which decompresses the chunks it is sent until the cache is dropped.
+    fn spawn_worker() -> Sender<PrefetchRequest<T, CHUNK_ELEMS>> {
+        let (worker, requests) = mpsc::channel::<PrefetchRequest<T, CHUNK_ELEMS>>();
+        std::thread::spawn(move || {
+            for (data, slot) in requests {
+                // The prefetch was dropped in the meantime, e.g. as the chunk changed
+                if Arc::strong_count(&slot) == 1 {
+                    continue;
+                }
+                let line = std::panic::catch_unwind(AssertUnwindSafe(|| decompress(&data)));
+                *slot.line.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(line);
+                slot.done.notify_one();
+            }
+        });
+        worker
+    }
+}
+
+/// Keeps every chunk which is read through a shared reference decompressed until the collection is next mutated.
+///
+/// Unlike the other caches this can hand out plain references from `&self`, which is what
+/// [`Index`](std::ops::Index) requires, at the cost of memory growing with the number of chunks read.
+pub struct Memoized<T> {
+    lines: OnceCell<Vec<OnceCell<Box<[T]>>>>,
+}
+
+impl<T> Default for Memoized<T> {
+    fn default() -> Self {
+        Self { lines: OnceCell::new() }
+    }
+}
+
+/// Keeps as many decompressed chunks as fit in a memory budget, evicting them in the order chosen by the policy
+/// `P` (the least recently used first by default).
+///
+/// Only the inline size of the elements (`size_of::<T>()`) is counted, not memory they own on the heap. The chunk
+/// read last is kept even if it alone is over budget, and pinned chunks are never evicted.
+pub struct Budgeted<T, P: EvictionPolicy = Lru> {
+    budget: usize,
+    used: usize,
+    pub(super) lines: HashMap<usize, Box<[T]>>,
+    policy: P,
+    /// The indices of the chunks which stay decompressed once read, whatever the budget
+    pinned: HashSet<usize>,
+}
+
+/// The default budget of a [`Budgeted`] cache, in bytes
+pub const DEFAULT_CACHE_BUDGET: usize = 10 * 1024 * 1024;
+
+impl<T, P: EvictionPolicy> Default for Budgeted<T, P> {
+    fn default() -> Self {
+        Self { budget: DEFAULT_CACHE_BUDGET, used: 0, lines: HashMap::new(), policy: P::default(), pinned: HashSet::new() }
+    }
+}
+
+impl<T, P: EvictionPolicy> Budgeted<T, P> {
+    fn line_bytes(line: &[T]) -> usize {
+        std::mem::size_of_val(line)
+    }
+    /// Evicts the lines chosen by the policy, other than `keep` and pinned ones, until the budget is met.
+    fn evict(&mut self, keep: Option<usize>) {
+        while self.used > self.budget {
+            let pinned = &self.pinned;
+            let Some(victim) = self.policy.victim(|index| Some(index) != keep && !pinned.contains(&index)) else { break };
+            self.remove(victim);
+        }
+    }
+    fn remove(&mut self, index: usize) {
+        if let Some(line) = self.lines.remove(&index) {
+            self.used -= Self::line_bytes(&line);
+            self.policy.remove(index);
+        }
+    }
+}
+
 pub trait Cache: Default {
     fn is_cached(&self, _index: usize) -> bool { false }
     fn kill_all(&mut self) {}
+    /// Forget the chunk at `index`, it has been removed or is about to change
+    fn kill(&mut self, index: usize) {
+        if self.is_cached(index) {
+            self.kill_all();
+        }
+    }
+    /// The collection now has `chunks` compressed chunks
+    fn resize(&mut self, _chunks: usize) {}
+    /// The inline size (`size_of::<T>()` per element) of the decompressed chunks held
+    fn cached_bytes(&self) -> usize { 0 }
 }
 
 impl Cache for Uncached {}
 
-impl<'c, T, const CHUNK_ELEMS: usize> Cache for Cached<T, CHUNK_ELEMS> {
+impl<T, const CHUNK_ELEMS: usize> Cache for Cached<T, CHUNK_ELEMS> {
     fn is_cached(&self, index: usize) -> bool {
         self.data.is_some() && self.index == index
     }
     fn kill_all(&mut self) {
         *self = Default::default();
     }
+    /// Drops the elements of the line but keeps its allocation for the next chunk decompressed, as a chunk is
+    /// usually changed in place (e.g. with `get_mut`) and then read again.
+    fn kill(&mut self, index: usize) {
+        if let Some(line) = self.data.as_mut().filter(|_| self.index == index) {
+            line.0.clear();
+            self.index = usize::MAX;
+        }
+    }
+    /// Counts the capacity of the line, which is kept when it is killed.
+    fn cached_bytes(&self) -> usize {
+        self.data.as_ref().map_or(0, |line| line.0.capacity() * std::mem::size_of::<T>())
+    }
 }
 
-impl<'c, T, const CHUNK_ELEMS: usize> Cache for RcCached<T, CHUNK_ELEMS> {
+impl<T, const CHUNK_ELEMS: usize> Cache for RcCached<T, CHUNK_ELEMS> {
     fn is_cached(&self, index: usize) -> bool {
         self.0.borrow().is_cached(index)
     }
     fn kill_all(&mut self) {
         self.0.borrow_mut().kill_all();
     }
+    fn kill(&mut self, index: usize) {
+        self.0.get_mut().kill(index);
+    }
+    fn cached_bytes(&self) -> usize {
+        self.0.borrow().cached_bytes()
+    }
+}
+
+impl<T, const CHUNK_ELEMS: usize> Cache for SyncCached<T, CHUNK_ELEMS> {
+    fn is_cached(&self, index: usize) -> bool {
+        match self.0.read() {
+            Ok(cache) => cache.is_cached(index),
+            Err(_) => false,
+        }
+    }
+    fn kill_all(&mut self) {
+        self.0.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()).kill_all();
+        self.0.clear_poison();
+    }
+    fn kill(&mut self, index: usize) {
+        if let Ok(cache) = self.0.get_mut() {
+            cache.kill(index);
+        } else {
+            self.kill_all();
+        }
+    }
+    fn cached_bytes(&self) -> usize {
+        match self.0.read() {
+            Ok(cache) => cache.cached_bytes(),
+            Err(_) => 0,
+        }
+    }
+}
+
+impl<T, const CHUNK_ELEMS: usize> Cache for Prefetching<T, CHUNK_ELEMS> {
+    fn is_cached(&self, index: usize) -> bool {
+        self.line.is_cached(index)
+    }
+    fn kill_all(&mut self) {
+        // The background thread is kept for the next prefetch
+        self.line.kill_all();
+        self.pending = None;
+    }
+    fn kill(&mut self, index: usize) {
+        self.line.kill(index);
+        // A prefetch of the old bytes is left to finish on its own
+        if self.pending.as_ref().is_some_and(|(pending, _)| *pending == index) {
+            self.pending = None;
+        }
+    }
+    fn resize(&mut self, chunks: usize) {
+        if self.line.index >= chunks {
+            self.line.kill_all();
+        }
+        if self.pending.as_ref().is_some_and(|(pending, _)| *pending >= chunks) {
+            self.pending = None;
+        }
+    }
+    fn cached_bytes(&self) -> usize {
+        self.line.cached_bytes()
+    }
+}
+
+impl<T, P: EvictionPolicy> Cache for Budgeted<T, P> {
+    fn is_cached(&self, index: usize) -> bool {
+        self.lines.contains_key(&index)
+    }
+    fn kill_all(&mut self) {
+        self.lines.clear();
+        self.used = 0;
+        self.policy = P::default();
+    }
+    fn kill(&mut self, index: usize) {
+        self.remove(index);
+    }
+    fn resize(&mut self, chunks: usize) {
+        let removed: Vec<usize> = self.lines.keys().copied().filter(|&index| index >= chunks).collect();
+        for index in removed {
+            self.remove(index);
+        }
+        self.pinned.retain(|&index| index < chunks);
+    }
+    fn cached_bytes(&self) -> usize {
+        self.used
+    }
+}
+
+impl<T> Cache for Memoized<T> {
+    fn is_cached(&self, index: usize) -> bool {
+        self.lines.get().and_then(|lines| lines.get(index)).is_some_and(|line| line.get().is_some())
+    }
+    fn kill_all(&mut self) {
+        *self = Default::default();
+    }
+    fn kill(&mut self, index: usize) {
+        if let Some(line) = self.lines.get_mut().and_then(|lines| lines.get_mut(index)) {
+            line.take();
+        }
+    }
+    fn resize(&mut self, chunks: usize) {
+        if let Some(lines) = self.lines.get_mut() {
+            lines.resize_with(chunks, OnceCell::new);
+        }
+    }
+    fn cached_bytes(&self) -> usize {
+        let lines = self.lines.get().map_or(&[][..], Vec::as_slice);
+        lines.iter().filter_map(OnceCell::get).map(|line| std::mem::size_of_val(&**line)).sum()
+    }
 }
 
 // pub trait CacheAccess<'c, T> {
@@ -106,24 +422,23 @@ impl<'de, T, const CHUNK_ELEMS: usize> Deserialize<'de> for CacheLine<T, CHUNK_E
 where T: for<'a> Deserialize<'a> {
     fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         let data: Vec<T> = Deserialize::deserialize(deserializer)?;
-        match data.into_boxed_slice().try_into() {
-            Ok(data) => Ok(Self(data)),
-            Err(err) => {
-                let expected: &str = &format!("expected {} elements", CHUNK_ELEMS);
-                Err(Error::invalid_length(err.len(), &expected))
-            }
+        if let Err(expected) = Self::check_len(data.len()) {
+            return Err(Error::invalid_length(data.len(), &expected.as_str()));
         }
+        Ok(Self(data))
     }
 }
 
 pub trait CacheAccess<T> {
-    fn get_compressed(&mut self, index: usize, offset: usize, data: &[u8]) -> &T;
+    fn get_compressed(&mut self, index: usize, offset: usize, data: &ChunkBytes<'_>) -> &T;
+    /// Called before chunk `index` is read, with the bytes of the chunk after it, for caches which read ahead.
+    fn prefetch(&mut self, _index: usize, _next: &[u8]) {}
 }
 
 impl<T, const CHUNK_ELEMS: usize> CacheAccess<T> for Cached<T, CHUNK_ELEMS>
 where for<'a> T: Deserialize<'a>
 {
-    fn get_compressed(&mut self, index: usize, offset: usize, data: &[u8]) -> &T {
+    fn get_compressed(&mut self, index: usize, offset: usize, data: &ChunkBytes<'_>) -> &T {
         if !self.is_cached(index) {
             self.fill_cache(index, data);
         }
@@ -131,25 +446,154 @@ where for<'a> T: Deserialize<'a>
     }
 }
 
+impl<T, P: EvictionPolicy> CacheAccess<T> for Budgeted<T, P>
+where for<'a> T: Deserialize<'a>
+{
+    fn get_compressed(&mut self, index: usize, offset: usize, data: &ChunkBytes<'_>) -> &T {
+        if self.lines.contains_key(&index) {
+            self.policy.touch(index);
+        } else {
+            let data = decompress::<Vec<T>>(data).into_boxed_slice();
+            self.used += Self::line_bytes(&data);
+            self.lines.insert(index, data);
+            self.policy.insert(index);
+            self.evict(Some(index));
+        }
+        &self.lines[&index][offset]
+    }
+}
+
+impl<T: Send + 'static, const CHUNK_ELEMS: usize> CacheAccess<T> for Prefetching<T, CHUNK_ELEMS>
+where for<'a> T: Deserialize<'a>
+{
+    fn get_compressed(&mut self, index: usize, offset: usize, data: &ChunkBytes<'_>) -> &T {
+        if !self.line.is_cached(index) {
+            match self.pending.take_if(|(pending, _)| *pending == index) {
+                Some((_, slot)) => {
+                    self.line.index = index;
+                    // Should decompressing in the background have panicked, decompress here to surface the error
+                    self.line.data = Some(slot.wait().unwrap_or_else(|_| decompress(data)));
+                }
+                None => self.line.fill_cache(index, data),
+            }
+        }
+        &self.line.data.as_ref().unwrap().0[offset]
+    }
+    fn prefetch(&mut self, index: usize, next: &[u8]) {
+        // The chunk read last is the one before this one
+        let sequential = self.line.data.is_some() && self.line.index.checked_add(1) == Some(index);
+        let next_index = index + 1;
+        if sequential && self.pending.as_ref().is_none_or(|(pending, _)| *pending != next_index) {
+            let slot = Arc::new(PrefetchSlot { line: Mutex::new(None), done: Condvar::new() });
+            let worker = self.worker.get_or_insert_with(Self::spawn_worker);
+            // The chunk is only read on demand should the background thread be gone
+            self.pending = worker.send((next.into(), Arc::clone(&slot))).is_ok().then_some((next_index, slot));
+        }
+    }
+}
+
+/// A cache which can keep chunks decompressed regardless of its eviction policy
+///
+/// Pins are by chunk index, a pinned chunk which is changed is decompressed again the next time it is read.
+pub trait CachePin {
+    fn pin(&mut self, index: usize);
+    fn unpin(&mut self, index: usize);
+}
+
+impl<T, P: EvictionPolicy> CachePin for Budgeted<T, P> {
+    fn pin(&mut self, index: usize) {
+        self.pinned.insert(index);
+    }
+    fn unpin(&mut self, index: usize) {
+        self.pinned.remove(&index);
+        self.evict(None);
+    }
+}
+
+/// A cache whose memory budget can be changed at runtime
+pub trait CacheBudget {
+    /// The budget in bytes
+    fn budget(&self) -> usize;
+    /// Sets the budget in bytes, evicting lines straight away if they no longer fit
+    fn set_budget(&mut self, bytes: usize);
+}
+
+impl<T, P: EvictionPolicy> CacheBudget for Budgeted<T, P> {
+    fn budget(&self) -> usize {
+        self.budget
+    }
+    fn set_budget(&mut self, bytes: usize) {
+        self.budget = bytes;
+        self.evict(None);
+    }
+}
+
+pub trait MemoCacheAccess<T> {
+    fn get_memoized(&self, chunks: usize, index: usize, offset: usize, data: &ChunkBytes<'_>) -> &T;
+}
+
+impl<T> MemoCacheAccess<T> for Memoized<T>
+where for<'a> T: Deserialize<'a>
+{
+    fn get_memoized(&self, chunks: usize, index: usize, offset: usize, data: &ChunkBytes<'_>) -> &T {
+        let lines = self.lines.get_or_init(|| (0..chunks).map(|_| OnceCell::new()).collect());
+        let line = lines[index].get_or_init(|| decompress::<Vec<T>>(data).into_boxed_slice());
+        &line[offset]
+    }
+}
+
 pub trait RcCacheAccess<T, const CHUNK_ELEMS: usize> {
-    fn get_compressed<'e>(&'e self, index: usize, offset: usize, data: &'e [u8]) -> Entry<'e, T, CHUNK_ELEMS>;
+    fn get_compressed<'e>(&'e self, index: usize, offset: usize, data: &'e ChunkBytes<'e>) -> Entry<'e, T, CHUNK_ELEMS>;
 }
 
 impl<T, const CHUNK_ELEMS: usize> RcCacheAccess<T, CHUNK_ELEMS> for RcCached<T, CHUNK_ELEMS>
 where for<'a> T: Deserialize<'a>
 {
-    fn get_compressed<'e>(&'e self, index: usize, offset: usize, data: &'e [u8]) -> Entry<'e, T, CHUNK_ELEMS> {
+    fn get_compressed<'e>(&'e self, index: usize, offset: usize, data: &'e ChunkBytes<'e>) -> Entry<'e, T, CHUNK_ELEMS> {
         Entry::Compressed { cache: self, index, offset, data }
     }
 }
 
+/// Caches which can read a clone of an element through a shared reference
+pub trait SharedCacheAccess<T> {
+    fn get_shared(&self, index: usize, offset: usize, data: &ChunkBytes<'_>) -> T;
+}
+
+impl<T: Clone, const CHUNK_ELEMS: usize> SharedCacheAccess<T> for RcCached<T, CHUNK_ELEMS>
+where for<'a> T: Deserialize<'a>
+{
+    fn get_shared(&self, index: usize, offset: usize, data: &ChunkBytes<'_>) -> T {
+        self.get_compressed(index, offset, data).borrow().clone()
+    }
+}
+
+impl<T: Clone, const CHUNK_ELEMS: usize> SharedCacheAccess<T> for SyncCached<T, CHUNK_ELEMS>
+where for<'a> T: Deserialize<'a>
+{
+    fn get_shared(&self, index: usize, offset: usize, data: &ChunkBytes<'_>) -> T {
+        if let Ok(cache) = self.0.read() {
+            if cache.is_cached(index) {
+                return cache.data.as_ref().unwrap().0[offset].clone();
+            }
+        }
+        let mut cache = self.write();
+        // Another thread may have filled it in the meantime
+        if !cache.is_cached(index) {
+            cache.fill_cache(index, data);
+        }
+        cache.data.as_ref().unwrap().0[offset].clone()
+    }
+}
+
 pub enum Entry<'e, T, const CHUNK_ELEMS: usize> {
     Compressed {
         cache: &'e RcCached<T, CHUNK_ELEMS>,
         index: usize,
         offset: usize,
-        data: &'e [u8],
+        data: &'e ChunkBytes<'e>,
     },
+    // Only handed out by the commented out `CVec::get_rc`
+    #[allow(dead_code)]
     Uncompressed(&'e T),
 }
 
@@ -167,7 +611,7 @@ impl<'e, T, const CHUNK_ELEMS: usize> Entry<'e, T, CHUNK_ELEMS> {
                 }
                 EntryRef::Compressed {
                     cache: cache.0.borrow(),
-                    offset: offset,
+                    offset,
                 }
             }
             Entry::Uncompressed(data) => EntryRef::Uncompressed(data),
@@ -189,7 +633,7 @@ impl<'e, T, const CHUNK_ELEMS: usize> std::ops::Deref for EntryRef<'e, T, CHUNK_
         match self {
             EntryRef::Compressed { cache, offset } =>
                 &cache.data.as_ref().unwrap().0[*offset],
-            EntryRef::Uncompressed(data) => *data,
+            EntryRef::Uncompressed(data) => data,
         }
     }
 }
@@ -0,0 +1,160 @@
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use arrow_array::builder::PrimitiveBuilder;
+use arrow_array::{Array, ArrowNativeTypeOp, ArrowPrimitiveType, PrimitiveArray, RecordBatch};
+use arrow_schema::{ArrowError, Field, Schema, SchemaRef};
+use serde::{Deserialize, Serialize};
+
+use super::cache::Cache;
+use super::CVecInner;
+
+/// Conversions between a collection of primitive elements and Arrow arrays, IPC streams and Parquet files.
+///
+/// The element type is given by the Arrow type `A` whose native type `T` is, e.g.
+/// [`Int64Type`](arrow_array::types::Int64Type) for a `CVec<i64>`. Arrow has no nulls in a CVec, so arrays with
+/// nulls are rejected on import. Exports write one record batch per chunk, so only one chunk is decompressed at a
+/// time.
+impl<T: ArrowNativeTypeOp, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// Copies the elements into an Arrow array.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use arrow_array::types::UInt32Type;
+    /// use compressed_collections::CVec;
+    ///
+    /// let compressed_stack: CVec<u32> = (0..(16 * 1024)).collect();
+    /// let array = compressed_stack.to_arrow::<UInt32Type>();
+    /// assert_eq!(array.len(), 16 * 1024);
+    /// assert_eq!(CVec::from_arrow(&array).unwrap(), compressed_stack);
+    /// ```
+    pub fn to_arrow<A>(&self) -> PrimitiveArray<A>
+    where
+        A: ArrowPrimitiveType<Native = T>,
+        T: for<'a> Deserialize<'a>,
+    {
+        let mut builder = PrimitiveBuilder::with_capacity(self.len());
+        for chunk in self.chunks() {
+            builder.append_slice(&chunk);
+        }
+        builder.finish()
+    }
+    /// Creates a collection holding the elements of an Arrow array, failing if the array has nulls.
+    pub fn from_arrow<A>(array: &PrimitiveArray<A>) -> Result<Self, ArrowError>
+    where
+        A: ArrowPrimitiveType<Native = T>,
+        T: Serialize,
+    {
+        let mut cvec = Self::default();
+        cvec.extend_from_arrow(array)?;
+        Ok(cvec)
+    }
+    /// Writes the elements as an Arrow IPC stream of record batches with a single non-nullable column `name`,
+    /// returning the writer.
+    pub fn write_arrow_ipc<A, W>(&self, writer: W, name: &str) -> Result<W, ArrowError>
+    where
+        A: ArrowPrimitiveType<Native = T>,
+        T: for<'a> Deserialize<'a>,
+        W: Write,
+    {
+        let schema = column_schema::<A>(name);
+        let mut writer = arrow_ipc::writer::StreamWriter::try_new(writer, &schema)?;
+        for batch in self.record_batches::<A>(&schema) {
+            writer.write(&batch?)?;
+        }
+        writer.finish()?;
+        writer.into_inner()
+    }
+    /// Reads the first column of an Arrow IPC stream, which must be of type `A` and have no nulls.
+    pub fn read_arrow_ipc<A, R>(reader: R) -> Result<Self, ArrowError>
+    where
+        A: ArrowPrimitiveType<Native = T>,
+        T: Serialize,
+        R: Read,
+    {
+        Self::from_record_batches::<A>(arrow_ipc::reader::StreamReader::try_new(reader, None)?)
+    }
+    /// Writes the elements as a Parquet file with a single non-nullable column `name`, returning the writer.
+    #[cfg(feature = "parquet")]
+    pub fn write_parquet<A, W>(&self, writer: W, name: &str) -> Result<W, parquet::errors::ParquetError>
+    where
+        A: ArrowPrimitiveType<Native = T>,
+        T: for<'a> Deserialize<'a>,
+        W: Write + Send,
+    {
+        let schema = column_schema::<A>(name);
+        let mut writer = parquet::arrow::ArrowWriter::try_new(writer, schema.clone(), None)?;
+        for batch in self.record_batches::<A>(&schema) {
+            writer.write(&batch?)?;
+        }
+        writer.into_inner()
+    }
+    /// Reads the first column of a Parquet file, which must be of type `A` and have no nulls.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use arrow_array::types::Float64Type;
+    /// use compressed_collections::CVec;
+    ///
+    /// let path = std::env::temp_dir().join(format!("read_parquet_example-{}.parquet", std::process::id()));
+    /// let compressed_stack: CVec<f64> = (0..(16 * 1024)).map(f64::from).collect();
+    /// compressed_stack.write_parquet::<Float64Type, _>(std::fs::File::create(&path).unwrap(), "values").unwrap();
+    /// let read_back: CVec<f64> = CVec::read_parquet::<Float64Type, _>(std::fs::File::open(&path).unwrap()).unwrap();
+    /// assert_eq!(read_back, compressed_stack);
+    /// std::fs::remove_file(&path).unwrap();
+    /// ```
+    #[cfg(feature = "parquet")]
+    pub fn read_parquet<A, R>(reader: R) -> Result<Self, parquet::errors::ParquetError>
+    where
+        A: ArrowPrimitiveType<Native = T>,
+        T: Serialize,
+        R: parquet::file::reader::ChunkReader + 'static,
+    {
+        let batches = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(reader)?.build()?;
+        Ok(Self::from_record_batches::<A>(batches)?)
+    }
+    /// One record batch per chunk, skipping empty ones.
+    fn record_batches<'s, A>(&'s self, schema: &'s SchemaRef) -> impl Iterator<Item = Result<RecordBatch, ArrowError>> + 's
+    where
+        A: ArrowPrimitiveType<Native = T>,
+        T: for<'a> Deserialize<'a>,
+    {
+        self.chunks().filter(|chunk| !chunk.is_empty()).map(|chunk| {
+            let array = PrimitiveArray::<A>::from_iter_values(chunk.iter().copied());
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(array)])
+        })
+    }
+    fn from_record_batches<A>(batches: impl IntoIterator<Item = Result<RecordBatch, ArrowError>>) -> Result<Self, ArrowError>
+    where
+        A: ArrowPrimitiveType<Native = T>,
+        T: Serialize,
+    {
+        let mut cvec = Self::default();
+        for batch in batches {
+            let batch = batch?;
+            let column = batch.columns().first().ok_or_else(|| ArrowError::SchemaError("there are no columns".into()))?;
+            let array = column.as_any().downcast_ref::<PrimitiveArray<A>>().ok_or_else(|| {
+                ArrowError::SchemaError(format!("the first column is of type {} rather than {}", column.data_type(), A::DATA_TYPE))
+            })?;
+            cvec.extend_from_arrow(array)?;
+        }
+        Ok(cvec)
+    }
+    fn extend_from_arrow<A>(&mut self, array: &PrimitiveArray<A>) -> Result<(), ArrowError>
+    where
+        A: ArrowPrimitiveType<Native = T>,
+        T: Serialize,
+    {
+        if array.null_count() != 0 {
+            return Err(ArrowError::InvalidArgumentError(format!("the array has {} nulls", array.null_count())));
+        }
+        self.extend_from_slice(array.values());
+        Ok(())
+    }
+}
+
+fn column_schema<A: ArrowPrimitiveType>(name: &str) -> SchemaRef {
+    Arc::new(Schema::new(vec![Field::new(name, A::DATA_TYPE, false)]))
+}
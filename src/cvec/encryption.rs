@@ -0,0 +1,201 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Seek, SeekFrom};
+use std::path::Path;
+
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+use super::cache::Cache;
+use super::inner::Chunk;
+use super::persist::{write_parts, ChunkEntry, TailEntry, HEADER_LEN};
+use super::stream::{invalid_data, read_exact_bytes};
+use super::CVecInner;
+use crate::compression::compress;
+use crate::format::{crc32, CODEC_SEALED};
+
+/// The byte length of the nonce in front of a sealed chunk
+const NONCE_LEN: usize = 24;
+/// The byte length of the authentication tag at the end of a sealed chunk
+const TAG_LEN: usize = 16;
+/// The tail of an encrypted container, whose elements are all in chunks: postcard encodes an empty sequence as its
+/// length 0
+const EMPTY_TAIL: [u8; 1] = [0];
+
+/// A 256-bit key which chunks are encrypted and authenticated with (using XChaCha20-Poly1305) before they are
+/// written to disk, see `CVec::save_encrypted` and `CVec::set_spill_key`
+///
+/// The key cannot be read back out, keep the bytes it was created from somewhere safe: data encrypted with it is
+/// lost without them.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    cipher: XChaCha20Poly1305,
+}
+
+impl EncryptionKey {
+    /// Creates a key from its 32 bytes.
+    pub fn new(key: [u8; 32]) -> Self {
+        Self { cipher: XChaCha20Poly1305::new(&key.into()) }
+    }
+    /// Returns 32 random bytes from the operating system's generator, to create a new key from.
+    pub fn random_bytes() -> [u8; 32] {
+        XChaCha20Poly1305::generate_key(&mut OsRng).into()
+    }
+    /// Encrypts `data` under a random nonce, binding it to `aad`. Returns the nonce followed by the ciphertext and
+    /// its tag.
+    pub(super) fn seal(&self, data: &[u8], aad: &[u8]) -> Box<[u8]> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self.cipher.encrypt(&nonce, Payload { msg: data, aad }).unwrap(); // Only errors for data longer than 256 GiB
+        let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        sealed.into_boxed_slice()
+    }
+    /// Decrypts bytes returned by [`seal`](Self::seal) with the same `aad`.
+    pub(super) fn open(&self, sealed: &[u8], aad: &[u8]) -> io::Result<Box<[u8]>> {
+        if sealed.len() < NONCE_LEN + TAG_LEN {
+            return Err(invalid_data("encrypted chunk is too short"));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let data = self.cipher.decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad });
+        let data = data.map_err(|_| invalid_data("chunk could not be decrypted, the key is wrong or it was tampered with"))?;
+        Ok(data.into_boxed_slice())
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// The associated data a chunk of an encrypted container is sealed with, which ties it to its place in the
+/// container: its index, its element count and the number of chunks.
+fn chunk_aad(chunk_idx: usize, elems: usize, chunks: usize) -> [u8; 24] {
+    let mut aad = [0; 24];
+    for (bytes, value) in aad.chunks_exact_mut(8).zip([chunk_idx, elems, chunks]) {
+        bytes.copy_from_slice(&(value as u64).to_le_bytes());
+    }
+    aad
+}
+
+/// Checks that a container only holds sealed chunks and an empty tail.
+fn check_sealed(entries: &[ChunkEntry], tail: &TailEntry) -> io::Result<()> {
+    if entries.iter().any(|entry| entry.codec != CODEC_SEALED) || tail.len != EMPTY_TAIL.len() || tail.checksum != crc32(&EMPTY_TAIL) {
+        return Err(invalid_data("container is not encrypted"));
+    }
+    Ok(())
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// The number of elements in the chunk at `chunk_idx`.
+    fn chunk_len(&self, chunk_idx: usize) -> usize {
+        self.chunk_ends[chunk_idx] - self.chunk_start(chunk_idx)
+    }
+    /// Like [`save`](Self::save), but every chunk is encrypted with `key`, and the uncompressed tail is written as
+    /// one more chunk so that no element is written in the clear. The file can only be read back with
+    /// [`load_encrypted`](Self::load_encrypted) and the same key.
+    ///
+    /// Each chunk is authenticated along with its position, its element count and the number of chunks, so chunks
+    /// which were changed, reordered or dropped are rejected on load. The number of elements in each chunk, and so
+    /// the length of the collection, is not hidden, and an empty collection has no chunk to authenticate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::{CVec, EncryptionKey};
+    ///
+    /// let path = std::env::temp_dir().join(format!("save_encrypted_example-{}.cvec", std::process::id()));
+    /// let key = EncryptionKey::new(EncryptionKey::random_bytes());
+    /// let compressed_stack: CVec<u32> = (0..(16 * 1024 + 1)).collect();
+    /// compressed_stack.save_encrypted(&path, &key).unwrap();
+    /// let loaded: CVec<u32> = CVec::load_encrypted(&path, &key).unwrap();
+    /// assert!(loaded.iter().eq(compressed_stack.iter()));
+    /// assert!(CVec::<u32>::load(&path).is_err());
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn save_encrypted(&self, path: impl AsRef<Path>, key: &EncryptionKey) -> io::Result<()>
+    where
+        T: Serialize,
+    {
+        let tail = (!self.uncompressed_buffer.is_empty()).then(|| compress(&self.uncompressed_buffer, COMPRESSION_LEVEL));
+        let chunks = self.compressed_storage.len() + usize::from(tail.is_some());
+        let sealed = (0..self.compressed_storage.len())
+            .map(|chunk_idx| (self.chunk_len(chunk_idx), self.compressed_storage[chunk_idx].compressed_bytes(COMPRESSION_LEVEL)))
+            .chain(tail.map(|data| (self.uncompressed_buffer.len(), Cow::Owned(data.into_vec()))))
+            .enumerate()
+            .map(|(chunk_idx, (elems, data))| Ok((elems, CODEC_SEALED, key.seal(&data, &chunk_aad(chunk_idx, elems, chunks)))));
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_parts(&mut writer, sealed, &EMPTY_TAIL)?;
+        writer.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()
+    }
+    /// Reads a collection written by [`save_encrypted`](Self::save_encrypted), decrypting its chunks with `key`.
+    /// Fails if the key is wrong, or if the file was tampered with or is not encrypted.
+    pub fn load_encrypted(path: impl AsRef<Path>, key: &EncryptionKey) -> io::Result<Self>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let mut reader = BufReader::new(File::open(path)?);
+        let (mut cvec, entries, tail) = Self::read_index(&mut reader, Self::default())?;
+        check_sealed(&entries, &tail)?;
+        reader.seek(SeekFrom::Start(HEADER_LEN))?;
+        let chunks = entries.len();
+        for (chunk_idx, entry) in entries.iter().enumerate() {
+            let sealed = read_exact_bytes(&mut reader, entry.len)?;
+            if crc32(&sealed) != entry.checksum {
+                return Err(invalid_data(format!("chunk {chunk_idx} does not match its checksum")));
+            }
+            let data = key.open(&sealed, &chunk_aad(chunk_idx, cvec.chunk_len(chunk_idx), chunks))?;
+            cvec.compressed_storage.push(Chunk::Compressed(data));
+        }
+        cvec.read_tail(&mut reader, tail)?;
+        Ok(cvec)
+    }
+    /// Re-encrypts a file written by [`save_encrypted`](Self::save_encrypted) with `new`, one chunk at a time so
+    /// only one chunk is held in memory. The new file is written next to it and then renamed over it, so the file
+    /// is never left half re-encrypted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::{CVec, EncryptionKey};
+    ///
+    /// let path = std::env::temp_dir().join(format!("rotate_key_example-{}.cvec", std::process::id()));
+    /// let (old, new) = (EncryptionKey::new([1; 32]), EncryptionKey::new([2; 32]));
+    /// let compressed_stack: CVec<u32> = (0..(16 * 1024)).collect();
+    /// compressed_stack.save_encrypted(&path, &old).unwrap();
+    /// CVec::<u32>::rotate_key(&path, &old, &new).unwrap();
+    /// assert!(CVec::<u32>::load_encrypted(&path, &old).is_err());
+    /// assert_eq!(CVec::<u32>::load_encrypted(&path, &new).unwrap(), compressed_stack);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn rotate_key(path: impl AsRef<Path>, old: &EncryptionKey, new: &EncryptionKey) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut reader = BufReader::new(File::open(path)?);
+        let (cvec, entries, tail) = Self::read_index(&mut reader, Self::default())?;
+        check_sealed(&entries, &tail)?;
+        reader.seek(SeekFrom::Start(HEADER_LEN))?;
+        let chunks = entries.len();
+        let resealed = entries.iter().enumerate().map(|(chunk_idx, entry)| {
+            let sealed = read_exact_bytes(&mut reader, entry.len)?;
+            if crc32(&sealed) != entry.checksum {
+                return Err(invalid_data(format!("chunk {chunk_idx} does not match its checksum")));
+            }
+            let elems = cvec.chunk_len(chunk_idx);
+            let aad = chunk_aad(chunk_idx, elems, chunks);
+            Ok((elems, CODEC_SEALED, new.seal(&old.open(&sealed, &aad)?, &aad)))
+        });
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        let written = write_parts(&mut writer, resealed, &EMPTY_TAIL)
+            .and_then(|()| writer.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all());
+        if let Err(err) = written {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(err);
+        }
+        std::fs::rename(&temp_path, path)
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::{HashMap, VecDeque};
+
+/// Chooses which decompressed chunk a [`CVecBudgeted`](crate::CVecBudgeted) evicts once it is over budget.
+///
+/// The policy is told about every line the cache holds, by chunk index, and is asked for a victim among those for
+/// which `evictable` holds (the chunk just read and pinned chunks are never evicted).
+pub trait EvictionPolicy: Default {
+    /// A line was decompressed into the cache.
+    fn insert(&mut self, index: usize);
+    /// A line in the cache was read again.
+    fn touch(&mut self, index: usize);
+    /// A line left the cache, because it was evicted or its chunk changed.
+    fn remove(&mut self, index: usize);
+    /// The line to evict next, if any is evictable.
+    fn victim(&mut self, evictable: impl Fn(usize) -> bool) -> Option<usize>;
+}
+
+/// Evicts the least recently used chunk
+#[derive(Default)]
+pub struct Lru {
+    /// Incremented on every access, to order the lines by when they were last used
+    clock: u64,
+    last_used: HashMap<usize, u64>,
+}
+
+impl EvictionPolicy for Lru {
+    fn insert(&mut self, index: usize) {
+        self.touch(index);
+    }
+    fn touch(&mut self, index: usize) {
+        self.clock += 1;
+        self.last_used.insert(index, self.clock);
+    }
+    fn remove(&mut self, index: usize) {
+        self.last_used.remove(&index);
+    }
+    fn victim(&mut self, evictable: impl Fn(usize) -> bool) -> Option<usize> {
+        self.last_used.iter()
+            .filter(|(&index, _)| evictable(index))
+            .min_by_key(|(_, &last_used)| last_used)
+            .map(|(&index, _)| index)
+    }
+}
+
+/// Evicts the least frequently used chunk, the least recently used one among those used equally often
+///
+/// Unlike [`Lru`] a single scan over the collection does not evict the chunks which are read over and over.
+#[derive(Default)]
+pub struct Lfu {
+    clock: u64,
+    /// The number of reads of each line since it was inserted, and when it was last read
+    uses: HashMap<usize, (u64, u64)>,
+}
+
+impl EvictionPolicy for Lfu {
+    fn insert(&mut self, index: usize) {
+        self.clock += 1;
+        self.uses.insert(index, (1, self.clock));
+    }
+    fn touch(&mut self, index: usize) {
+        self.clock += 1;
+        if let Some((count, last_used)) = self.uses.get_mut(&index) {
+            *count += 1;
+            *last_used = self.clock;
+        }
+    }
+    fn remove(&mut self, index: usize) {
+        self.uses.remove(&index);
+    }
+    fn victim(&mut self, evictable: impl Fn(usize) -> bool) -> Option<usize> {
+        self.uses.iter()
+            .filter(|(&index, _)| evictable(index))
+            .min_by_key(|(_, &uses)| uses)
+            .map(|(&index, _)| index)
+    }
+}
+
+/// Evicts the chunk which was decompressed first, however often it was read since
+#[derive(Default)]
+pub struct Fifo {
+    order: VecDeque<usize>,
+}
+
+impl EvictionPolicy for Fifo {
+    fn insert(&mut self, index: usize) {
+        self.order.push_back(index);
+    }
+    fn touch(&mut self, _index: usize) {}
+    fn remove(&mut self, index: usize) {
+        self.order.retain(|&i| i != index);
+    }
+    fn victim(&mut self, evictable: impl Fn(usize) -> bool) -> Option<usize> {
+        self.order.iter().copied().find(|&index| evictable(index))
+    }
+}
+
+/// Approximates [`Lru`] by giving each chunk a second chance: the hand sweeps over the chunks in insertion order,
+/// clearing the referenced bit of the chunks read since it last passed and evicting the first one without it.
+#[derive(Default)]
+pub struct Clock {
+    /// The lines in the order the hand visits them, with their referenced bit
+    ring: VecDeque<(usize, bool)>,
+}
+
+impl EvictionPolicy for Clock {
+    fn insert(&mut self, index: usize) {
+        self.ring.push_back((index, false));
+    }
+    fn touch(&mut self, index: usize) {
+        if let Some((_, referenced)) = self.ring.iter_mut().find(|(i, _)| *i == index) {
+            *referenced = true;
+        }
+    }
+    fn remove(&mut self, index: usize) {
+        self.ring.retain(|&(i, _)| i != index);
+    }
+    fn victim(&mut self, evictable: impl Fn(usize) -> bool) -> Option<usize> {
+        // Two sweeps clear every referenced bit, so a victim is found if there is any
+        for _ in 0..2 * self.ring.len() {
+            let (index, referenced) = self.ring.pop_front()?;
+            if evictable(index) && !referenced {
+                self.ring.push_front((index, false));
+                return Some(index);
+            }
+            self.ring.push_back((index, referenced && !evictable(index)));
+        }
+        None
+    }
+}
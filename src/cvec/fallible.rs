@@ -0,0 +1,153 @@
+use std::collections::TryReserveError;
+use std::io;
+
+use either::Either;
+use serde::{Deserialize, Serialize};
+
+use super::cache::Cache;
+use super::inner::Chunk;
+use super::{CVecChunk, CVecInner};
+use crate::compression::{try_compress, try_decompress};
+use crate::{Error, PushError};
+
+/// Versions of the basic operations which return an [`Error`] rather than panicking on damaged chunks or elements
+/// which fail to serialize.
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// Like [`push`](Self::push), but hands `value` back if it could not be pushed: if the collection has a budget
+    /// with [`OverflowPolicy::Reject`](crate::OverflowPolicy::Reject) which pushing it would exceed, if it filled a
+    /// chunk whose elements could not be serialized, or if growing the uncompressed buffer or the chunk list failed
+    /// to allocate. Compressing a chunk still allocates infallibly, see [`try_reserve`](Self::try_reserve).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::{CVec, Error, OverflowPolicy, PushError};
+    ///
+    /// let mut compressed_stack = CVec::new::<1024, 0>();
+    /// compressed_stack.set_memory_budget(0, OverflowPolicy::Reject);
+    /// assert!(matches!(compressed_stack.try_push(1), Err(PushError { value: 1, error: Error::BudgetExceeded })));
+    /// ```
+    pub fn try_push(&mut self, value: T) -> Result<(), PushError<T>>
+    where
+        T: Serialize,
+    {
+        if self.rejects_push() {
+            return Err(PushError { value, error: Error::BudgetExceeded });
+        }
+        if let Err(err) = self.uncompressed_buffer.try_reserve(1) {
+            return Err(PushError { value, error: Error::Alloc(err) });
+        }
+        self.uncompressed_buffer.push(value);
+        if self.buffer_full() {
+            if let Err(error) = self.compress_pushed(Self::try_compress_buffer) {
+                let value = self.uncompressed_buffer.pop().unwrap();
+                return Err(PushError { value, error });
+            }
+        }
+        Ok(())
+    }
+    /// Like [`reserve`](Self::reserve), but returns an error instead of aborting if an allocation fails, like
+    /// [`Vec::try_reserve`].
+    ///
+    /// Room is reserved in the chunk list for the chunks the elements will fill, and in the uncompressed buffer for
+    /// as many of them as it holds at once, so pushing them with [`try_push`](Self::try_push) only allocates to
+    /// compress chunks. As those allocations are small and short-lived, reserving ahead is a way to fail early when
+    /// memory is short.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let mut compressed_stack = CVec::new::<1024, 0>();
+    /// compressed_stack.try_reserve(16 * 1024).unwrap();
+    /// assert!(compressed_stack.try_reserve(usize::MAX).is_err());
+    /// for i in 0..(16 * 1024) {
+    ///     compressed_stack.try_push(i).unwrap();
+    /// }
+    /// ```
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let (chunks, buffered) = self.reserve_amounts(additional);
+        self.compressed_storage.try_reserve(chunks)?;
+        self.chunk_ends.try_reserve(chunks)?;
+        self.uncompressed_buffer.try_reserve(buffered)
+    }
+    /// Like [`pop`](Self::pop), but returns an error if the last chunk could not be decompressed (or, if it was
+    /// spilled, read back), the collection is left as it was in that case.
+    pub fn try_pop(&mut self) -> Result<Option<T>, Error>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        if self.uncompressed_buffer.is_empty() && !self.compressed_storage.is_empty() {
+            // Decoded before it is removed, so that nothing is lost on error
+            let decoded = match self.try_chunk(self.compressed_storage.len() - 1)? {
+                Some(CVecChunk::Decompressed(values)) => Some(values),
+                _ => None,
+            };
+            let chunk = self.pop_last_chunk();
+            self.uncompressed_buffer = decoded.or_else(|| chunk.map(Chunk::into_values)).unwrap_or_default();
+        }
+        Ok(self.uncompressed_buffer.pop())
+    }
+    /// Like [`get`](Self::get), but returns an error if the chunk holding the element could not be decompressed
+    /// (or, if it was spilled, read back) or does not hold as many elements as it should. The chunk is decompressed without going through the cache, like
+    /// [`get_uncached`](Self::get_uncached).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let compressed_stack: CVec<u32> = (0..(16 * 1024)).collect();
+    /// assert_eq!(compressed_stack.try_get(1000).unwrap(), Some(1000));
+    /// assert_eq!(compressed_stack.try_get(16 * 1024).unwrap(), None);
+    /// ```
+    pub fn try_get(&self, idx: usize) -> Result<Option<T>, Error>
+    where
+        T: Clone + for<'a> Deserialize<'a>,
+    {
+        match self.split(idx) {
+            Some(Either::Left((chunk_idx, chunk_offset))) => Ok(self.try_chunk(chunk_idx)?.map(|chunk| chunk[chunk_offset].clone())),
+            Some(Either::Right(elem)) => Ok(Some(self.uncompressed_buffer[elem].clone())),
+            None => Ok(None),
+        }
+    }
+    /// Like `chunk`, but returns an error rather than panicking if the chunk could not be decompressed, or
+    /// [`Error::Corrupt`] if it does not hold as many elements as the collection expects.
+    pub(crate) fn try_chunk(&self, chunk_idx: usize) -> Result<Option<CVecChunk<'_, T>>, Error>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let data = match self.compressed_storage.get(chunk_idx) {
+            Some(Chunk::Compressed(data)) => data,
+            Some(Chunk::Spilled(spilled)) => &spilled.try_load().map_err(|err| match err.kind() {
+                io::ErrorKind::InvalidData => Error::Corrupt { chunk: chunk_idx },
+                _ => Error::Io(err),
+            })?,
+            _ => return Ok(self.chunk(chunk_idx)),
+        };
+        let values: Vec<T> = try_decompress(data)?;
+        // A chunk which decodes but holds the wrong number of elements would otherwise be indexed out of bounds
+        self.check_chunk_len(chunk_idx, values.len()).map_err(|_| Error::Corrupt { chunk: chunk_idx })?;
+        Ok(Some(CVecChunk::Decompressed(values)))
+    }
+    /// Like `compress_buffer`, but leaves the uncompressed buffer as it is if its elements could not be
+    /// serialized or there was no room for another chunk.
+    fn try_compress_buffer(&mut self) -> Result<(), Error>
+    where
+        T: Serialize,
+    {
+        self.compressed_storage.try_reserve(1)?;
+        self.chunk_ends.try_reserve(1)?;
+        if self.raw_window > 0 {
+            // The chunk is only compressed once it leaves the raw window, so check that it can be now
+            postcard::to_stdvec(&self.uncompressed_buffer).map_err(Error::Serialize)?;
+            self.compress_buffer();
+            return Ok(());
+        }
+        let compressed = try_compress(&self.uncompressed_buffer, COMPRESSION_LEVEL)?;
+        self.push_chunk(Chunk::Compressed(compressed), self.uncompressed_buffer.len());
+        self.uncompressed_buffer.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::Deserialize;
+
+use crate::compression::decompress;
+
+/// Handed out to each frozen collection (and shared by its clones), so that the per-thread caches can tell them
+/// apart
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A chunk read from a frozen collection: the collection's id, the chunk index and its values
+type FrozenLine = (usize, usize, Box<dyn Any>);
+
+thread_local! {
+    /// The chunk this thread read last from any frozen collection
+    static LINE: RefCell<Option<FrozenLine>> = const { RefCell::new(None) };
+}
+
+/// An immutable snapshot of a CVec, returned by `CVec::freeze`
+///
+/// All of its elements are compressed, and the chunks are shared between clones, so cloning it is cheap. It is
+/// `Send` and `Sync`: each thread keeps the chunk it read last decompressed in a cache of its own, so threads
+/// never wait on each other.
+///
+/// # Examples
+///
+/// ```
+/// use compressed_collections::CVec;
+///
+/// let compressed_stack: CVec<u32> = (0..(16 * 1024)).collect();
+/// let frozen = compressed_stack.freeze();
+/// std::thread::scope(|s| {
+///     for t in 0..4 {
+///         let frozen = frozen.clone();
+///         s.spawn(move || {
+///             for i in (t * 4096)..((t + 1) * 4096) {
+///                 assert_eq!(frozen.get(i), Some(i as u32));
+///             }
+///         });
+///     }
+/// });
+/// assert!((0..(16 * 1024)).eq(&frozen));
+/// ```
+pub struct FrozenCVec<T> {
+    id: usize,
+    chunks: Arc<[Box<[u8]>]>,
+    /// The (exclusive) end index of each chunk
+    chunk_ends: Arc<[usize]>,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for FrozenCVec<T> {
+    fn clone(&self) -> Self {
+        Self { id: self.id, chunks: Arc::clone(&self.chunks), chunk_ends: Arc::clone(&self.chunk_ends), phantom: PhantomData }
+    }
+}
+
+impl<T> std::fmt::Debug for FrozenCVec<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrozenCVec").field("len", &self.len()).field("chunks", &self.chunks.len()).finish()
+    }
+}
+
+impl<T> FrozenCVec<T> {
+    pub(super) fn new(chunks: Vec<Box<[u8]>>, chunk_ends: Vec<usize>) -> Self {
+        Self { id: NEXT_ID.fetch_add(1, Ordering::Relaxed), chunks: chunks.into(), chunk_ends: chunk_ends.into(), phantom: PhantomData }
+    }
+    /// The number of elements
+    pub fn len(&self) -> usize {
+        self.chunk_ends.last().copied().unwrap_or(0)
+    }
+    /// Whether there are no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns a clone of the element at `idx`, decompressing its chunk into this thread's cache unless it is
+    /// already there.
+    #[must_use]
+    pub fn get(&self, idx: usize) -> Option<T> where T: Clone + for<'a> Deserialize<'a> + 'static {
+        if idx >= self.len() {
+            return None;
+        }
+        let chunk_idx = self.chunk_ends.partition_point(|&end| end <= idx);
+        let offset = idx - chunk_idx.checked_sub(1).map_or(0, |prev| self.chunk_ends[prev]);
+        LINE.with(|line| {
+            if let Some((id, cached, values)) = &*line.borrow() {
+                if (*id, *cached) == (self.id, chunk_idx) {
+                    return Some(values.downcast_ref::<Vec<T>>().unwrap()[offset].clone());
+                }
+            }
+            let values: Vec<T> = decompress(&self.chunks[chunk_idx]);
+            let value = values[offset].clone();
+            // The old line is dropped once the cache is no longer borrowed, its elements may read frozen collections
+            let old = line.borrow_mut().replace((self.id, chunk_idx, Box::new(values)));
+            drop(old);
+            Some(value)
+        })
+    }
+    /// Returns an iterator over the elements, bottom to top like `CVec::iter`, decompressing one chunk at a time.
+    pub fn iter(&self) -> FrozenIter<'_, T> {
+        FrozenIter { chunks: self.chunks.iter(), chunk: Vec::new().into_iter(), remaining: self.len() }
+    }
+}
+
+/// Iterator over the elements of a [`FrozenCVec`], returned by `FrozenCVec::iter`
+pub struct FrozenIter<'i, T> {
+    chunks: std::slice::Iter<'i, Box<[u8]>>,
+    chunk: std::vec::IntoIter<T>,
+    remaining: usize,
+}
+
+impl<T> Iterator for FrozenIter<'_, T>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(x) = self.chunk.next() {
+                self.remaining -= 1;
+                return Some(x);
+            }
+            self.chunk = decompress::<Vec<T>>(self.chunks.next()?).into_iter();
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for FrozenIter<'_, T>
+where
+    T: for<'a> Deserialize<'a>,
+{}
+
+impl<'i, T> IntoIterator for &'i FrozenCVec<T>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    type Item = T;
+    type IntoIter = FrozenIter<'i, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
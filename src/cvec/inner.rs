@@ -1,57 +1,284 @@
-use serde::{Serialize, Deserialize};
+use std::borrow::Cow;
 
-use super::cache::{Cache, Cached};
+use serde::{de, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{budget::MemoryBudget, cache::{Cache, Cached, ChunkBytes}, log::ChunkLog, maintenance::Maintenance, recovery::CorruptChunkPolicy, spill::{Spill, SpilledChunk}, ChunkSize};
+use super::verify::{ChunkDamage, DamagedChunk};
+use crate::compression::{compress, decompress, deserialize, try_decompress};
 
-#[derive(Serialize, Deserialize)]
 pub struct CVec<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0, C: Cache = Cached<T, CHUNK_ELEMS>> {
-    pub(super) compressed_storage: Vec<Box<[u8]>>,
+    pub(super) compressed_storage: Vec<Chunk<T>>,
+    /// The (exclusive) end index of each chunk in `compressed_storage`, chunks hold at most `CHUNK_ELEMS` elements
+    pub(super) chunk_ends: Vec<usize>,
     pub(super) uncompressed_buffer: Vec<T>,
-    #[serde(skip)]
     pub(super) cache: C,
+    /// The chunk size, only read when it is chosen at runtime (`CHUNK_ELEMS == 0`), it is not serialized so
+    /// deserialized collections use the default chunk size
+    pub(super) chunk_elems: usize,
+    /// The serialized size the uncompressed buffer is flushed at, set by a [`ChunkSize`] in bytes and not serialized
+    pub(super) chunk_bytes: Option<usize>,
+    /// The length of the uncompressed buffer when its serialized size was last counted, and that size, see
+    /// `chunk_bytes`
+    pub(super) buffer_bytes: (usize, usize),
+    /// The chunk which was decompressed into a [`Chunk::Raw`] to be mutated in place, it is compressed again once
+    /// another chunk is opened or the collection is flushed
+    pub(super) open_chunk: Option<usize>,
+    /// The compressed bytes of the open chunk while it has only been read, it is dirty (and has to be compressed
+    /// again) once this is `None`
+    pub(super) open_clean: Option<Box<[u8]>>,
+    /// The number of most recently pushed chunks which are kept as [`Chunk::Raw`], see `CVec::set_raw_window`
+    pub(super) raw_window: usize,
+    pub(super) maintenance: Maintenance,
+    /// Set once chunks are to be spilled to disk, see `CVec::set_spill_budget`
+    pub(super) spill: Option<Spill>,
+    /// Set while every pushed chunk is logged to a file, see `CVec::set_log`
+    pub(super) log: Option<ChunkLog>,
+    /// Set once the memory used is limited, see `CVec::set_memory_budget`
+    pub(super) budget: Option<MemoryBudget>,
+    /// What `CVec::try_iter` does when a chunk fails to decode
+    pub(super) corrupt_policy: CorruptChunkPolicy,
+}
+
+/// The chunk size of a new collection, [`ChunkSize::Default`] if it is chosen at runtime.
+pub(super) fn default_chunk_elems<T, const CHUNK_ELEMS: usize>() -> usize {
+    if CHUNK_ELEMS == 0 {
+        ChunkSize::Default.elems::<T>()
+    } else {
+        CHUNK_ELEMS
+    }
+}
+
+/// A chunk of elements, usually compressed but held as plain values while it is being mutated, or on disk once
+/// it was spilled.
+#[derive(Clone)]
+pub(super) enum Chunk<T> {
+    Compressed(Box<[u8]>),
+    Raw(Vec<T>),
+    Spilled(SpilledChunk),
+}
+
+impl<T> Chunk<T> {
+    pub(super) fn compressed_bytes(&self, compression_level: i32) -> Cow<'_, [u8]> where T: Serialize {
+        match self {
+            Chunk::Compressed(data) => Cow::Borrowed(data),
+            Chunk::Raw(values) => Cow::Owned(compress(values, compression_level).into_vec()),
+            Chunk::Spilled(spilled) => Cow::Owned(spilled.load().into_vec()),
+        }
+    }
+    /// Like [`compressed_bytes`](Self::compressed_bytes), but a spilled chunk which cannot be read is an error.
+    pub(super) fn try_compressed_bytes(&self, compression_level: i32) -> std::io::Result<Cow<'_, [u8]>> where T: Serialize {
+        match self {
+            Chunk::Spilled(spilled) => Ok(Cow::Owned(spilled.try_load()?.into_vec())),
+            chunk => Ok(chunk.compressed_bytes(compression_level)),
+        }
+    }
+    /// The compressed bytes of a chunk which is not raw, for a cache: the bytes of a spilled chunk are only read
+    /// back from disk if the cache misses.
+    pub(super) fn cache_bytes(&self) -> Option<ChunkBytes<'_>> {
+        match self {
+            Chunk::Compressed(data) => Some(ChunkBytes::new(data)),
+            Chunk::Raw(_) => None,
+            Chunk::Spilled(spilled) => Some(ChunkBytes::spilled(spilled)),
+        }
+    }
+    pub(super) fn into_values(self) -> Vec<T> where T: for<'a> Deserialize<'a> {
+        match self {
+            Chunk::Compressed(data) => decompress(&data),
+            Chunk::Raw(values) => values,
+            Chunk::Spilled(spilled) => decompress(&spilled.load()),
+        }
+    }
+    /// Decodes a copy of the values, leaving the chunk as it is.
+    pub(super) fn to_values(&self) -> Vec<T> where T: Serialize + for<'a> Deserialize<'a> {
+        match self {
+            Chunk::Compressed(data) => decompress(data),
+            Chunk::Raw(values) => copy_values(values),
+            Chunk::Spilled(spilled) => decompress(&spilled.load()),
+        }
+    }
+    /// Replaces a compressed (or spilled) chunk with its values, returns its bytes if it was not raw before.
+    pub(super) fn decompress(&mut self) -> Option<Box<[u8]>> where T: for<'a> Deserialize<'a> {
+        let data = match self {
+            Chunk::Compressed(data) => std::mem::take(data),
+            Chunk::Raw(_) => return None,
+            Chunk::Spilled(spilled) => spilled.load(),
+        };
+        *self = Chunk::Raw(decompress(&data));
+        Some(data)
+    }
+    pub(super) fn compress(&mut self, compression_level: i32) where T: Serialize {
+        if let Chunk::Raw(values) = self {
+            *self = Chunk::Compressed(compress(values, compression_level));
+        }
+    }
+}
+
+/// Copies `values` through their serialized form, so that `T` need not be `Clone`.
+pub(super) fn copy_values<T: Serialize + for<'a> Deserialize<'a>>(values: &[T]) -> Vec<T> {
+    deserialize(&postcard::to_stdvec(values).unwrap()) // Only errors on OOM
+}
+
+impl<T: Serialize, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> Serialize for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Storage<'a, T, const COMPRESSION_LEVEL: i32>(&'a [Chunk<T>]);
+        impl<T: Serialize, const COMPRESSION_LEVEL: i32> Serialize for Storage<'_, T, COMPRESSION_LEVEL> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq(self.0.iter().map(|chunk| chunk.compressed_bytes(COMPRESSION_LEVEL)))
+            }
+        }
+        // The chunk ends are not serialized, which keeps the layout of older versions, see `deserialize`
+        let mut state = serializer.serialize_struct("CVec", 2)?;
+        state.serialize_field("compressed_storage", &Storage::<T, COMPRESSION_LEVEL>(&self.compressed_storage))?;
+        state.serialize_field("uncompressed_buffer", &self.uncompressed_buffer)?;
+        state.end()
+    }
+}
+
+/// The serialized form of a [`CVec`] whose elements were written as `Old`, with the chunks left as bytes so that
+/// they can be decoded by hand when `T` no longer matches the layout they were written with.
+#[derive(Deserialize)]
+#[serde(rename = "CVec", bound(deserialize = "Old: for<'a> Deserialize<'a>"))]
+pub(super) struct RawCVec<Old> {
+    pub(super) compressed_storage: Vec<Box<[u8]>>,
+    pub(super) uncompressed_buffer: Vec<Old>,
+}
+
+impl<'de, T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> Deserialize<'de> for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    /// Every chunk is decoded to rebuild the chunk ends from the number of elements it holds, so that malformed
+    /// input is rejected here rather than breaking `len` and indexing later.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "CVec", bound(deserialize = "T: for<'a> Deserialize<'a>"))]
+        struct Parts<T> {
+            compressed_storage: Vec<Box<[u8]>>,
+            uncompressed_buffer: Vec<T>,
+        }
+
+        let parts = Parts::deserialize(deserializer)?;
+        let mut chunk_ends = Vec::with_capacity(parts.compressed_storage.len());
+        for (index, data) in parts.compressed_storage.iter().enumerate() {
+            let values: Vec<T> = try_decompress(data)
+                .map_err(|err| de::Error::custom(DamagedChunk { index, damage: ChunkDamage::Corrupt(err.to_string()) }))?;
+            chunk_ends.push(chunk_ends.last().copied().unwrap_or(0) + values.len());
+        }
+        let cvec = Self {
+            compressed_storage: parts.compressed_storage.into_iter().map(Chunk::Compressed).collect(),
+            chunk_ends,
+            uncompressed_buffer: parts.uncompressed_buffer,
+            ..Self::default()
+        };
+        cvec.check_structure().map_err(de::Error::custom)?;
+        Ok(cvec)
+    }
 }
 
 impl<T: Clone, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> Clone for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
     fn clone(&self) -> Self {
         Self {
             compressed_storage: self.compressed_storage.clone(),
+            chunk_ends: self.chunk_ends.clone(),
             uncompressed_buffer: self.uncompressed_buffer.clone(),
             cache: C::default(),
+            chunk_elems: self.chunk_elems,
+            chunk_bytes: self.chunk_bytes,
+            buffer_bytes: self.buffer_bytes,
+            open_chunk: self.open_chunk,
+            open_clean: self.open_clean.clone(),
+            raw_window: self.raw_window,
+            maintenance: Default::default(),
+            spill: self.spill.clone(),
+            log: None,
+            budget: self.budget.clone(),
+            corrupt_policy: self.corrupt_policy,
+        }
+    }
+}
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    /// Walks the elements of both collections in order, passing `f` runs of equal length until it returns `Some`
+    /// or either collection runs out. Only one chunk of each is decompressed at a time, so the result does not
+    /// depend on where the chunks end or on how they are held.
+    fn zip_runs<R>(&self, other: &Self, mut f: impl FnMut(&[T], &[T]) -> Option<R>) -> Option<R> {
+        let (mut chunks, mut other_chunks) = (self.chunks(), other.chunks());
+        let (mut chunk, mut other_chunk) = (chunks.next(), other_chunks.next());
+        let (mut offset, mut other_offset) = (0, 0);
+        while let (Some(values), Some(other_values)) = (&chunk, &other_chunk) {
+            let run = (values.len() - offset).min(other_values.len() - other_offset);
+            if let Some(result) = f(&values[offset..offset + run], &other_values[other_offset..other_offset + run]) {
+                return Some(result);
+            }
+            offset += run;
+            other_offset += run;
+            if offset == values.len() {
+                (chunk, offset) = (chunks.next(), 0);
+            }
+            if other_offset == other_values.len() {
+                (other_chunk, other_offset) = (other_chunks.next(), 0);
+            }
         }
+        None
     }
 }
-impl<T: PartialEq, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> PartialEq for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+
+/// Collections are compared by their elements, like a `Vec`, whatever their chunks are and however they are held.
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> PartialEq for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: PartialEq + for<'a> Deserialize<'a>,
+{
     fn eq(&self, other: &Self) -> bool {
-        self.compressed_storage.eq(&other.compressed_storage) && self.uncompressed_buffer.eq(&other.uncompressed_buffer)
+        self.len() == other.len() && self.zip_runs(other, |a, b| (a != b).then_some(())).is_none()
     }
 }
-impl<T: Eq, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> Eq for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {}
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> Eq for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Eq + for<'a> Deserialize<'a>,
+{}
 
-impl<T: PartialOrd, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> PartialOrd for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> PartialOrd for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: PartialOrd + for<'a> Deserialize<'a>,
+{
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match self.compressed_storage.partial_cmp(&other.compressed_storage) {
-            Some(core::cmp::Ordering::Equal) => {}
-            ord => return ord,
-        }
-        self.uncompressed_buffer.partial_cmp(&other.uncompressed_buffer)
+        let ord = self.zip_runs(other, |a, b| match a.partial_cmp(b) {
+            Some(std::cmp::Ordering::Equal) => None,
+            ord => Some(ord),
+        });
+        ord.unwrap_or_else(|| self.len().partial_cmp(&other.len()))
     }
 }
-impl<T: Ord, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> Ord for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> Ord for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Ord + for<'a> Deserialize<'a>,
+{
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.compressed_storage.cmp(&other.compressed_storage).then(self.uncompressed_buffer.cmp(&other.uncompressed_buffer))
+        self.zip_runs(other, |a, b| Some(a.cmp(b)).filter(|ord| ord.is_ne()))
+            .unwrap_or_else(|| self.len().cmp(&other.len()))
     }
 }
 
-impl<T: std::hash::Hash, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> std::hash::Hash for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+/// Hashes the length and then every element, so equal collections hash the same however they are chunked.
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> std::hash::Hash for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: std::hash::Hash + for<'a> Deserialize<'a>,
+{
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.compressed_storage.hash(state);
-        self.uncompressed_buffer.hash(state);
+        state.write_usize(self.len());
+        for chunk in self.chunks() {
+            chunk.iter().for_each(|value| value.hash(state));
+        }
     }
 }
 
-struct CompressedElem<'a, T, const CHUNK_ELEMS: usize>(&'a Vec<Box<[u8]>>, std::marker::PhantomData<T>);
-impl<'a, T, const CHUNK_ELEMS: usize> std::fmt::Debug for CompressedElem<'a, T, CHUNK_ELEMS> {
+/// Debug representation of a run of compressed chunks holding `.1` elements
+pub(crate) struct CompressedElem<'a, T>(pub(crate) &'a [&'a [u8]], pub(crate) usize, pub(crate) std::marker::PhantomData<T>);
+impl<'a, T> std::fmt::Debug for CompressedElem<'a, T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let elems = self.0.len() * CHUNK_ELEMS;
+        let elems = self.1;
         write!(f, "<{elems}x compressed")?;
         let compressed_bytes = self.0.iter().map(|x| x.len()).sum::<usize>();
         if std::mem::needs_drop::<T>() {
@@ -71,11 +298,41 @@ impl<'a, T, const CHUNK_ELEMS: usize> std::fmt::Debug for CompressedElem<'a, T,
         write!(f, ">")
     }
 }
+/// Debug representation of a spilled chunk holding `.0` elements
+struct SpilledElem(usize);
+impl std::fmt::Debug for SpilledElem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<{}x spilled>", self.0)
+    }
+}
 impl<T: std::fmt::Debug, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> std::fmt::Debug for CVec<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut f = f.debug_list();
-        if !self.compressed_storage.is_empty() {
-            f.entry(&CompressedElem::<T, CHUNK_ELEMS>(&self.compressed_storage, std::marker::PhantomData));
+        let (mut compressed, mut elems, mut start) = (Vec::new(), 0, 0);
+        for (chunk, &end) in self.compressed_storage.iter().zip(&self.chunk_ends) {
+            match chunk {
+                Chunk::Compressed(data) => {
+                    compressed.push(&**data);
+                    elems += end - start;
+                }
+                // Ends the current run of compressed chunks and is shown as its element count, without reading it back
+                Chunk::Spilled(_) => {
+                    if !compressed.is_empty() {
+                        f.entry(&CompressedElem::<T>(&std::mem::take(&mut compressed), std::mem::take(&mut elems), std::marker::PhantomData));
+                    }
+                    f.entry(&SpilledElem(end - start));
+                }
+                Chunk::Raw(values) => {
+                    if !compressed.is_empty() {
+                        f.entry(&CompressedElem::<T>(&std::mem::take(&mut compressed), std::mem::take(&mut elems), std::marker::PhantomData));
+                    }
+                    f.entries(values);
+                }
+            }
+            start = end;
+        }
+        if !compressed.is_empty() {
+            f.entry(&CompressedElem::<T>(&compressed, elems, std::marker::PhantomData));
         }
         f.entries(&self.uncompressed_buffer);
         f.finish()?;
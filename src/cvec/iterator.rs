@@ -1,22 +1,35 @@
+use std::thread::JoinHandle;
+
 use either::Either;
 use serde::{Deserialize, Serialize};
 
-use crate::compression::decompress;
+use crate::compression::{decompress, decompress_into};
 
-use super::{inner::CVec as CVecInner, cache::{Cache, Cached, Uncached}};
+use super::{inner::{CVec as CVecInner, Chunk}, cache::{Cache, Cached, Uncached}};
 
 // Owned Iterator
 
-/// TODO: doc
+/// Owning iterator over a CVec, yields elements front to back like `Vec::into_iter`
+///
+/// Chunks are decompressed one at a time as they are reached, from either end: use `.rev()` to consume the
+/// collection back to front (as repeated `pop` calls would).
 pub type CVecIntoIter<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> = CVecIntoIterInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, Cached<T, CHUNK_ELEMS>>;
-/// TODO: doc
+/// Owning iterator over a CVecUncached, see [`CVecIntoIter`]
 pub type CVecIntoIterUncached<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> = CVecIntoIterInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, Uncached>;
 
 use inner::CVecIntoIter as CVecIntoIterInner;
 mod inner {
     use super::*;
     pub struct CVecIntoIter<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0, C: Cache = Cached<T, CHUNK_ELEMS>> {
-        pub(super) inner: CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>,
+        pub(super) remaining: usize,
+        /// The chunks not yet reached from either end, followed by the uncompressed buffer (as a final
+        /// [`Chunk::Raw`])
+        pub(super) chunks: std::vec::IntoIter<Chunk<T>>,
+        /// The number of elements in each of `chunks`
+        pub(super) chunk_lens: std::vec::IntoIter<usize>,
+        pub(super) front: std::vec::IntoIter<T>,
+        pub(super) back: std::vec::IntoIter<T>,
+        pub(super) cache: std::marker::PhantomData<C>,
     }
 }
 
@@ -27,12 +40,58 @@ where
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner.pop()
+        self.nth(0)
     }
     fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.inner.len(), Some(self.inner.len()))
+        (self.remaining, Some(self.remaining))
+    }
+    /// Skips over whole chunks which lie before the `n`th element without decompressing them.
+    fn nth(&mut self, mut n: usize) -> Option<Self::Item> {
+        if n >= self.remaining {
+            self.remaining = 0;
+            self.chunks = Default::default();
+            self.chunk_lens = Default::default();
+            self.front = Default::default();
+            self.back = Default::default();
+            return None;
+        }
+        self.remaining -= n + 1;
+        while n >= self.front.len() {
+            n -= self.front.len();
+            self.front = Default::default();
+            match (self.chunks.next(), self.chunk_lens.next()) {
+                (Some(chunk), Some(len)) if n < len => self.front = chunk.into_values().into_iter(),
+                (Some(_), Some(len)) => n -= len,
+                // The remaining elements were already decompressed from the back
+                _ => std::mem::swap(&mut self.front, &mut self.back),
+            }
+        }
+        self.front.nth(n)
+    }
+}
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> DoubleEndedIterator for CVecIntoIterInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        while self.back.len() == 0 {
+            match (self.chunks.next_back(), self.chunk_lens.next_back()) {
+                (Some(chunk), Some(_)) => self.back = chunk.into_values().into_iter(),
+                // The remaining elements were already decompressed from the front
+                _ => std::mem::swap(&mut self.front, &mut self.back),
+            }
+        }
+        self.remaining -= 1;
+        self.back.next_back()
     }
 }
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> ExactSizeIterator for CVecIntoIterInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: for<'a> Deserialize<'a>,
+{}
 
 // Owned IntoIterator
 
@@ -43,8 +102,21 @@ where
     type Item = T;
     type IntoIter = CVecIntoIterInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>;
 
-    fn into_iter(self) -> Self::IntoIter {
-        CVecIntoIterInner { inner: self }
+    fn into_iter(mut self) -> Self::IntoIter {
+        let remaining = self.len();
+        let mut chunks = std::mem::take(&mut self.compressed_storage);
+        let mut start = 0;
+        let mut chunk_lens: Vec<usize> = self.chunk_ends.iter().map(|&end| end - std::mem::replace(&mut start, end)).collect();
+        chunk_lens.push(self.uncompressed_buffer.len());
+        chunks.push(Chunk::Raw(std::mem::take(&mut self.uncompressed_buffer)));
+        CVecIntoIterInner {
+            remaining,
+            chunks: chunks.into_iter(),
+            chunk_lens: chunk_lens.into_iter(),
+            front: Default::default(),
+            back: Default::default(),
+            cache: std::marker::PhantomData,
+        }
     }
 }
 
@@ -112,10 +184,120 @@ where
 
 // Shared borrow Iterator
 
+/// Iterator over clones of the elements of a CVec, returned by `CVec::iter` and `CVec::iter_range`
+///
+/// When iterating from both ends the front and back each decompress their own copy of the chunk they are in, so
+/// the chunk where they meet may be decompressed twice. Each end decompresses chunks into the same buffer, so once
+/// it is large enough for a chunk iterating does not allocate.
 pub struct CVecIter<'i, T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0, C: Cache = Uncached> {
     chunk_idx: usize,
+    remaining: usize,
     inner: &'i CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>,
-    iter: Either<std::vec::IntoIter<T>, std::slice::Iter<'i, T>>,
+    /// The elements left in the chunk being iterated over from the front, decompressed ones in reverse order so
+    /// that they are popped off the end
+    iter: Either<Vec<T>, std::slice::Iter<'i, T>>,
+    /// One past the index of the next element to be yielded from the back
+    end: usize,
+    /// The elements left in the chunk being iterated over from the back, in order
+    back_iter: Either<Vec<T>, std::slice::Iter<'i, T>>,
+}
+
+/// Takes the buffer of the chunk decompressed last, emptied, to decompress the next one into.
+fn reuse<T>(iter: &mut Either<Vec<T>, std::slice::Iter<'_, T>>) -> Vec<T> {
+    match std::mem::replace(iter, Either::Right([].iter())) {
+        Either::Left(values) => values,
+        Either::Right(_) => Vec::new(),
+    }
+}
+
+impl<'i, T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecIter<'i, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    /// Iterates over `range`, which must be in bounds, starting from the chunk containing its start.
+    pub(super) fn new(inner: &'i CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>, range: std::ops::Range<usize>) -> Self {
+        let mut iter = CVecIter {
+            chunk_idx: 0,
+            remaining: range.len(),
+            inner,
+            iter: Either::Right([].iter()),
+            end: range.end,
+            back_iter: Either::Right([].iter()),
+        };
+        iter.seek(range.start);
+        iter
+    }
+    /// Moves the front of the iterator to `idx`, decompressing only the chunk containing it.
+    fn seek(&mut self, idx: usize) {
+        if let Some(location) = self.inner.split(idx) {
+            let offset = match location {
+                Either::Left((chunk_idx, chunk_offset)) => {
+                    self.chunk_idx = chunk_idx;
+                    chunk_offset
+                }
+                Either::Right(elem) => {
+                    self.chunk_idx = self.inner.compressed_storage.len();
+                    elem
+                }
+            };
+            self.load_chunk();
+            self.skip_loaded(offset);
+        }
+    }
+    /// Skips `n` elements of the chunk being iterated over from the front.
+    fn skip_loaded(&mut self, n: usize) {
+        if n > 0 {
+            match &mut self.iter {
+                Either::Left(values) => values.truncate(values.len().saturating_sub(n)),
+                Either::Right(i) => { i.nth(n - 1); }
+            }
+        }
+    }
+    /// Starts iterating over the chunk at `chunk_idx` (the uncompressed buffer if it is one past the last chunk).
+    fn load_chunk(&mut self) -> bool {
+        if let Some(x) = self.inner.compressed_storage.get(self.chunk_idx) {
+            self.iter = match x {
+                Chunk::Compressed(data) => Either::Left(decompress_reversed(data, reuse(&mut self.iter))),
+                Chunk::Raw(values) => Either::Right(values.iter()),
+                Chunk::Spilled(spilled) => Either::Left(decompress_reversed(&spilled.load(), reuse(&mut self.iter))),
+            };
+        } else if self.chunk_idx == self.inner.compressed_storage.len() {
+            self.iter = Either::Right(self.inner.uncompressed_buffer.iter());
+        } else {
+            return false;
+        }
+        self.chunk_idx += 1;
+        true
+    }
+    /// Starts iterating backwards over the chunk containing the element before `end`.
+    fn load_back_chunk(&mut self) {
+        self.back_iter = match self.inner.split(self.end - 1) {
+            Some(Either::Left((chunk_idx, chunk_offset))) => match &self.inner.compressed_storage[chunk_idx] {
+                Chunk::Compressed(data) => {
+                    let mut values = reuse(&mut self.back_iter);
+                    decompress_into(data, &mut values);
+                    values.truncate(chunk_offset + 1);
+                    Either::Left(values)
+                }
+                Chunk::Spilled(spilled) => {
+                    let mut values = reuse(&mut self.back_iter);
+                    decompress_into(&spilled.load(), &mut values);
+                    values.truncate(chunk_offset + 1);
+                    Either::Left(values)
+                }
+                Chunk::Raw(values) => Either::Right(values[..=chunk_offset].iter()),
+            },
+            Some(Either::Right(elem)) => Either::Right(self.inner.uncompressed_buffer[..=elem].iter()),
+            None => unreachable!("`end` is within bounds while elements remain"),
+        };
+    }
+}
+
+/// Decompresses a chunk into `values`, in reverse order.
+fn decompress_reversed<T: for<'a> Deserialize<'a>>(data: &[u8], mut values: Vec<T>) -> Vec<T> {
+    decompress_into(data, &mut values);
+    values.reverse();
+    values
 }
 
 impl<'i, T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Iterator for CVecIter<'i, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
@@ -125,28 +307,48 @@ where
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.iter.as_ref().either(|i| i.len(), |i| i.len()) == 0 {
-            if let Some(x) = self.inner.compressed_storage.get(self.chunk_idx) {
-                let data: Vec<T> = decompress(x);
-                self.iter = Either::Left(data.into_iter());
-            } else if self.chunk_idx == self.inner.compressed_storage.len() {
-                self.iter = Either::Right(self.inner.uncompressed_buffer.iter());
-            } else {
-                return None;
-            }
-            self.chunk_idx += 1;
+        if self.remaining == 0 {
+            return None;
         }
-        self.iter.as_mut().either(|i| i.next(), |i| i.next().cloned())
+        if self.iter.as_ref().either(|i| i.len(), |i| i.len()) == 0 && !self.load_chunk() {
+            return None;
+        }
+        self.remaining -= 1;
+        self.iter.as_mut().either(|values| values.pop(), |i| i.next().cloned())
     }
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let len = self.iter.as_ref().either(|i| i.len(), |i| i.len());
-        let compressed_len = self.inner.compressed_storage.len().saturating_sub(self.chunk_idx) * CHUNK_ELEMS;
-        let uncompressed_len = if self.chunk_idx <= self.inner.compressed_storage.len() {
-            self.inner.uncompressed_buffer.len()
+        (self.remaining, Some(self.remaining))
+    }
+    /// Seeks straight to the chunk containing the `n`th element if it lies past the current one, so the chunks in
+    /// between are never decompressed.
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        if n >= self.remaining {
+            self.remaining = 0;
+            return None;
+        }
+        if n < self.iter.as_ref().either(|i| i.len(), |i| i.len()) {
+            self.skip_loaded(n);
         } else {
-            0
-        };
-        (len + compressed_len + uncompressed_len, Some(len + compressed_len + uncompressed_len))
+            self.seek(self.end - self.remaining + n);
+        }
+        self.remaining -= n;
+        self.next()
+    }
+}
+impl<'i, T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> DoubleEndedIterator for CVecIter<'i, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Clone + for<'a> Deserialize<'a>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        if self.back_iter.as_ref().either(|i| i.len(), |i| i.len()) == 0 {
+            self.load_back_chunk();
+        }
+        self.remaining -= 1;
+        self.end -= 1;
+        self.back_iter.as_mut().either(|values| values.pop(), |i| i.next_back().cloned())
     }
 }
 impl<'i, T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> ExactSizeIterator for CVecIter<'i, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
@@ -164,7 +366,248 @@ where
     type IntoIter = CVecIter<'i, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>;
 
     fn into_iter(self) -> Self::IntoIter {
-        CVecIter { chunk_idx: 0, inner: self, iter: Either::Right([].iter()) }
+        CVecIter::new(self, 0..self.len())
+    }
+}
+
+// Pipelined Iterator
+
+/// Iterator over clones of the elements of a CVec, returned by `CVec::iter_pipelined`
+///
+/// Whenever it reaches a chunk the next one starts being decompressed on a helper thread, so decompression overlaps
+/// with whatever the consumer does with the elements of the current chunk.
+pub struct CVecPipelinedIter<'i, T> {
+    remaining: usize,
+    /// The chunks not yet reached, other than the one in `pending`
+    chunks: std::slice::Iter<'i, Chunk<T>>,
+    /// The uncompressed buffer, until it is reached
+    buffer: Option<&'i [T]>,
+    iter: Either<std::vec::IntoIter<T>, std::slice::Iter<'i, T>>,
+    /// The chunk after the current one, being decompressed on the helper thread
+    pending: Option<JoinHandle<Vec<T>>>,
+}
+
+impl<'i, T> CVecPipelinedIter<'i, T>
+where
+    T: Send + 'static + for<'a> Deserialize<'a>,
+{
+    pub(super) fn new<C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>(inner: &'i CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>) -> Self {
+        Self {
+            remaining: inner.len(),
+            chunks: inner.compressed_storage.iter(),
+            buffer: Some(&inner.uncompressed_buffer),
+            iter: Either::Right([].iter()),
+            pending: None,
+        }
+    }
+    /// Moves on to the next chunk, returning `false` if there is none.
+    fn advance(&mut self) -> bool {
+        self.iter = match self.pending.take() {
+            Some(handle) => Either::Left(handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)).into_iter()),
+            None => match self.chunks.next() {
+                Some(Chunk::Compressed(data)) => Either::Left(decompress::<Vec<T>>(data).into_iter()),
+                Some(Chunk::Raw(values)) => Either::Right(values.iter()),
+                Some(Chunk::Spilled(spilled)) => Either::Left(decompress::<Vec<T>>(&spilled.load()).into_iter()),
+                None => match self.buffer.take() {
+                    Some(buffer) => Either::Right(buffer.iter()),
+                    None => return false,
+                },
+            },
+        };
+        self.pending = match self.chunks.as_slice().first() {
+            Some(Chunk::Compressed(next)) => {
+                let next = next.to_vec();
+                Some(std::thread::spawn(move || decompress(&next)))
+            }
+            // Reading it back from disk happens on the helper thread too
+            Some(Chunk::Spilled(next)) => {
+                let next = next.clone();
+                Some(std::thread::spawn(move || decompress(&next.load())))
+            }
+            Some(Chunk::Raw(_)) | None => return true,
+        };
+        self.chunks.next();
+        true
+    }
+}
+
+impl<T> Iterator for CVecPipelinedIter<'_, T>
+where
+    T: Clone + Send + 'static + for<'a> Deserialize<'a>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let value = match &mut self.iter {
+                Either::Left(values) => values.next(),
+                Either::Right(values) => values.next().cloned(),
+            };
+            if value.is_some() {
+                self.remaining -= 1;
+                return value;
+            }
+            if !self.advance() {
+                return None;
+            }
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for CVecPipelinedIter<'_, T>
+where
+    T: Clone + Send + 'static + for<'a> Deserialize<'a>,
+{}
+
+// Chunk Iterator
+
+/// One chunk of elements yielded by `CVec::chunks`, dereferences to a slice
+pub enum CVecChunk<'i, T> {
+    /// A chunk which had to be decompressed
+    Decompressed(Vec<T>),
+    /// A chunk which was already held uncompressed
+    Borrowed(&'i [T]),
+}
+
+impl<T> std::ops::Deref for CVecChunk<'_, T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        match self {
+            CVecChunk::Decompressed(values) => values,
+            CVecChunk::Borrowed(values) => values,
+        }
+    }
+}
+
+/// Iterator over the chunks of a CVec, returned by `CVec::chunks`
+pub struct CVecChunks<'i, T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0, C: Cache = Cached<T, CHUNK_ELEMS>> {
+    chunk_idx: usize,
+    inner: &'i CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>,
+}
+
+impl<'i, T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecChunks<'i, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    pub(super) fn new(inner: &'i CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>) -> Self {
+        Self { chunk_idx: 0, inner }
+    }
+}
+
+impl<'i, T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Iterator for CVecChunks<'i, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    type Item = CVecChunk<'i, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = self.inner.chunk(self.chunk_idx)?;
+        self.chunk_idx += 1;
+        Some(chunk)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.inner.chunk_count().saturating_sub(self.chunk_idx);
+        (len, Some(len))
+    }
+}
+impl<'i, T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> ExactSizeIterator for CVecChunks<'i, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: for<'a> Deserialize<'a>,
+{}
+
+// Windows "Iterator"
+
+/// Iterator over overlapping windows of a CVec, returned by `CVec::windows`
+///
+/// This does not implement [`Iterator`]: windows can span two chunks so they are copied into a buffer owned by the
+/// iterator, each window borrows it until the next call to `next`.
+pub struct CVecWindows<'i, T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0, C: Cache = Cached<T, CHUNK_ELEMS>> {
+    size: usize,
+    chunk_idx: usize,
+    /// Start of the next window in `buffer`
+    start: usize,
+    /// The tail of the previous chunk (at most `size - 1` elements) followed by the current chunk
+    buffer: Vec<T>,
+    inner: &'i CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>,
+}
+
+impl<'i, T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecWindows<'i, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Clone + for<'a> Deserialize<'a>,
+{
+    pub(super) fn new(inner: &'i CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>, size: usize) -> Self {
+        assert!(size != 0, "window size must be non-zero");
+        Self { size, chunk_idx: 0, start: 0, buffer: Vec::new(), inner }
+    }
+    /// Advances the iterator and returns the next window, or `None` once the end of the collection is reached.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<&[T]> {
+        while self.buffer.len() - self.start < self.size {
+            let chunk = self.inner.chunk(self.chunk_idx)?;
+            self.chunk_idx += 1;
+            self.buffer.drain(..self.start);
+            self.start = 0;
+            match chunk {
+                CVecChunk::Decompressed(values) => self.buffer.extend(values),
+                CVecChunk::Borrowed(values) => self.buffer.extend_from_slice(values),
+            }
+        }
+        self.start += 1;
+        Some(&self.buffer[self.start - 1..][..self.size])
+    }
+    /// Calls `f` on each remaining window.
+    pub fn for_each<F: FnMut(&[T])>(mut self, mut f: F) {
+        while let Some(window) = self.next() {
+            f(window);
+        }
+    }
+}
+
+// Mutable borrow "Iterator"
+
+/// Mutable iterator over a CVec, returned by `CVec::iter_mut`
+///
+/// This does not implement [`Iterator`]: each element borrows the iterator, which is what allows its chunk to be
+/// compressed again as soon as the iteration moves past it.
+pub struct CVecIterMut<'i, T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0, C: Cache = Cached<T, CHUNK_ELEMS>> {
+    idx: usize,
+    inner: &'i mut CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>,
+}
+
+impl<'i, T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecIterMut<'i, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Serialize + for<'a> Deserialize<'a>,
+{
+    pub(super) fn new(inner: &'i mut CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>) -> Self {
+        Self { idx: 0, inner }
+    }
+    /// Advances the iterator and returns the next element, or `None` once all elements have been visited.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<&mut T> {
+        let idx = self.idx;
+        self.idx += 1;
+        self.inner.get_mut(idx)
+    }
+    /// Calls `f` on each remaining element.
+    pub fn for_each<F: FnMut(&mut T)>(mut self, mut f: F) {
+        while let Some(x) = self.next() {
+            f(x);
+        }
+    }
+}
+
+// Extend
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Extend<T> for CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Serialize
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for i in iter {
+            self.push(i);
+        }
     }
 }
 
@@ -176,9 +619,7 @@ where
 {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
         let mut c = Self::default();
-        for i in iter {
-            c.push(i);
-        }
+        c.extend(iter);
         c
     }
 }
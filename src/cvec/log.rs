@@ -0,0 +1,169 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::stream::{invalid_data, read_exact_bytes, read_len, write_len};
+use super::{cache::Cache, inner::{CVec as CVecInner, Chunk}};
+
+/// When the log of a CVec is synced to disk, see `CVec::set_log`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LogSync {
+    /// After every chunk, so a chunk is durable once the push which filled it returns
+    Always,
+    /// After every this many chunks, a crash may lose the chunks written since the last sync
+    EveryChunks(usize),
+    /// Only on `CVec::sync_log`, otherwise the OS writes the log back whenever it likes
+    Never,
+}
+
+/// The file chunks are logged to, it is not serialized or cloned
+pub(super) struct ChunkLog {
+    file: File,
+    sync: LogSync,
+    /// The number of chunks written since the last sync
+    unsynced: usize,
+    /// The first error hit while logging a chunk, nothing is logged anymore after it
+    error: Option<io::Error>,
+}
+
+impl ChunkLog {
+    fn write_chunk(&mut self, elems: usize, data: &[u8]) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(err) = self.try_write_chunk(elems, data) {
+            self.error = Some(err);
+        }
+    }
+    fn try_write_chunk(&mut self, elems: usize, data: &[u8]) -> io::Result<()> {
+        // A single write, so a crash leaves at most one partial chunk at the end
+        let mut record = Vec::with_capacity(16 + data.len());
+        write_len(&mut record, elems)?;
+        write_len(&mut record, data.len())?;
+        record.extend_from_slice(data);
+        self.file.write_all(&record)?;
+        self.unsynced += 1;
+        let sync = match self.sync {
+            LogSync::Always => true,
+            LogSync::EveryChunks(chunks) => self.unsynced >= chunks,
+            LogSync::Never => false,
+        };
+        if sync {
+            self.sync()?;
+        }
+        Ok(())
+    }
+    fn sync(&mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// Logs every chunk to the file at `path` as soon as it is pushed, so that after a crash the collection can be
+    /// recovered with [`recover_log`](Self::recover_log), losing at most the elements which were not yet pushed
+    /// into a chunk (and, depending on `sync`, the chunks which were not yet synced).
+    ///
+    /// The log starts out with the chunks which are already in the collection, it is written to a temporary file
+    /// next to `path` first which then replaces `path`. From then on it is only appended to, it records the
+    /// elements in the order they were pushed: changes to elements which were already pushed into a chunk (e.g.
+    /// with `get_mut`, `pop` or `remove`) are not logged. Chunks kept uncompressed in the raw window are compressed
+    /// once more for the log.
+    ///
+    /// The log uses the layout written by [`CVecWriter`](crate::CVecWriter), but does not end in its end marker.
+    /// A collection logs to at most one file, clones do not log.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::{CVec, LogSync};
+    ///
+    /// let path = std::env::temp_dir().join(format!("log_example-{}.log", std::process::id()));
+    /// let mut compressed_stack: CVec<u32> = CVec::default();
+    /// compressed_stack.set_log(&path, LogSync::Always).unwrap();
+    /// compressed_stack.extend(0..(16 * 1024 + 10));
+    /// drop(compressed_stack);
+    /// let recovered: CVec<u32> = CVec::recover_log(&path).unwrap();
+    /// assert!((0..(16 * 1024)).eq(&recovered));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn set_log(&mut self, path: impl AsRef<Path>, sync: LogSync) -> io::Result<()>
+    where
+        T: Serialize,
+    {
+        if let LogSync::EveryChunks(0) = sync {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot sync every 0 chunks"));
+        }
+        self.log = None;
+        let path = path.as_ref();
+        let mut temp_path = OsString::from(path);
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        for (chunk_idx, chunk) in self.compressed_storage.iter().enumerate() {
+            let chunk = chunk.compressed_bytes(COMPRESSION_LEVEL);
+            write_len(&mut writer, self.chunk_ends[chunk_idx] - self.chunk_start(chunk_idx))?;
+            write_len(&mut writer, chunk.len())?;
+            writer.write_all(&chunk)?;
+        }
+        writer.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+        std::fs::rename(&temp_path, path)?;
+        let file = File::options().append(true).open(path)?;
+        self.log = Some(ChunkLog { file, sync, unsynced: 0, error: None });
+        Ok(())
+    }
+    /// Syncs the log to disk. Pushing cannot fail, so this is where an error hit while logging a chunk is
+    /// returned: the log is left as it was before that chunk and nothing more is logged.
+    pub fn sync_log(&mut self) -> io::Result<()> {
+        self.log.as_mut().map_or(Ok(()), ChunkLog::sync)
+    }
+    /// Syncs the log to disk and stops logging, the file is left where it is.
+    pub fn stop_log(&mut self) -> io::Result<()> {
+        let result = self.sync_log();
+        self.log = None;
+        result
+    }
+    /// Reads the chunks of a log written by [`set_log`](Self::set_log) back into a collection, without
+    /// recompressing them. A chunk cut short by a crash at the end of the log is ignored.
+    pub fn recover_log(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut cvec = Self::default();
+        loop {
+            let record = read_len(&mut reader).and_then(|elems| {
+                let len = read_len(&mut reader)?;
+                Ok((elems, read_exact_bytes(&mut reader, len)?))
+            });
+            let (elems, data) = match record {
+                Ok(record) => record,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            };
+            if elems == 0 {
+                // The end marker of a chunk stream
+                break;
+            }
+            if elems > cvec.chunk_elems() {
+                return Err(invalid_data("chunk length does not match the chunk size"));
+            }
+            cvec.chunk_ends.push(cvec.compressed_len() + elems);
+            cvec.compressed_storage.push(Chunk::Compressed(data));
+        }
+        Ok(cvec)
+    }
+    /// Logs a chunk which is about to be pushed.
+    pub(super) fn log_chunk(&mut self, chunk: &Chunk<T>, elems: usize)
+    where
+        T: Serialize,
+    {
+        if let Some(log) = &mut self.log {
+            log.write_chunk(elems, &chunk.compressed_bytes(COMPRESSION_LEVEL));
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::cache::{Cache, Cached};
+use super::inner::Chunk;
+use super::CVecInner;
+use crate::compression::{compress_bytes_fully, decompress, decompress_bytes};
+
+/// How far `CVec::maintain` got, it is not serialized or cloned
+#[derive(Default)]
+pub(super) struct Maintenance {
+    /// The chunk to look at next
+    cursor: usize,
+    /// Hashes of the compressed bytes of the chunks which were already recompressed, chunks change their bytes
+    /// whenever they are mutated so stale hashes are harmless
+    compacted: HashSet<u64>,
+}
+
+fn hash_bytes(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// Does one step of maintenance, returning `false` once there is nothing left to do. See [`Maintained`] to
+    /// run it on a background thread whenever the collection is idle.
+    ///
+    /// A step either merges two neighbouring chunks which together fit in one chunk (as left behind by e.g.
+    /// `remove`, `retain` or `append`), or recompresses a chunk at `level` if that makes it smaller. Chunks which
+    /// are uncompressed (in the raw window or open for mutation) are left alone, and chunks which are mutated later
+    /// are compressed at `COMPRESSION_LEVEL` again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is not between 0 and 11.
+    pub fn maintain(&mut self, level: i32) -> bool
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        assert!((0..=11).contains(&level), "Compression level must be between 0 and 11");
+        let chunks = self.compressed_storage.len();
+        for _ in 0..chunks {
+            let chunk_idx = self.maintenance.cursor % chunks;
+            self.maintenance.cursor = chunk_idx + 1;
+            if self.merge_step(chunk_idx, level) || self.recompress_step(chunk_idx, level) {
+                return true;
+            }
+        }
+        // A whole lap without work, forget the hashes of chunks which are gone
+        let current: HashSet<u64> = self.compressed_storage.iter().filter_map(|chunk| match chunk {
+            Chunk::Compressed(data) => Some(hash_bytes(data)),
+            Chunk::Raw(_) | Chunk::Spilled(_) => None,
+        }).collect();
+        self.maintenance.compacted.retain(|hash| current.contains(hash));
+        false
+    }
+    /// Merges the chunk after `chunk_idx` into it if both are compressed and fit in one chunk.
+    fn merge_step(&mut self, chunk_idx: usize, level: i32) -> bool
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        let next = chunk_idx + 1;
+        let (Some(Chunk::Compressed(data)), Some(Chunk::Compressed(next_data))) = (self.compressed_storage.get(chunk_idx), self.compressed_storage.get(next)) else {
+            return false;
+        };
+        if self.chunk_ends[next] - self.chunk_start(chunk_idx) > self.chunk_elems() {
+            return false;
+        }
+        let mut values: Vec<T> = decompress(data);
+        values.extend(decompress::<Vec<T>>(next_data));
+        let merged = compress_bytes_fully(&postcard::to_stdvec(&values).unwrap(), level); // Only errors on OOM or incorrect `serialize` implementation
+        self.maintenance.compacted.insert(hash_bytes(&merged));
+        self.compressed_storage[chunk_idx] = Chunk::Compressed(merged);
+        self.chunk_ends[chunk_idx] = self.chunk_ends[next];
+        // Its elements now belong to `chunk_idx`, so removing it leaves the later chunk ends as they are
+        self.remove_chunk(next);
+        true
+    }
+    /// Recompresses the chunk at `chunk_idx` at `level` unless that was done already.
+    pub(super) fn recompress_step(&mut self, chunk_idx: usize, level: i32) -> bool {
+        let Chunk::Compressed(data) = &mut self.compressed_storage[chunk_idx] else {
+            return false;
+        };
+        if !self.maintenance.compacted.insert(hash_bytes(data)) {
+            return false;
+        }
+        // The serialized elements are the same whatever the level, so they need not be deserialized
+        let recompressed = compress_bytes_fully(&decompress_bytes(data), level);
+        if recompressed.len() < data.len() {
+            self.maintenance.compacted.insert(hash_bytes(&recompressed));
+            // The values are unchanged, so decompressed copies in the cache are still valid
+            *data = recompressed;
+        }
+        true
+    }
+}
+
+struct MaintainedShared<V> {
+    collection: Mutex<V>,
+    /// Incremented by every `Maintained::lock`, the worker only does maintenance once it stops changing
+    accesses: AtomicU64,
+    stop: Mutex<bool>,
+    stopped: Condvar,
+}
+
+impl<V> MaintainedShared<V> {
+    fn lock(&self) -> MutexGuard<'_, V> {
+        self.collection.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A CVec shared with a background thread which maintains it while it is idle
+///
+/// Every `interval` the thread checks whether the collection was locked since it last looked, if not it calls
+/// [`CVec::maintain`] until there is nothing left to do and then drops the decompressed chunks held by the cache.
+/// The thread only ever holds the lock for a single step and stops as soon as the collection is locked again.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use compressed_collections::{CVec, Maintained};
+///
+/// let compressed_stack: CVec<u32> = (0..(16 * 1024)).collect();
+/// let maintained = Maintained::new(compressed_stack, 9, Duration::from_millis(10));
+/// maintained.lock().push(16 * 1024);
+/// std::thread::sleep(Duration::from_millis(100));
+/// assert_eq!(maintained.lock().len(), 16 * 1024 + 1);
+/// let compressed_stack = maintained.into_inner();
+/// assert!((0..=(16 * 1024)).eq(&compressed_stack));
+/// ```
+pub struct Maintained<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0, C: Cache = Cached<T, CHUNK_ELEMS>> {
+    shared: Arc<MaintainedShared<CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl<T, C, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Maintained<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Serialize + for<'a> Deserialize<'a> + Send + 'static,
+    C: Cache + Send + 'static,
+{
+    /// Starts a thread which maintains `collection` at compression `level`, see [`CVec::maintain`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is not between 0 and 11.
+    pub fn new(collection: CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>, level: i32, interval: Duration) -> Self {
+        assert!((0..=11).contains(&level), "Compression level must be between 0 and 11");
+        let shared = Arc::new(MaintainedShared {
+            collection: Mutex::new(collection),
+            accesses: AtomicU64::new(0),
+            stop: Mutex::new(false),
+            stopped: Condvar::new(),
+        });
+        let worker = {
+            let shared = Arc::clone(&shared);
+            std::thread::spawn(move || Self::work(&shared, level, interval))
+        };
+        Self { shared, worker: Some(worker) }
+    }
+    fn work(shared: &MaintainedShared<CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>>, level: i32, interval: Duration) {
+        let mut seen = shared.accesses.load(Ordering::Relaxed);
+        loop {
+            let stop = shared.stop.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let stop = shared.stopped.wait_timeout_while(stop, interval, |stop| !*stop).unwrap_or_else(|poisoned| poisoned.into_inner()).0;
+            if *stop {
+                return;
+            }
+            drop(stop);
+            let accesses = shared.accesses.load(Ordering::Relaxed);
+            if std::mem::replace(&mut seen, accesses) != accesses {
+                continue;
+            }
+            // Stopping also counts as an access, so this gives up the lock straight away
+            while let Ok(mut collection) = shared.collection.try_lock() {
+                if shared.accesses.load(Ordering::Relaxed) != seen {
+                    break;
+                }
+                if !collection.maintain(level) {
+                    collection.release_cache();
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Maintained<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// Locks the collection, waiting for at most one maintenance step to finish.
+    pub fn lock(&self) -> MutexGuard<'_, CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>> {
+        self.shared.accesses.fetch_add(1, Ordering::Relaxed);
+        self.shared.lock()
+    }
+    /// Stops the background thread and returns the collection.
+    pub fn into_inner(mut self) -> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+        self.stop();
+        let shared = Arc::clone(&self.shared);
+        drop(self);
+        let shared = Arc::into_inner(shared).unwrap_or_else(|| unreachable!("the worker has been joined"));
+        shared.collection.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    fn stop(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            *self.shared.stop.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = true;
+            self.shared.accesses.fetch_add(1, Ordering::Relaxed);
+            self.shared.stopped.notify_all();
+            // A panic of the worker poisoned the lock at worst, which `lock` ignores
+            let _ = worker.join();
+        }
+    }
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Drop for Maintained<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -1,16 +1,114 @@
-mod cache;
+mod appender;
+mod budget;
+pub(crate) mod cache;
+#[cfg(feature = "arrow")]
+mod columnar;
 mod iterator;
+mod log;
+#[cfg(feature = "encryption")]
+mod encryption;
+mod eviction;
+mod fallible;
+mod frozen;
 mod inner;
+mod maintenance;
+mod offsets;
+mod persist;
+#[cfg(feature = "tokio")]
+mod asynchronous;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod pool;
+mod recovery;
+mod spill;
+mod store;
+mod stream;
+mod verify;
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::io;
+use std::ops::{Bound, Index, IndexMut, RangeBounds};
+use std::thread::JoinHandle;
 
 use either::Either;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use self::cache::{Cache, Cached, Uncached, CacheAccess, RcCacheAccess, RcCached};
-use self::inner::CVec as CVecInner;
-pub use self::iterator::{CVecIntoIter, CVecIntoIterUncached};
-use crate::compression::{compress, decompress};
+use self::cache::{Budgeted, Cache, CacheBudget, CachePin, Cached, ChunkBytes, Uncached, CacheAccess, MemoCacheAccess, Memoized, Prefetching, RcCached, SharedCacheAccess, SyncCached};
+pub use self::cache::DEFAULT_CACHE_BUDGET;
+pub use self::eviction::{Clock, EvictionPolicy, Fifo, Lfu, Lru};
+use self::offsets::Indexed;
+use self::pool::Pooled;
+use self::spill::Spill;
+pub use self::pool::{on_release_caches, release_caches, CachePool};
+use self::inner::{copy_values, default_chunk_elems, Chunk, RawCVec};
+pub(crate) use self::inner::CVec as CVecInner;
+pub(crate) use self::inner::CompressedElem;
+#[cfg(feature = "tokio")]
+pub use self::asynchronous::CVecStream;
+#[cfg(feature = "rayon")]
+pub use self::parallel::{CVecIntoParIter, CVecParIter};
+pub use self::appender::{CVecAppender, CVecShard};
+pub use self::budget::OverflowPolicy;
+#[cfg(feature = "encryption")]
+pub use self::encryption::EncryptionKey;
+pub use self::frozen::{FrozenCVec, FrozenIter};
+pub use self::log::LogSync;
+pub use self::maintenance::Maintained;
+pub use self::store::{ChunkStore, VecChunkStore};
+pub use self::stream::{CVecReader, CVecWriter};
+pub use self::recovery::{CorruptChunkPolicy, CVecTryIter};
+pub use self::verify::{ChunkDamage, DamagedChunk};
+pub use self::iterator::{CVecChunk, CVecChunks, CVecIntoIter, CVecIntoIterUncached, CVecIter, CVecIterMut, CVecPipelinedIter, CVecWindows};
+use crate::compression::{compress, decompress, decompress_bytes, decompress_into, decompress_nth, serialized_size};
 
 pub type Value<A, B> = Option<Either<A, B>>;
+
+/// Size of the chunks of a [`CVecDyn`], chosen at runtime
+///
+/// A size in bytes bounds both the number of elements in a chunk, by their in-memory size, and the serialized size
+/// of the elements, which is counted as they are pushed: a chunk is flushed once either is reached. So chunks of
+/// elements with heap data such as `String`s hold about as many bytes whatever the length of the elements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChunkSize {
+    /// A number of elements
+    SizeElements(usize),
+    /// A number of bytes of uncompressed elements
+    SizeBytes(usize),
+    /// A number of megabytes of uncompressed elements
+    SizeMB(usize),
+    /// 10MB of uncompressed elements
+    Default,
+}
+
+impl ChunkSize {
+    /// The number of elements of type `T` in a chunk, at least 1.
+    pub(crate) fn elems<T>(self) -> usize {
+        let elem_size = std::mem::size_of::<T>().max(1);
+        let elems = match self {
+            ChunkSize::SizeElements(x) => x,
+            ChunkSize::SizeBytes(x) => x / elem_size,
+            ChunkSize::SizeMB(x) => x.saturating_mul(1024 * 1024) / elem_size,
+            ChunkSize::Default => 10 * 1024 * 1024 / elem_size,
+        };
+        elems.max(1)
+    }
+    /// The serialized size of the elements in a chunk, if it is given in bytes.
+    pub(crate) fn bytes(self) -> Option<usize> {
+        match self {
+            ChunkSize::SizeBytes(x) => Some(x),
+            ChunkSize::SizeMB(x) => Some(x.saturating_mul(1024 * 1024)),
+            ChunkSize::SizeElements(_) | ChunkSize::Default => None,
+        }
+    }
+}
+
+/// The length of a collection at some point, to [`rollback`](CVec::rollback) to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Checkpoint(usize);
+
+/// Number of sorted runs merged at once by [`CVec::sort`], each holds one chunk uncompressed during the merge
+const SORT_MERGE_WAYS: usize = 16;
 #[allow(non_snake_case)]
 const fn Compressed<A, B>(a: A) -> Value<A, B> {
     Some(Either::Left(a))
@@ -22,6 +120,10 @@ const fn Uncompressed<A, B>(b: B) -> Value<A, B> {
 
 /// A stack which automatically compresses itself over a certain size
 ///
+/// Elements are read with [`get_ref`](CVec::get_ref) and [`get_mut`](CVec::get_mut): `v[i]` does not
+/// compile, as reading a chunk fills the cache through `&mut self`. Use [`CVecMemo`] where indexing syntax is
+/// needed.
+///
 /// # Examples
 ///
 /// ```
@@ -46,6 +148,10 @@ impl<T> CVec<T, 0, 0> {
 
 /// A stack which automatically compresses itself over a certain size
 ///
+/// Elements are read with [`get`](CVecRc::get), which clones them, and [`get_mut`](CVecRc::get_mut): `v[i]`
+/// does not compile, as the cache cannot hand out plain references. Use [`CVecMemo`] where indexing syntax is
+/// needed.
+///
 /// # Examples
 ///
 /// ```
@@ -92,31 +198,653 @@ impl<T> CVecUncached<T, 0, 0> {
     }
 }
 
+/// A stack which automatically compresses itself over a certain size, and which can be read with
+/// [`get`](CVecSync::get) from several threads at once
+///
+/// The chunk read last is kept decompressed behind a `RwLock`, reading another chunk briefly takes the write lock.
+/// The collection is `Send` and `Sync` whenever `T` is, so it can also be put in an `Arc` and shared with threads
+/// which outlive the current scope.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use compressed_collections::CVecSync;
+///
+/// let mut compressed_stack = CVecSync::new::<1024, 0>();
+/// for i in 0..(4096) {
+///     compressed_stack.push(i);
+/// }
+/// std::thread::scope(|s| {
+///     for t in 0..4 {
+///         let compressed_stack = &compressed_stack;
+///         s.spawn(move || assert_eq!(compressed_stack.get(t * 1024), Some(t * 1024)));
+///     }
+/// });
+/// let shared = Arc::new(compressed_stack);
+/// let handles: Vec<_> = (0..4).map(|t| {
+///     let shared = Arc::clone(&shared);
+///     std::thread::spawn(move || shared.get(t * 1000))
+/// }).collect();
+/// for (t, handle) in handles.into_iter().enumerate() {
+///     assert_eq!(handle.join().unwrap(), Some(t * 1000));
+/// }
+/// ```
+pub type CVecSync<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> = CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, SyncCached<T, CHUNK_ELEMS>>;
+
+impl<T> CVecSync<T, 0, 0> {
+    pub fn new<const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>() -> CVecSync<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        CVecInner::default()
+    }
+}
+
+/// A stack which automatically compresses itself over a certain size, and which decompresses the next chunk in the
+/// background while chunks are read in order with [`get_ref`](CVecPrefetch::get_ref)
+///
+/// # Examples
+///
+/// ```
+/// use compressed_collections::CVecPrefetch;
+///
+/// let mut compressed_stack = CVecPrefetch::new::<1024, 0>();
+/// for i in 0..(16 * 1024) {
+///     compressed_stack.push(i);
+/// }
+/// for i in 0..(16 * 1024) {
+///     assert_eq!(compressed_stack.get_ref(i), Some(&i));
+/// }
+/// ```
+pub type CVecPrefetch<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> = CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, Prefetching<T, CHUNK_ELEMS>>;
+
+impl<T> CVecPrefetch<T, 0, 0> {
+    pub fn new<const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>() -> CVecPrefetch<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        CVecInner::default()
+    }
+}
+
+/// A stack which automatically compresses itself over a certain size, and which can be indexed like a `Vec`
+///
+/// Chunks read through `&self` (e.g. with `v[i]`) stay decompressed until the collection is next mutated, so
+/// prefer [`CVec`] unless indexing syntax is needed.
+///
+/// # Examples
+///
+/// ```
+/// use compressed_collections::CVecMemo;
+///
+/// let mut compressed_stack = CVecMemo::new::<1024, 0>();
+/// for i in 0..(4096) {
+///     compressed_stack.push(i);
+/// }
+/// compressed_stack[10] += 1;
+/// assert_eq!(compressed_stack[10], 11);
+/// ```
+///
+/// # Panics
+///
+/// Indexing panics if the index is out of bounds, like `Vec`. Otherwise this should not panic (except on out of
+/// memory conditions). If it does, please submit an issue.
+pub type CVecMemo<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> = CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, Memoized<T>>;
+
+impl<T> CVecMemo<T, 0, 0> {
+    pub fn new<const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>() -> CVecMemo<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        CVecInner::default()
+    }
+}
+
+/// A stack which automatically compresses itself over a certain size, and which keeps as many chunks decompressed
+/// as fit in a memory budget
+///
+/// The budget defaults to [`DEFAULT_CACHE_BUDGET`] bytes and can be changed with
+/// [`set_cache_budget`](CVecBudgeted::set_cache_budget). Which chunks are evicted first is up to the
+/// [`EvictionPolicy`] `P`, by default the least recently read ones ([`Lru`]).
+///
+/// # Examples
+///
+/// ```
+/// use compressed_collections::CVecBudgeted;
+///
+/// let mut compressed_stack = CVecBudgeted::new::<1024, 0>();
+/// compressed_stack.set_cache_budget(4 * 1024 * std::mem::size_of::<u32>());
+/// for i in 0..(16 * 1024) {
+///     compressed_stack.push(i);
+/// }
+/// // Four chunks stay decompressed
+/// for i in (0..(4 * 1024)).cycle().take(64 * 1024) {
+///     assert_eq!(compressed_stack.get_ref(i), Some(&i));
+/// }
+/// ```
+pub type CVecBudgeted<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0, P = Lru> = CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, Budgeted<T, P>>;
+
+impl<T> CVecBudgeted<T, 0, 0> {
+    pub fn new<const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>() -> CVecBudgeted<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        CVecInner::default()
+    }
+}
+
+/// A stack which automatically compresses itself over a certain size, and which indexes where the elements of each
+/// chunk start so that reading one does not deserialize the whole chunk
+///
+/// Chunks are still decompressed on every read, so this only pays off for elements which are costly to
+/// deserialize, e.g. large strings or vectors. See [`CVecBudgeted`] to keep chunks decompressed instead.
+///
+/// # Examples
+///
+/// ```
+/// use compressed_collections::CVecIndexed;
+///
+/// let mut compressed_stack = CVecIndexed::new::<1024, 0>();
+/// for i in 0..(4 * 1024) {
+///     compressed_stack.push(i.to_string().repeat(100));
+/// }
+/// assert_eq!(compressed_stack.get_ref(1500).map(String::len), Some(400));
+/// ```
+pub type CVecIndexed<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> = CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, Indexed<T>>;
+
+impl<T> CVecIndexed<T, 0, 0> {
+    pub fn new<const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>() -> CVecIndexed<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        CVecInner::default()
+    }
+}
+
+/// A stack which automatically compresses itself over a certain size, and which shares a budget of decompressed
+/// memory with the other collections in its [`CachePool`]
+///
+/// Collections created with `new`, cloned or deserialized join [`CachePool::thread_default`], use
+/// [`new_in`](CVecPooled::new_in) or [`set_cache_pool`](CVecPooled::set_cache_pool) to pick another pool.
+///
+/// # Examples
+///
+/// ```
+/// use compressed_collections::{CachePool, CVecPooled};
+///
+/// let pool = CachePool::new(4 * 1024 * std::mem::size_of::<u32>());
+/// let mut stacks: Vec<_> = (0..100).map(|_| CVecPooled::new_in::<1024, 0>(&pool)).collect();
+/// for stack in &mut stacks {
+///     stack.extend(0..(2 * 1024));
+///     assert_eq!(stack.get_ref(10), Some(&10));
+/// }
+/// // Only the four chunks read last are still decompressed
+/// assert_eq!(pool.used(), 4 * 1024 * std::mem::size_of::<u32>());
+/// ```
+pub type CVecPooled<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> = CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, Pooled<T>>;
+
+impl<T> CVecPooled<T, 0, 0> {
+    pub fn new<const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>() -> CVecPooled<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        CVecInner::default()
+    }
+    pub fn new_in<const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>(pool: &CachePool) -> CVecPooled<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        let mut cvec = CVecInner::default();
+        cvec.set_cache_pool(pool);
+        cvec
+    }
+}
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecPooled<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+    /// The pool this collection's cache is in.
+    pub fn cache_pool(&self) -> &CachePool {
+        self.cache.pool()
+    }
+    /// Moves this collection's cache to `pool`, dropping its decompressed chunks.
+    pub fn set_cache_pool(&mut self, pool: &CachePool) {
+        self.cache = pool.join();
+    }
+}
+
+/// A stack which automatically compresses itself over a certain size, with a chunk size chosen at runtime
+///
+/// This is the same collection as [`CVec`] with `CHUNK_ELEMS` set to 0, which means the chunk size is stored in
+/// the collection instead. It is not serialized: deserialized collections use [`ChunkSize::Default`].
+///
+/// # Examples
+///
+/// ```
+/// use compressed_collections::{CVecDyn, ChunkSize};
+///
+/// let mut compressed_stack: CVecDyn<u32> = CVecDyn::with_chunk_size(ChunkSize::SizeBytes(4096));
+/// for i in 0..(4096) {
+///     compressed_stack.push(i);
+/// }
+/// assert_eq!(compressed_stack.chunk_elems(), 1024);
+/// assert_eq!(compressed_stack.compressed_chunks().count(), 4);
+/// ```
+pub type CVecDyn<T, const COMPRESSION_LEVEL: i32 = 0> = CVecInner<T, 0, COMPRESSION_LEVEL, Cached<T, 0>>;
+
+impl<T, C: Cache, const COMPRESSION_LEVEL: i32> CVecInner<T, 0, COMPRESSION_LEVEL, C> {
+    /// Creates an empty collection whose chunks hold `chunk_size` worth of elements.
+    pub fn with_chunk_size(chunk_size: ChunkSize) -> Self {
+        Self { chunk_elems: chunk_size.elems::<T>(), chunk_bytes: chunk_size.bytes(), ..Self::default() }
+    }
+}
+
 impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>Default for CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
     fn default() -> Self {
-        let _ = Self::ASSERT_SUPPORTED_SIZE;
-        let _ = Self::COMPRESSION_LEVEL_CHECK;
+        let () = Self::COMPRESSION_LEVEL_CHECK;
         Self {
             uncompressed_buffer: Default::default(),
             compressed_storage: Default::default(),
+            chunk_ends: Default::default(),
             cache: Default::default(),
+            chunk_elems: default_chunk_elems::<T, CHUNK_ELEMS>(),
+            chunk_bytes: None,
+            buffer_bytes: (0, 0),
+            open_chunk: None,
+            open_clean: None,
+            raw_window: 0,
+            maintenance: Default::default(),
+            spill: None,
+            log: None,
+            budget: None,
+            corrupt_policy: Default::default(),
         }
     }
 }
 
 impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
-    const ASSERT_SUPPORTED_SIZE: () = assert!(CHUNK_ELEMS > 0, "Chunk size must be greater than 0");
     const COMPRESSION_LEVEL_CHECK: () = assert!(COMPRESSION_LEVEL >= 0 && COMPRESSION_LEVEL <= 11, "Compression level must be between 0 and 11");
 
+    /// Creates a collection of `len` elements where each element is `f(idx)`, compressing each chunk as soon as it
+    /// is filled so the elements never all exist uncompressed.
+    pub fn from_fn<F>(len: usize, f: F) -> Self
+    where
+        T: Serialize,
+        F: FnMut(usize) -> T,
+    {
+        (0..len).map(f).collect()
+    }
+    /// Creates a collection of `len` clones of `value`.
+    ///
+    /// A single full chunk is compressed and its bytes are shared by copy between all full chunks.
+    pub fn repeat(value: T, len: usize) -> Self
+    where
+        T: Clone + Serialize,
+    {
+        let mut cvec = Self::default();
+        cvec.fill(len, value);
+        cvec
+    }
     pub fn push(&mut self, value: T)
     where
         T: Serialize,
     {
         self.uncompressed_buffer.push(value);
-        if self.uncompressed_buffer.len() >= CHUNK_ELEMS {
-            let compressed = compress(&self.uncompressed_buffer, COMPRESSION_LEVEL);
-            self.compressed_storage.push(compressed);
-            self.uncompressed_buffer.clear();
+        if self.buffer_full() {
+            self.compress_pushed(Self::compress_buffer);
+        }
+    }
+    /// Appends all elements of a slice, compressing each run of `CHUNK_ELEMS` elements straight from the slice.
+    ///
+    /// Only the elements needed to top up the uncompressed buffer, and those left over at the end, are cloned.
+    pub fn extend_from_slice(&mut self, mut values: &[T])
+    where
+        T: Clone + Serialize,
+    {
+        if self.chunk_bytes.is_some() {
+            // Where chunks end depends on the size of each element
+            values.iter().cloned().for_each(|value| self.push(value));
+            return;
+        }
+        self.reserve(values.len());
+        let chunk_elems = self.chunk_elems();
+        if !self.uncompressed_buffer.is_empty() {
+            let fill = chunk_elems.saturating_sub(self.uncompressed_buffer.len()).min(values.len());
+            self.uncompressed_buffer.extend_from_slice(&values[..fill]);
+            values = &values[fill..];
+            if self.uncompressed_buffer.len() >= chunk_elems {
+                self.compress_buffer();
+            }
+        }
+        let mut chunks = values.chunks_exact(chunk_elems);
+        for chunk in &mut chunks {
+            self.push_chunk(Chunk::Compressed(compress(chunk, COMPRESSION_LEVEL)), chunk_elems);
+        }
+        self.uncompressed_buffer.extend_from_slice(chunks.remainder());
+    }
+    /// Pushes all elements of a slice, the bulk equivalent of [`push`](Self::push).
+    ///
+    /// Same as [`extend_from_slice`](Self::extend_from_slice): whole chunks are serialized and compressed straight
+    /// from the slice in one pass rather than going through the uncompressed buffer element by element.
+    pub fn push_slice(&mut self, values: &[T])
+    where
+        T: Clone + Serialize,
+    {
+        self.extend_from_slice(values);
+    }
+    /// Moves all the elements of `other` to the end of `self`, leaving `other` empty.
+    ///
+    /// When `other`'s chunks fit within this collection's chunk size they are moved over as-is without being
+    /// recompressed (the uncompressed buffer of `self` becomes a short chunk of its own), otherwise the elements are
+    /// decompressed and pushed one chunk at a time.
+    pub fn append<C2: Cache, const OTHER_CHUNK_ELEMS: usize, const OTHER_COMPRESSION_LEVEL: i32>(
+        &mut self,
+        other: &mut CVecInner<T, OTHER_CHUNK_ELEMS, OTHER_COMPRESSION_LEVEL, C2>,
+    )
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        other.flush();
+        // Its raw window is not part of this collection's, which would leave the chunks raw for good
+        other.compress_raw();
+        other.cache.kill_all();
+        let chunks = std::mem::take(&mut other.compressed_storage);
+        let chunk_ends = std::mem::take(&mut other.chunk_ends);
+        if other.chunk_elems() <= self.chunk_elems() {
+            if !self.uncompressed_buffer.is_empty() && !chunks.is_empty() {
+                self.compress_buffer();
+            }
+            let mut start = 0;
+            for (chunk, end) in chunks.into_iter().zip(chunk_ends) {
+                self.push_chunk(chunk, end - start);
+                start = end;
+            }
+        } else {
+            for chunk in chunks {
+                self.extend(chunk.into_values());
+            }
+        }
+        self.extend(other.uncompressed_buffer.drain(..));
+    }
+    /// Splits the collection into two at the given index, returning a new collection containing the elements
+    /// `[at, len)` and leaving `[0, at)` in `self`.
+    ///
+    /// Whole chunks are moved to the new collection by ownership, only the chunk containing `at` is decompressed:
+    /// its tail is compressed into the first chunk of the new collection and its head becomes the uncompressed
+    /// buffer of `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > len`.
+    #[must_use]
+    pub fn split_off(&mut self, at: usize) -> Self
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        let len = self.len();
+        assert!(at <= len, "`at` split index (is {at}) should be <= len (is {len})");
+        let mut tail = self.empty_like();
+        let compressed_len = self.compressed_len();
+        if at >= compressed_len {
+            tail.uncompressed_buffer = self.uncompressed_buffer.split_off(at - compressed_len);
+            return tail;
+        }
+        self.flush();
+        let Some(Either::Left((chunk_idx, chunk_offset))) = self.split(at) else { unreachable!() };
+        let first_moved = if chunk_offset == 0 { chunk_idx } else { chunk_idx + 1 };
+        let moved = self.compressed_storage.split_off(first_moved);
+        let moved_ends = self.chunk_ends.split_off(first_moved);
+        if chunk_offset != 0 {
+            let mut head = self.compressed_storage.pop().unwrap().into_values();
+            self.chunk_ends.pop();
+            let boundary = head.split_off(chunk_offset);
+            tail.push_chunk(Chunk::Compressed(compress(&boundary, COMPRESSION_LEVEL)), boundary.len());
+            tail.uncompressed_buffer = std::mem::replace(&mut self.uncompressed_buffer, head);
+        } else {
+            tail.uncompressed_buffer = std::mem::take(&mut self.uncompressed_buffer);
+        }
+        for (mut chunk, end) in moved.into_iter().zip(moved_ends) {
+            // The raw window of `self` is not part of the tail's, which would leave the chunk raw for good
+            chunk.compress(COMPRESSION_LEVEL);
+            tail.compressed_storage.push(chunk);
+            tail.chunk_ends.push(end - at);
+        }
+        self.cache.kill_all();
+        tail
+    }
+    /// Retains only the elements specified by the predicate, preserving their order.
+    ///
+    /// Chunks are decompressed one at a time and the kept elements are re-chunked as they go, so at most a couple
+    /// of chunks are held uncompressed at once.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+        F: FnMut(&T) -> bool,
+    {
+        self.flush();
+        self.cache.kill_all();
+        let chunks = std::mem::take(&mut self.compressed_storage);
+        self.chunk_ends.clear();
+        let buffer = std::mem::take(&mut self.uncompressed_buffer);
+        for chunk in chunks {
+            self.extend(chunk.into_values().into_iter().filter(&mut f));
+        }
+        self.extend(buffer.into_iter().filter(f));
+    }
+    /// Sorts the collection.
+    ///
+    /// This sort is stable and, like [`sort_by_key`](Self::sort_by_key), never holds more than 18 chunks
+    /// uncompressed at once: each chunk is sorted on its own and the resulting runs
+    /// are then merged 16 at a time, streaming through their chunks, until a single run is left.
+    pub fn sort(&mut self)
+    where
+        T: Ord + Serialize + for<'a> Deserialize<'a>,
+    {
+        self.merge_sort_by(T::cmp, true);
+    }
+    /// Sorts the collection with a key extraction function, see [`sort`](Self::sort).
+    pub fn sort_by_key<K, F>(&mut self, mut f: F)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.merge_sort_by(|a, b| f(a).cmp(&f(b)), true);
+    }
+    /// Sorts the collection with a comparator function, see [`sort`](Self::sort).
+    ///
+    /// Memory use is bounded by the chunk size rather than the length of the collection: at most 16 sorted runs are
+    /// merged at once, each holding one decompressed chunk, plus the chunk being sorted or filled by the merge.
+    /// Every merge pass decompresses and recompresses the whole collection once, there are
+    /// `ceil(log16(len / CHUNK_ELEMS))` of them.
+    ///
+    /// The chunks are only replaced once the merge is done, so the collection is left as it was if `compare`
+    /// panics. Until then the compressed runs are held alongside them, spilled to disk if the collection spills
+    /// (see [`set_spill_budget`](Self::set_spill_budget)).
+    pub fn sort_by<F>(&mut self, compare: F)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.merge_sort_by(compare, true);
+    }
+    /// Sorts the collection with a comparator function, but might not preserve the order of equal elements.
+    ///
+    /// Has the same memory profile as [`sort_by`](Self::sort_by), only the sorting of each individual chunk is
+    /// unstable.
+    pub fn sort_unstable_by<F>(&mut self, compare: F)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.merge_sort_by(compare, false);
+    }
+    /// Consumes the collection, splitting it into the elements for which `f` returns `true` and those for which it
+    /// returns `false`, see [`Iterator::partition`].
+    ///
+    /// Both collections are built in one pass over the chunks, each is compressed as it fills up so at most one
+    /// chunk per collection (plus the one being read) is held uncompressed.
+    pub fn partition<F>(self, mut f: F) -> (Self, Self)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+        F: FnMut(&T) -> bool,
+    {
+        let (mut left, mut right) = (self.empty_like(), self.empty_like());
+        for value in self {
+            if f(&value) {
+                left.push(value);
+            } else {
+                right.push(value);
+            }
+        }
+        (left, right)
+    }
+    /// Removes consecutive repeated elements, see [`Vec::dedup`].
+    ///
+    /// Like [`retain`](Self::retain) chunks are decompressed one at a time and the storage is rebuilt as they go.
+    pub fn dedup(&mut self)
+    where
+        T: PartialEq + Serialize + for<'a> Deserialize<'a>,
+    {
+        self.dedup_by(|a, b| a == b);
+    }
+    /// Removes all but the first of consecutive elements that resolve to the same key, see [`Vec::dedup_by_key`].
+    pub fn dedup_by_key<K, F>(&mut self, mut key: F)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+        K: PartialEq,
+        F: FnMut(&mut T) -> K,
+    {
+        self.dedup_by(|a, b| key(a) == key(b));
+    }
+    /// Removes all but the first of consecutive elements satisfying an equality relation, see [`Vec::dedup_by`].
+    ///
+    /// `same_bucket` is passed the element being considered and the last element kept, in that order.
+    pub fn dedup_by<F>(&mut self, mut same_bucket: F)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+        F: FnMut(&mut T, &mut T) -> bool,
+    {
+        self.flush();
+        self.cache.kill_all();
+        let chunks = std::mem::take(&mut self.compressed_storage);
+        self.chunk_ends.clear();
+        let buffer = std::mem::take(&mut self.uncompressed_buffer);
+        let mut kept: Option<T> = None;
+        for mut value in chunks.into_iter().flat_map(Chunk::into_values).chain(buffer) {
+            if let Some(prev) = &mut kept {
+                if same_bucket(&mut value, prev) {
+                    continue;
+                }
+            }
+            if let Some(prev) = kept.replace(value) {
+                self.push(prev);
+            }
+        }
+        self.extend(kept);
+    }
+    /// Inserts an element at position `idx`, shifting all elements after it to the right.
+    ///
+    /// Only the chunk containing `idx` is decompressed, if it overflows it is split in two rather than shifting
+    /// elements through all following chunks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx > len`.
+    pub fn insert(&mut self, idx: usize, value: T)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        let len = self.len();
+        assert!(idx <= len, "insertion index (is {idx}) should be <= len (is {len})");
+        let compressed_len = self.compressed_len();
+        if idx >= compressed_len {
+            let offset = idx - compressed_len;
+            self.uncompressed_buffer.insert(offset, value);
+            if self.buffer_full_at(offset) {
+                self.compress_inserted(offset, Self::compress_buffer);
+            }
+            return;
+        }
+        let Some(Either::Left((chunk_idx, chunk_offset))) = self.split(idx) else { unreachable!() };
+        let chunk_elems = self.chunk_elems();
+        let values = self.reopen(chunk_idx);
+        values.insert(chunk_offset, value);
+        let split = (values.len() > chunk_elems).then(|| values.split_off(values.len() / 2));
+        for end in &mut self.chunk_ends[chunk_idx..] {
+            *end += 1;
+        }
+        if let Some(tail) = split {
+            let tail_len = tail.len();
+            self.compressed_storage.insert(chunk_idx + 1, Chunk::Raw(tail));
+            self.chunk_ends.insert(chunk_idx, self.chunk_ends[chunk_idx] - tail_len);
+            // Chunk indices after `chunk_idx` have shifted
+            self.cache.kill_all();
+            self.cache.resize(self.compressed_storage.len());
+            self.inserted_chunk(chunk_idx + 1);
+        }
+    }
+    /// Swaps two elements, decompressing at most the two chunks containing them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `a` or `b` are out of bounds.
+    pub fn swap(&mut self, a: usize, b: usize)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        let len = self.len();
+        let split = |idx| self.split(idx).unwrap_or_else(|| panic!("index out of bounds: the len is {len} but the index is {idx}"));
+        match (split(a), split(b)) {
+            (Either::Right(a), Either::Right(b)) => self.uncompressed_buffer.swap(a, b),
+            (Either::Left((chunk_a, a)), Either::Left((chunk_b, b))) if chunk_a == chunk_b => self.reopen(chunk_a).swap(a, b),
+            (Either::Left((chunk_a, a)), Either::Left((chunk_b, b))) => {
+                // Keep the chunk which is already open (if either is) open, and rewrite the other one
+                let ((chunk_a, a), (chunk_b, b)) = if self.open_chunk == Some(chunk_b) {
+                    ((chunk_b, b), (chunk_a, a))
+                } else {
+                    ((chunk_a, a), (chunk_b, b))
+                };
+                self.reopen(chunk_a);
+                // A raw chunk (in the raw window) is swapped into in place, any other is decoded and only written back,
+                // in its kind, once it was compressed again, so a panic leaves both chunks as they were
+                let mut values_b = match &mut self.compressed_storage[chunk_b] {
+                    Chunk::Raw(values) => std::mem::take(values),
+                    Chunk::Compressed(data) => decompress(data),
+                    Chunk::Spilled(spilled) => decompress(&spilled.load()),
+                };
+                std::mem::swap(&mut self.reopen(chunk_a)[a], &mut values_b[b]);
+                if let Chunk::Raw(values) = &mut self.compressed_storage[chunk_b] {
+                    *values = values_b;
+                    return;
+                }
+                let compressed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| compress(&values_b, COMPRESSION_LEVEL)));
+                let compressed = compressed.unwrap_or_else(|panic| {
+                    std::mem::swap(&mut self.reopen(chunk_a)[a], &mut values_b[b]);
+                    std::panic::resume_unwind(panic)
+                });
+                let bytes = compressed.len();
+                let spilled = matches!(self.compressed_storage[chunk_b], Chunk::Spilled(_));
+                self.compressed_storage[chunk_b] = Chunk::Compressed(compressed);
+                self.cache.kill(chunk_b);
+                if spilled {
+                    // Spilled again like a pushed chunk, if the budget is exceeded
+                    self.spill_pushed(bytes);
+                }
+            }
+            (Either::Left((chunk_idx, offset)), Either::Right(elem)) | (Either::Right(elem), Either::Left((chunk_idx, offset))) => {
+                self.reopen(chunk_idx);
+                let Chunk::Raw(values) = &mut self.compressed_storage[chunk_idx] else { unreachable!() };
+                std::mem::swap(&mut values[offset], &mut self.uncompressed_buffer[elem]);
+            }
+        }
+    }
+    /// Removes and returns the element at position `idx`, shifting all elements after it to the left.
+    ///
+    /// Only the chunk containing `idx` is decompressed, chunks are allowed to shrink below `CHUNK_ELEMS` elements
+    /// and are dropped once empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn remove(&mut self, idx: usize) -> T
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        let len = self.len();
+        match self.split(idx) {
+            Some(Either::Left((chunk_idx, chunk_offset))) => {
+                let values = self.reopen(chunk_idx);
+                let value = values.remove(chunk_offset);
+                let now_empty = values.is_empty();
+                for end in &mut self.chunk_ends[chunk_idx..] {
+                    *end -= 1;
+                }
+                if now_empty {
+                    self.remove_chunk(chunk_idx);
+                }
+                value
+            }
+            Some(Either::Right(elem)) => self.uncompressed_buffer.remove(elem),
+            None => panic!("removal index (is {idx}) should be < len (is {len})"),
         }
     }
     pub fn pop(&mut self) -> Option<T>
@@ -124,17 +852,206 @@ impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecIn
         T: for<'a> Deserialize<'a>,
     {
         if self.uncompressed_buffer.is_empty() {
-            if let Some(x) = self.compressed_storage.pop() {
-                self.uncompressed_buffer = decompress(&x);
-                if self.cache.is_cached(self.compressed_storage.len()) {
-                    self.cache.kill_all();
-                }
+            if let Some(values) = self.pop_chunk() {
+                self.uncompressed_buffer = values;
             }
         }
         self.uncompressed_buffer.pop()
     }
+    /// Removes and returns the last chunk's worth of elements, in order, or `None` if the collection is empty.
+    ///
+    /// If the uncompressed buffer isn't empty its (fewer than `CHUNK_ELEMS`) elements are returned, otherwise the
+    /// last compressed chunk is decompressed in one go.
+    pub fn pop_chunk(&mut self) -> Option<Vec<T>>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        if !self.uncompressed_buffer.is_empty() {
+            return Some(std::mem::take(&mut self.uncompressed_buffer));
+        }
+        // Decoded (into the allocation of the empty buffer) before it is removed, so that a chunk which fails to
+        // decode (and panics) is left in place
+        let mut values = std::mem::take(&mut self.uncompressed_buffer);
+        match self.compressed_storage.last()? {
+            Chunk::Compressed(data) => decompress_into(data, &mut values),
+            Chunk::Spilled(spilled) => decompress_into(&spilled.load(), &mut values),
+            Chunk::Raw(_) => {}
+        }
+        match self.pop_last_chunk()? {
+            Chunk::Raw(raw) => {
+                self.uncompressed_buffer = values;
+                Some(raw)
+            }
+            _ => Some(values),
+        }
+    }
+    /// Removes and returns the last `n` elements (or all of them if there are fewer), in order, so the last element
+    /// of the result is the one [`pop`](Self::pop) would have returned first.
+    ///
+    /// Whole chunks are decompressed in one go, only the lowest chunk reached is split, its remaining elements
+    /// becoming the uncompressed buffer.
+    pub fn pop_n(&mut self, n: usize) -> Vec<T>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let mut remaining = n.min(self.len());
+        let mut chunks = Vec::new();
+        while remaining > 0 {
+            let mut values = self.pop_chunk().unwrap();
+            if values.len() > remaining {
+                let popped = values.split_off(values.len() - remaining);
+                self.uncompressed_buffer = std::mem::replace(&mut values, popped);
+            }
+            remaining -= values.len();
+            chunks.push(values);
+        }
+        let mut popped = Vec::with_capacity(chunks.iter().map(Vec::len).sum());
+        for values in chunks.into_iter().rev() {
+            popped.extend(values);
+        }
+        popped
+    }
+    /// Shortens the collection to `len` elements, dropping the rest; has no effect if it is already shorter.
+    ///
+    /// Whole chunks past the new end are dropped without being decompressed, only the chunk containing the new end
+    /// is decompressed and its remaining elements become the uncompressed buffer. Nothing is compressed.
+    pub fn truncate(&mut self, len: usize)
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let compressed_len = self.compressed_len();
+        if len >= compressed_len {
+            self.uncompressed_buffer.truncate(len - compressed_len);
+            return;
+        }
+        let Some(Either::Left((chunk_idx, chunk_offset))) = self.split(len) else { unreachable!() };
+        let (chunks, kept) = (self.compressed_storage.len(), chunk_idx + usize::from(chunk_offset != 0));
+        self.uncompressed_buffer.clear();
+        self.compressed_storage.truncate(kept);
+        self.chunk_ends.truncate(kept);
+        for dropped in kept..chunks {
+            self.cache.kill(dropped);
+        }
+        self.cache.resize(kept);
+        if self.open_chunk.is_some_and(|open| open >= kept) {
+            self.open_chunk = None;
+            self.open_clean = None;
+        }
+        if chunk_offset != 0 {
+            let mut head = self.pop_chunk().unwrap();
+            head.truncate(chunk_offset);
+            self.uncompressed_buffer = head;
+        }
+    }
+    /// Records the current length so that elements pushed afterwards can be dropped again with
+    /// [`rollback`](Self::rollback).
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.len())
+    }
+    /// Drops every element pushed since `checkpoint` was taken, like [`truncate`](Self::truncate): the chunks
+    /// compressed since are dropped without being decompressed, only the chunk holding the uncompressed buffer as it
+    /// was at the checkpoint is decompressed.
+    ///
+    /// Elements at or below the checkpoint which were changed since are not restored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the collection has been popped below the checkpoint's length.
+    pub fn rollback(&mut self, checkpoint: Checkpoint)
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let len = self.len();
+        assert!(checkpoint.0 <= len, "checkpoint length (is {}) should be <= len (is {len})", checkpoint.0);
+        self.truncate(checkpoint.0);
+    }
+    /// Resizes the collection to `new_len` elements, truncating it or appending clones of `value`.
+    ///
+    /// Appended full chunks are all copies of a single compressed chunk of `value`s, which compresses to a handful
+    /// of bytes, so even large fills are cheap.
+    pub fn resize(&mut self, new_len: usize, value: T)
+    where
+        T: Clone + Serialize + for<'a> Deserialize<'a>,
+    {
+        let len = self.len();
+        if new_len <= len {
+            self.truncate(new_len);
+        } else {
+            self.fill(new_len - len, value);
+        }
+    }
+    /// Reserves capacity for at least `additional` more elements: room in the chunk list for the chunks they will
+    /// fill, and in the uncompressed buffer for as many of them as it will hold at once.
+    pub fn reserve(&mut self, additional: usize) {
+        let (chunks, buffered) = self.reserve_amounts(additional);
+        self.compressed_storage.reserve(chunks);
+        self.chunk_ends.reserve(chunks);
+        self.uncompressed_buffer.reserve(buffered);
+    }
+    /// The number of chunks and of buffered elements to reserve room for, for `additional` more elements.
+    fn reserve_amounts(&self, additional: usize) -> (usize, usize) {
+        let buffered = self.uncompressed_buffer.len();
+        let chunk_elems = self.chunk_elems();
+        (buffered.saturating_add(additional) / chunk_elems, additional.min(chunk_elems.saturating_sub(buffered)))
+    }
+    /// Removes all elements.
+    ///
+    /// The allocations of the uncompressed buffer and of the chunk list are kept, so refilling the collection does
+    /// not need to allocate them again.
+    pub fn clear(&mut self) {
+        self.compressed_storage.clear();
+        self.chunk_ends.clear();
+        self.uncompressed_buffer.clear();
+        self.open_chunk = None;
+        self.open_clean = None;
+        self.cache.kill_all();
+    }
+    /// Removes all elements like [`clear`](Self::clear), but frees their memory on a background thread (with the
+    /// `rayon` feature, on the rayon thread pool from there), returning straight away.
+    ///
+    /// Freeing hundreds of thousands of chunks one after the other can take seconds. The returned handle can be
+    /// joined to wait until all memory has been freed, or dropped to let it happen in the background.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let mut compressed_stack: CVec<u32> = (0..(16 * 1024)).collect();
+    /// let freeing = compressed_stack.fast_clear();
+    /// assert!(compressed_stack.is_empty());
+    /// compressed_stack.push(1);
+    /// freeing.join().unwrap();
+    /// ```
+    pub fn fast_clear(&mut self) -> JoinHandle<()> where T: Send + 'static {
+        let chunks = std::mem::take(&mut self.compressed_storage);
+        let buffer = std::mem::take(&mut self.uncompressed_buffer);
+        self.clear();
+        std::thread::spawn(move || {
+            drop(buffer);
+            #[cfg(feature = "rayon")]
+            {
+                use rayon::prelude::*;
+                chunks.into_par_iter().for_each(drop);
+            }
+            #[cfg(not(feature = "rayon"))]
+            drop(chunks);
+        })
+    }
+    /// Drops the collection on a background thread, see [`fast_clear`](Self::fast_clear).
+    pub fn drop_in_background(mut self) -> JoinHandle<()> where T: Send + 'static {
+        self.fast_clear()
+    }
+    /// The maximum number of elements in a chunk, `CHUNK_ELEMS` unless it is chosen at runtime (see [`CVecDyn`]).
+    pub fn chunk_elems(&self) -> usize {
+        if CHUNK_ELEMS == 0 {
+            self.chunk_elems
+        } else {
+            CHUNK_ELEMS
+        }
+    }
     pub fn len(&self) -> usize {
-        self.uncompressed_buffer.len() + self.compressed_storage.len() * CHUNK_ELEMS
+        self.uncompressed_buffer.len() + self.compressed_len()
     }
     pub fn is_empty(&self) -> bool {
         self.uncompressed_buffer.is_empty() && self.compressed_storage.is_empty()
@@ -142,41 +1059,157 @@ impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecIn
     #[must_use]
     pub fn get_uncached(&self, idx: usize) -> Value<T, &T> where T: for<'a> Deserialize<'a> {
         match self.split(idx)? {
-            Either::Left((chunk_idx, chunk_offset)) => {
-                let data: Vec<T> = decompress(&self.compressed_storage[chunk_idx]);
-                Compressed(data.into_iter().nth(chunk_offset).unwrap())
-            }
+            Either::Left((chunk_idx, chunk_offset)) => match &self.compressed_storage[chunk_idx] {
+                Chunk::Compressed(data) => Compressed(decompress_nth(data, chunk_offset)),
+                Chunk::Spilled(spilled) => Compressed(decompress_nth(&spilled.load(), chunk_offset)),
+                Chunk::Raw(values) => Uncompressed(&values[chunk_offset]),
+            },
             Either::Right(elem) =>
                 Uncompressed(&self.uncompressed_buffer[elem]),
         }
     }
+    /// Returns the first element, decompressing only the first chunk if there is one, see
+    /// [`get_uncached`](Self::get_uncached).
+    #[must_use]
+    pub fn first(&self) -> Value<T, &T> where T: for<'a> Deserialize<'a> {
+        self.get_uncached(0)
+    }
+    /// Returns the last element, which is read from the uncompressed buffer unless it is empty, see
+    /// [`get_uncached`](Self::get_uncached).
+    #[must_use]
+    pub fn last(&self) -> Value<T, &T> where T: for<'a> Deserialize<'a> {
+        self.get_uncached(self.len().checked_sub(1)?)
+    }
     #[must_use]
     pub fn get_ref(&mut self, idx: usize) -> Option<&T> where T: for<'a> Deserialize<'a>, C: CacheAccess<T> {
         match self.split(idx)? {
-            Either::Left((chunk_idx, chunk_offset)) => {
-                let data = &self.compressed_storage[chunk_idx];
-                Some(self.cache.get_compressed(chunk_idx, chunk_offset, data))
-            }
+            Either::Left((chunk_idx, chunk_offset)) => match &self.compressed_storage[chunk_idx] {
+                Chunk::Compressed(data) => {
+                    if let Some(Chunk::Compressed(next)) = self.compressed_storage.get(chunk_idx + 1).filter(|_| !self.cache.is_cached(chunk_idx)) {
+                        self.cache.prefetch(chunk_idx, next);
+                    }
+                    Some(self.cache.get_compressed(chunk_idx, chunk_offset, &ChunkBytes::new(data)))
+                }
+                Chunk::Raw(values) => Some(&values[chunk_offset]),
+                chunk => Some(self.cache.get_compressed(chunk_idx, chunk_offset, &chunk.cache_bytes().unwrap())),
+            },
             Either::Right(elem) =>
                 Some(&self.uncompressed_buffer[elem]),
         }
     }
 
+    /// Returns clones of the elements at each of `indices`, in the same order, or `None` if any is out of bounds.
+    ///
+    /// The indices are grouped by chunk, so each chunk holding at least one of them is decompressed exactly once
+    /// however many indices fall in it and in whichever order they are given.
     #[must_use]
-    pub fn get(&self, idx: usize) -> Option<T> where T: for<'a> Deserialize<'a> + Clone, C: RcCacheAccess<T, CHUNK_ELEMS> {
-        match self.split(idx)? {
-            Either::Left((chunk_idx, chunk_offset)) => {
-                let data = &self.compressed_storage[chunk_idx];
-                Some(self.cache.get_compressed(chunk_idx, chunk_offset, data).borrow().clone())
+    pub fn get_many(&self, indices: &[usize]) -> Option<Vec<T>> where T: Clone + for<'a> Deserialize<'a> {
+        let mut order: Vec<usize> = (0..indices.len()).collect();
+        order.sort_unstable_by_key(|&pos| indices[pos]);
+        let mut values: Vec<Option<T>> = vec![None; indices.len()];
+        let mut chunk: Option<(usize, CVecChunk<'_, T>)> = None;
+        for pos in order {
+            let value = match self.split(indices[pos])? {
+                Either::Left((chunk_idx, chunk_offset)) => {
+                    if !matches!(chunk, Some((loaded, _)) if loaded == chunk_idx) {
+                        chunk = Some((chunk_idx, self.chunk(chunk_idx).unwrap()));
+                    }
+                    chunk.as_ref().unwrap().1[chunk_offset].clone()
+                }
+                Either::Right(elem) => self.uncompressed_buffer[elem].clone(),
+            };
+            values[pos] = Some(value);
+        }
+        values.into_iter().collect()
+    }
+    /// The memory budget of the cache in bytes, see [`CVecBudgeted`].
+    pub fn cache_budget(&self) -> usize where C: CacheBudget {
+        self.cache.budget()
+    }
+    /// Sets the memory budget of the cache in bytes, evicting decompressed chunks straight away if they no longer
+    /// fit.
+    pub fn set_cache_budget(&mut self, bytes: usize) where C: CacheBudget {
+        self.cache.set_budget(bytes);
+    }
+    /// Decompresses the chunks holding the elements in `range` into the cache ahead of time, so that reading them
+    /// later does not stall on decompression.
+    ///
+    /// Only as many chunks stay cached as the cache holds: as many as fit in the budget for [`CVecBudgeted`] and
+    /// [`CVecPooled`] (so warm the most important chunks last), and only the last one for [`CVec`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or its start is greater than its end, like slice indexing.
+    pub fn warm<R: RangeBounds<usize>>(&mut self, range: R) where T: for<'a> Deserialize<'a>, C: CacheAccess<T> {
+        let range = self.resolve_range(range);
+        if range.is_empty() || range.start >= self.compressed_len() {
+            return;
+        }
+        let first = self.chunk_ends.partition_point(|&end| end <= range.start);
+        let last = self.chunk_ends.partition_point(|&end| end < range.end).min(self.compressed_storage.len() - 1);
+        for chunk_idx in first..=last {
+            if !self.cache.is_cached(chunk_idx) {
+                if let Some(data) = self.compressed_storage[chunk_idx].cache_bytes() {
+                    self.cache.get_compressed(chunk_idx, 0, &data);
+                }
             }
-            Either::Right(elem) =>
-                Some(self.uncompressed_buffer[elem].clone()),
         }
     }
-    // #[must_use]
-    // pub fn get_rc(&self, idx: usize) -> Option<Entry<'_, T, CHUNK_ELEMS>> where T: for<'a> Deserialize<'a>, C: RcCacheAccess<T, CHUNK_ELEMS> {
-    //     match self.split(idx)? {
-    //         Either::Left((chunk_idx, chunk_offset)) => {
+    /// Drops all decompressed chunks held by the cache, e.g. from a hook registered with [`on_release_caches`].
+    pub fn release_cache(&mut self) {
+        self.cache.kill_all();
+    }
+    /// Decompresses the chunk at `chunk_idx` into the cache and keeps it there until it is unpinned, regardless of
+    /// the cache budget. Useful for chunks which are read on every query, e.g. an index at the start.
+    ///
+    /// Pins are by chunk index, so they follow whatever chunk ends up at that index if chunks are removed or the
+    /// collection is sorted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_idx` is not less than the number of compressed chunks.
+    pub fn pin_chunk(&mut self, chunk_idx: usize) where T: for<'a> Deserialize<'a>, C: CachePin + CacheAccess<T> {
+        let chunks = self.compressed_storage.len();
+        assert!(chunk_idx < chunks, "chunk index out of bounds: there are {chunks} chunks but the index is {chunk_idx}");
+        self.cache.pin(chunk_idx);
+        if let Some(data) = self.compressed_storage[chunk_idx].cache_bytes() {
+            self.cache.get_compressed(chunk_idx, 0, &data);
+        }
+    }
+    /// Lets the chunk at `chunk_idx` be evicted from the cache again, see [`pin_chunk`](Self::pin_chunk).
+    pub fn unpin_chunk(&mut self, chunk_idx: usize) where C: CachePin {
+        self.cache.unpin(chunk_idx);
+    }
+    /// Returns a reference to the last element, which `pop` would return next.
+    ///
+    /// It is read from the uncompressed buffer unless that is empty, in which case the last chunk is decompressed in
+    /// place (like [`get_mut`](Self::get_mut) does), so neither repeated peeks nor the pops that follow decompress
+    /// it again. The chunk is not dirtied, so if nothing mutates it, it is not compressed again on
+    /// [`flush`](Self::flush). This needs no cache.
+    #[must_use]
+    pub fn peek(&mut self) -> Option<&T> where T: Serialize + for<'a> Deserialize<'a> {
+        let last = self.len().checked_sub(1)?;
+        match self.split(last)? {
+            Either::Left((chunk_idx, chunk_offset)) => Some(&self.view(chunk_idx)[chunk_offset]),
+            Either::Right(elem) => Some(&self.uncompressed_buffer[elem]),
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, idx: usize) -> Option<T> where T: for<'a> Deserialize<'a> + Clone, C: SharedCacheAccess<T> {
+        match self.split(idx)? {
+            Either::Left((chunk_idx, chunk_offset)) => match &self.compressed_storage[chunk_idx] {
+                Chunk::Raw(values) => Some(values[chunk_offset].clone()),
+                chunk => Some(self.cache.get_shared(chunk_idx, chunk_offset, &chunk.cache_bytes().unwrap())),
+            },
+            Either::Right(elem) =>
+                Some(self.uncompressed_buffer[elem].clone()),
+        }
+    }
+    // #[must_use]
+    // pub fn get_rc(&self, idx: usize) -> Option<Entry<'_, T, CHUNK_ELEMS>> where T: for<'a> Deserialize<'a>, C: RcCacheAccess<T, CHUNK_ELEMS> {
+    //     match self.split(idx)? {
+    //         Either::Left((chunk_idx, chunk_offset)) => {
     //             let data = &self.compressed_storage[chunk_idx];
     //             Some(self.cache.get_compressed(chunk_idx, chunk_offset, data))
     //         }
@@ -194,28 +1227,761 @@ impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecIn
     //         }
     //     }
     // }
+    /// Deserializes a collection that was persisted while its elements had a different layout, `Old`.
+    ///
+    /// `migrate` is called with the postcard encoding of each stored chunk (a `Vec<Old>`) in order, followed by the
+    /// uncompressed tail, which is encoded the same way, and returns the values that replace them. The result is
+    /// re-chunked as it is built, so the migration may change the number of elements.
+    pub fn deserialize_migrating<'de, Old, D, F>(deserializer: D, mut migrate: F) -> Result<Self, D::Error>
+    where
+        T: Serialize,
+        Old: Serialize + for<'a> Deserialize<'a>,
+        D: Deserializer<'de>,
+        F: FnMut(&[u8]) -> Vec<T>,
+    {
+        let raw = RawCVec::<Old>::deserialize(deserializer)?;
+        let mut migrated = Self::default();
+        for chunk in raw.compressed_storage.iter() {
+            for value in migrate(&decompress_bytes(chunk)) {
+                migrated.push(value);
+            }
+        }
+        let tail = postcard::to_stdvec(&raw.uncompressed_buffer).map_err(serde::de::Error::custom)?;
+        for value in migrate(&tail) {
+            migrated.push(value);
+        }
+        Ok(migrated)
+    }
+    /// Returns a mutable reference to the element at `idx`, or `None` if it is out of bounds.
+    ///
+    /// If the element is compressed its chunk is decompressed and kept that way until another chunk is mutated or
+    /// the collection is [flushed](Self::flush), so consecutive writes to the same chunk only decompress and
+    /// recompress it once.
+    #[must_use]
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> where T: Serialize + for<'a> Deserialize<'a> {
+        self.open(idx)
+    }
+    /// Replaces the element at `idx` with `value`, returning the old element.
+    ///
+    /// Only the chunk containing `idx` is decompressed, it stays that way like with [`get_mut`](Self::get_mut).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn set(&mut self, idx: usize, value: T) -> T where T: Serialize + for<'a> Deserialize<'a> {
+        let len = self.len();
+        let elem = self.open(idx).unwrap_or_else(|| panic!("index out of bounds: the len is {len} but the index is {idx}"));
+        std::mem::replace(elem, value)
+    }
+    /// Returns an iterator over clones of the elements, front to back.
+    ///
+    /// Used as a stack this is bottom to top, the reverse of the order [`pop`](Self::pop) returns them in. Use
+    /// `.rev()` to go top to bottom instead, which starts by decompressing the top chunk rather than the bottom one.
+    /// The collection is left untouched either way, `&CVec` also implements `IntoIterator` with this iterator.
+    pub fn iter(&self) -> CVecIter<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> where T: for<'a> Deserialize<'a> {
+        CVecIter::new(self, 0..self.len())
+    }
+    /// Returns an iterator over clones of the elements which decompresses the next chunk on a helper thread while
+    /// the current one is consumed, hiding decompression behind consumption.
+    ///
+    /// Worth it when the consumer does enough work per chunk to make up for a thread being spawned per chunk.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let compressed_stack: CVec<u32> = (0..(16 * 1024)).collect();
+    /// assert!((0..(16 * 1024)).eq(compressed_stack.iter_pipelined()));
+    /// ```
+    pub fn iter_pipelined(&self) -> CVecPipelinedIter<'_, T> where T: Send + 'static + for<'a> Deserialize<'a> {
+        CVecPipelinedIter::new(self)
+    }
+    /// Returns an iterator over clones of the elements in `range`.
+    ///
+    /// Iteration starts by decompressing the chunk containing the start of the range, and no chunks past its end
+    /// are decompressed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or its start is greater than its end, like slice indexing.
+    pub fn iter_range<R: RangeBounds<usize>>(&self, range: R) -> CVecIter<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> where T: for<'a> Deserialize<'a> {
+        CVecIter::new(self, self.resolve_range(range))
+    }
+    /// Returns an iterator over the chunks of the collection, front to back, each as a slice of at most
+    /// `CHUNK_ELEMS` elements.
+    ///
+    /// Compressed chunks are decompressed one at a time as the iterator advances, chunks which are already
+    /// uncompressed (including the buffer at the end) are borrowed.
+    pub fn chunks(&self) -> CVecChunks<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+        CVecChunks::new(self)
+    }
+    /// Returns `true` if the collection contains an element equal to `x`.
+    ///
+    /// Chunks are decompressed one at a time, stopping at the first one containing `x`, and elements are only
+    /// compared by reference so `T` need not be `Clone` (unlike with [`iter`](Self::iter)).
+    pub fn contains(&self, x: &T) -> bool where T: PartialEq + for<'a> Deserialize<'a> {
+        self.chunks().any(|chunk| chunk.contains(x))
+    }
+    /// Binary searches this (sorted) collection for `x`, see [`slice::binary_search`].
+    ///
+    /// The search first bisects over whole chunks, comparing against the first and last element of each, and then
+    /// searches within the one chunk that may hold `x`; only `O(log(len / CHUNK_ELEMS))` chunks are decompressed.
+    pub fn binary_search(&self, x: &T) -> Result<usize, usize> where T: Ord + for<'a> Deserialize<'a> {
+        self.binary_search_by(|probe| probe.cmp(x))
+    }
+    /// Binary searches this collection with a comparator function, see [`slice::binary_search_by`].
+    pub fn binary_search_by<F>(&self, mut f: F) -> Result<usize, usize>
+    where
+        T: for<'a> Deserialize<'a>,
+        F: FnMut(&T) -> Ordering,
+    {
+        let chunks = self.chunk_count();
+        let (mut lo, mut hi) = (0, chunks);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let chunk = self.chunk(mid).unwrap();
+            if f(chunk.last().unwrap()) == Ordering::Less {
+                lo = mid + 1;
+            } else if f(chunk.first().unwrap()) == Ordering::Greater {
+                hi = mid;
+            } else {
+                let start = self.chunk_start(mid);
+                return chunk.binary_search_by(f).map(|idx| start + idx).map_err(|idx| start + idx);
+            }
+        }
+        Err(if lo == chunks { self.len() } else { self.chunk_start(lo) })
+    }
+    /// Binary searches this collection with a key extraction function, see [`slice::binary_search_by_key`].
+    pub fn binary_search_by_key<B, F>(&self, b: &B, mut f: F) -> Result<usize, usize>
+    where
+        T: for<'a> Deserialize<'a>,
+        B: Ord,
+        F: FnMut(&T) -> B,
+    {
+        self.binary_search_by(|probe| f(probe).cmp(b))
+    }
+    /// Returns an iterator over the results of calling `f` on each chunk, front to back, see
+    /// [`chunks`](Self::chunks).
+    pub fn map_chunks<'s, R, F>(&'s self, mut f: F) -> impl Iterator<Item = R> + 's
+    where
+        T: for<'a> Deserialize<'a>,
+        F: FnMut(&[T]) -> R + 's,
+    {
+        self.chunks().map(move |chunk| f(&chunk))
+    }
+    /// Folds every chunk into an accumulator, front to back, decompressing one chunk at a time.
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let compressed_stack: CVec<u64, 100> = (0..1000).collect();
+    /// assert_eq!(compressed_stack.fold_chunks(0, |sum, chunk| sum + chunk.iter().sum::<u64>()), 499500);
+    /// ```
+    pub fn fold_chunks<B, F>(&self, init: B, mut f: F) -> B
+    where
+        T: for<'a> Deserialize<'a>,
+        F: FnMut(B, &[T]) -> B,
+    {
+        self.chunks().fold(init, |acc, chunk| f(acc, &chunk))
+    }
+    /// Returns an iterator over all contiguous windows of length `size`, like [`slice::windows`].
+    ///
+    /// Chunks are decompressed one at a time as the windows slide over them, at most two are held uncompressed at
+    /// once (as long as `size <= CHUNK_ELEMS`).
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let compressed_stack: CVec<u32, 100> = (0..1000).collect();
+    /// let mut windows = compressed_stack.windows(3);
+    /// while let Some(window) = windows.next() {
+    ///     assert_eq!(window[0] + 2, window[2]);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    pub fn windows(&self, size: usize) -> CVecWindows<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> where T: Clone + for<'a> Deserialize<'a> {
+        CVecWindows::new(self, size)
+    }
+    /// Returns an iterator that allows modifying each element, front to back.
+    ///
+    /// Chunks are decompressed one at a time with the same mechanism as [`get_mut`](Self::get_mut): each is
+    /// compressed again when the iteration moves on to the next one, and the last chunk visited stays decompressed
+    /// until another chunk is mutated or the collection is [flushed](Self::flush).
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let mut compressed_stack: CVec<u32, 100> = (0..1000).collect();
+    /// let mut iter = compressed_stack.iter_mut();
+    /// while let Some(x) = iter.next() {
+    ///     *x *= 2;
+    /// }
+    /// assert_eq!(compressed_stack.get_ref(999), Some(&1998));
+    /// ```
+    pub fn iter_mut(&mut self) -> CVecIterMut<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> where T: Serialize + for<'a> Deserialize<'a> {
+        CVecIterMut::new(self)
+    }
+    /// Compresses the chunk which was decompressed to be mutated in place, if any.
+    ///
+    /// This happens automatically when a different chunk is mutated, so it is only needed to minimise memory usage.
+    /// A chunk which was only read (e.g. by [`peek`](Self::peek)) gets its old bytes back instead of being compressed
+    /// again, and a chunk in the [raw window](Self::set_raw_window) is left as it is.
+    pub fn flush(&mut self) where T: Serialize {
+        if let Some(chunk_idx) = self.open_chunk.take() {
+            let in_raw_window = self.in_raw_window(chunk_idx);
+            let chunk = &mut self.compressed_storage[chunk_idx];
+            match self.open_clean.take() {
+                Some(data) => *chunk = Chunk::Compressed(data),
+                None if in_raw_window => {}
+                None => chunk.compress(COMPRESSION_LEVEL),
+            }
+        }
+    }
+    /// The number of most recently filled chunks which are kept uncompressed, 0 by default.
+    pub fn raw_window(&self) -> usize {
+        self.raw_window
+    }
+    /// Keeps the `chunks` most recently filled chunks uncompressed, a chunk is only compressed once `chunks` more
+    /// have been filled after it.
+    ///
+    /// This spreads out the cost of compression instead of stalling every push which fills a chunk, and makes
+    /// popping right after pushing across a chunk boundary cheap, at the cost of holding up to `chunks` chunks
+    /// uncompressed. Shrinking the window compresses the chunks which leave it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let mut compressed_stack = CVec::new::<1024, 0>();
+    /// compressed_stack.set_raw_window(2);
+    /// for i in 0..(4 * 1024) {
+    ///     compressed_stack.push(i);
+    /// }
+    /// // Popping back into the last chunks needs no decompression
+    /// for i in (0..(4 * 1024)).rev().take(2 * 1024) {
+    ///     assert_eq!(compressed_stack.pop(), Some(i));
+    /// }
+    /// ```
+    pub fn set_raw_window(&mut self, chunks: usize) where T: Serialize {
+        self.raw_window = chunks;
+        self.compress_window();
+    }
+    /// Turns the collection into an immutable [`FrozenCVec`] which is cheap to clone and can be read from many threads
+    /// at once. The uncompressed buffer is compressed as a final, shorter chunk.
+    pub fn freeze(mut self) -> FrozenCVec<T> where T: Serialize {
+        self.flush();
+        self.compress_raw();
+        if !self.uncompressed_buffer.is_empty() {
+            self.compress_buffer();
+            self.compress_raw();
+        }
+        let chunks = std::mem::take(&mut self.compressed_storage).into_iter().map(|chunk| match chunk {
+            Chunk::Compressed(data) => data,
+            Chunk::Spilled(spilled) => spilled.load(),
+            Chunk::Raw(_) => unreachable!("all chunks are compressed after a flush"),
+        }).collect();
+        FrozenCVec::new(chunks, std::mem::take(&mut self.chunk_ends))
+    }
+    /// Decompresses the whole collection into a `Vec`, chunks are freed as they are decompressed.
+    pub fn into_vec(self) -> Vec<T> where T: for<'a> Deserialize<'a> {
+        self.into_iter().collect()
+    }
+    /// Compresses everything, including a partially filled uncompressed buffer (as a short chunk), and shrinks the
+    /// capacity of the internal buffers as much as possible.
+    ///
+    /// Useful once the collection is done growing. It can still be pushed to afterwards, the elements pushed simply
+    /// start a new chunk.
+    #[doc(alias = "compact")]
+    pub fn shrink_to_fit(&mut self) where T: Serialize {
+        self.flush();
+        self.compress_raw();
+        if !self.uncompressed_buffer.is_empty() {
+            self.compress_buffer();
+        }
+        self.uncompressed_buffer.shrink_to_fit();
+        self.compressed_storage.shrink_to_fit();
+        self.chunk_ends.shrink_to_fit();
+    }
     pub fn uncompressed(&self) -> &Vec<T> {
         &self.uncompressed_buffer
     }
-    pub fn compressed(&self) -> &Vec<Box<[u8]>> {
-        &self.compressed_storage
+    /// Returns the compressed bytes of each chunk, not including the uncompressed buffer.
+    ///
+    /// Chunks are no longer all held compressed in memory, so their bytes are copied into a new `Vec`. Use
+    /// [`compressed_chunks`](Self::compressed_chunks), which yields them one at a time, instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a spilled chunk can no longer be read.
+    #[deprecated(note = "copies every chunk, use `compressed_chunks` instead")]
+    pub fn compressed(&self) -> Vec<Box<[u8]>> where T: Serialize {
+        self.compressed_chunks().map(|chunk| chunk.into_owned().into_boxed_slice()).collect()
+    }
+    /// Returns an iterator over the compressed bytes of each chunk, not including the uncompressed buffer.
+    ///
+    /// The collection is left as it is: chunks held uncompressed (the open chunk, or those in the
+    /// [raw window](Self::set_raw_window)) are compressed on the fly and spilled chunks are read back one at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a spilled chunk can no longer be read, see [`try_compressed_chunks`](Self::try_compressed_chunks).
+    pub fn compressed_chunks(&self) -> impl Iterator<Item = Cow<'_, [u8]>> where T: Serialize {
+        self.compressed_storage.iter().map(|chunk| chunk.compressed_bytes(COMPRESSION_LEVEL))
+    }
+    /// Like [`compressed_chunks`](Self::compressed_chunks), but a spilled chunk which can no longer be read (or does
+    /// not match its checksum) is an error rather than a panic.
+    pub fn try_compressed_chunks(&self) -> impl Iterator<Item = io::Result<Cow<'_, [u8]>>> where T: Serialize {
+        self.compressed_storage.iter().map(|chunk| chunk.try_compressed_bytes(COMPRESSION_LEVEL))
+    }
+
+    /// External merge sort: sorts each chunk into a run and then merges runs `SORT_MERGE_WAYS` at a time.
+    ///
+    /// The runs are built from copies of the chunks, so the collection is left as it was if `compare` panics. They
+    /// spill like the collection does, see [`temporary_spill`](Self::temporary_spill).
+    fn merge_sort_by<F>(&mut self, mut compare: F, stable: bool)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.flush();
+        let spill = self.temporary_spill();
+        let buffer = (!self.uncompressed_buffer.is_empty()).then(|| copy_values(&self.uncompressed_buffer));
+        let mut runs: Vec<CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, Uncached>> = self.compressed_storage.iter()
+            .map(Chunk::to_values)
+            .chain(buffer)
+            .map(|mut values| {
+                if stable {
+                    values.sort_by(&mut compare);
+                } else {
+                    values.sort_unstable_by(&mut compare);
+                }
+                let mut run = self.empty_like();
+                run.spill = spill.clone();
+                let compressed = compress(&values, COMPRESSION_LEVEL);
+                let bytes = compressed.len();
+                run.push_chunk(Chunk::Compressed(compressed), values.len());
+                run.spill_pushed(bytes);
+                run
+            })
+            .collect();
+        while runs.len() > 1 {
+            // Each pass spills to a file of its own, which is deleted once the pass after it has read the runs
+            let spill = self.temporary_spill();
+            let mut runs_iter = runs.into_iter().peekable();
+            runs = Vec::new();
+            while runs_iter.peek().is_some() {
+                let group = runs_iter.by_ref().take(SORT_MERGE_WAYS).map(IntoIterator::into_iter).collect();
+                runs.push(self.merge_runs(group, &mut compare, &spill));
+            }
+        }
+        if let Some(sorted) = runs.pop() {
+            self.cache.kill_all();
+            self.compressed_storage = sorted.compressed_storage;
+            self.chunk_ends = sorted.chunk_ends;
+            self.uncompressed_buffer = sorted.uncompressed_buffer;
+            self.cache.resize(self.compressed_storage.len());
+        }
+    }
+    /// Merges sorted runs into a single one, taking from the earliest run on ties so that the merge is stable.
+    fn merge_runs<I, F>(&self, mut runs: Vec<I>, compare: &mut F, spill: &Option<Spill>) -> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, Uncached>
+    where
+        T: Serialize,
+        I: Iterator<Item = T>,
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let mut merged = self.empty_like();
+        merged.spill = spill.clone();
+        let mut heads: Vec<Option<T>> = runs.iter_mut().map(Iterator::next).collect();
+        while let Some(min) = (0..heads.len()).filter(|&i| heads[i].is_some()).reduce(|min, i| {
+            match compare(heads[i].as_ref().unwrap(), heads[min].as_ref().unwrap()) {
+                Ordering::Less => i,
+                _ => min,
+            }
+        }) {
+            let next = runs[min].next();
+            merged.push(std::mem::replace(&mut heads[min], next).unwrap());
+        }
+        merged
+    }
+    /// Returns a mutable reference to the element at `idx`, decompressing its chunk with [`reopen`](Self::reopen).
+    fn open(&mut self, idx: usize) -> Option<&mut T> where T: Serialize + for<'a> Deserialize<'a> {
+        match self.split(idx)? {
+            Either::Left((chunk_idx, chunk_offset)) =>
+                Some(&mut self.reopen(chunk_idx)[chunk_offset]),
+            Either::Right(elem) =>
+                Some(&mut self.uncompressed_buffer[elem]),
+        }
+    }
+    /// Decompresses a chunk into a [`Chunk::Raw`] which is compressed again once another chunk is opened.
+    fn reopen(&mut self, chunk_idx: usize) -> &mut Vec<T> where T: Serialize + for<'a> Deserialize<'a> {
+        self.view(chunk_idx);
+        // The caller may mutate it, so its old bytes are stale
+        self.open_clean = None;
+        match &mut self.compressed_storage[chunk_idx] {
+            Chunk::Raw(values) => values,
+            Chunk::Compressed(_) | Chunk::Spilled(_) => unreachable!(),
+        }
+    }
+    /// Like [`reopen`](Self::reopen), but for reading only: the compressed bytes are kept so that the chunk need not
+    /// be compressed again unless it is mutated in the meantime.
+    fn view(&mut self, chunk_idx: usize) -> &Vec<T> where T: Serialize + for<'a> Deserialize<'a> {
+        if self.open_chunk != Some(chunk_idx) {
+            self.flush();
+            self.open_chunk = Some(chunk_idx);
+        }
+        let chunk = &mut self.compressed_storage[chunk_idx];
+        if let Some(data) = chunk.decompress() {
+            self.open_clean = Some(data);
+            self.cache.kill(chunk_idx);
+        }
+        match chunk {
+            Chunk::Raw(values) => values,
+            Chunk::Compressed(_) | Chunk::Spilled(_) => unreachable!(),
+        }
+    }
+    /// An empty collection with the same chunk size as this one.
+    fn empty_like<C2: Cache>(&self) -> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C2> {
+        CVecInner { chunk_elems: self.chunk_elems, chunk_bytes: self.chunk_bytes, ..CVecInner::default() }
+    }
+    /// Removes the chunk at `chunk_idx` along with its elements.
+    fn remove_chunk(&mut self, chunk_idx: usize) -> Chunk<T> {
+        let chunk = self.compressed_storage.remove(chunk_idx);
+        let chunk_len = self.chunk_ends.remove(chunk_idx) - self.chunk_start(chunk_idx);
+        for end in &mut self.chunk_ends[chunk_idx..] {
+            *end -= chunk_len;
+        }
+        if self.open_chunk == Some(chunk_idx) {
+            self.open_clean = None;
+        }
+        self.open_chunk = match self.open_chunk {
+            Some(open) if open == chunk_idx => None,
+            Some(open) if open > chunk_idx => Some(open - 1),
+            open => open,
+        };
+        // Chunk indices after `chunk_idx` have shifted
+        self.cache.kill_all();
+        chunk
+    }
+    /// Removes the last chunk, leaving the uncompressed buffer as it is.
+    fn pop_last_chunk(&mut self) -> Option<Chunk<T>> {
+        let chunk = self.compressed_storage.pop()?;
+        self.chunk_ends.pop();
+        let chunks = self.compressed_storage.len();
+        self.cache.kill(chunks);
+        self.cache.resize(chunks);
+        if self.open_chunk == Some(chunks) {
+            self.open_chunk = None;
+            self.open_clean = None;
+        }
+        Some(chunk)
+    }
+    /// Appends `additional` clones of `value`, compressing a single full chunk of them whose bytes are copied for
+    /// every full chunk.
+    fn fill(&mut self, mut additional: usize, value: T) where T: Clone + Serialize {
+        if self.chunk_bytes.is_some() {
+            (0..additional).for_each(|_| self.push(value.clone()));
+            return;
+        }
+        self.reserve(additional);
+        let chunk_elems = self.chunk_elems();
+        let top_up = chunk_elems.saturating_sub(self.uncompressed_buffer.len()).min(additional);
+        if !self.uncompressed_buffer.is_empty() && top_up > 0 {
+            self.uncompressed_buffer.resize(self.uncompressed_buffer.len() + top_up, value.clone());
+            additional -= top_up;
+            if self.uncompressed_buffer.len() >= chunk_elems {
+                self.compress_buffer();
+            }
+        }
+        let chunks = additional / chunk_elems;
+        if chunks > 0 {
+            let chunk = compress(&vec![value.clone(); chunk_elems], COMPRESSION_LEVEL);
+            for _ in 0..chunks {
+                self.push_chunk(Chunk::Compressed(chunk.clone()), chunk_elems);
+            }
+        }
+        self.uncompressed_buffer.resize(self.uncompressed_buffer.len() + additional % chunk_elems, value);
+    }
+    /// Whether the element just pushed filled the uncompressed buffer: it holds a chunk's worth of elements or, if
+    /// the chunk size is in bytes, of serialized bytes.
+    ///
+    /// The size of the buffer is counted from the size of each pushed element, and counted again from scratch if
+    /// its length changed otherwise (e.g. it was popped from). Elements replaced in place count as the size they
+    /// were pushed with.
+    fn buffer_full(&mut self) -> bool where T: Serialize {
+        self.buffer_full_at(self.uncompressed_buffer.len() - 1)
+    }
+    /// Like [`buffer_full`](Self::buffer_full) for the element just inserted into the buffer at `added`.
+    fn buffer_full_at(&mut self, added: usize) -> bool where T: Serialize {
+        let len = self.uncompressed_buffer.len();
+        if len >= self.chunk_elems() {
+            return true;
+        }
+        let Some(chunk_bytes) = self.chunk_bytes else { return false };
+        let (counted, bytes) = self.buffer_bytes;
+        let others = || self.uncompressed_buffer[..added].iter().chain(&self.uncompressed_buffer[added + 1..]);
+        let bytes = if counted + 1 == len { bytes } else { others().map(serialized_size).sum() };
+        let bytes = bytes + serialized_size(&self.uncompressed_buffer[added]);
+        self.buffer_bytes = (len, bytes);
+        bytes >= chunk_bytes
+    }
+    /// Runs `compress` once the element just pushed filled the uncompressed buffer. If it panics (e.g. in
+    /// `T::serialize`, or writing a spill file) the push is undone before the panic is resumed, so the collection
+    /// is left as it was.
+    fn compress_pushed<R>(&mut self, compress: impl FnOnce(&mut Self) -> R) -> R {
+        self.compress_inserted(self.uncompressed_buffer.len() - 1, compress)
+    }
+    /// Like [`compress_pushed`](Self::compress_pushed) for the element just inserted into the buffer at `added`.
+    fn compress_inserted<R>(&mut self, added: usize, compress: impl FnOnce(&mut Self) -> R) -> R {
+        let chunks = self.compressed_storage.len();
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| compress(self))) {
+            Ok(result) => result,
+            Err(panic) => {
+                // The buffer is only emptied once its chunk was pushed, unless it was moved into a raw chunk
+                if self.compressed_storage.len() > chunks {
+                    if let Some(Chunk::Raw(values)) = self.pop_last_chunk() {
+                        if self.uncompressed_buffer.is_empty() {
+                            self.uncompressed_buffer = values;
+                        }
+                    }
+                }
+                self.uncompressed_buffer.remove(added);
+                std::panic::resume_unwind(panic)
+            }
+        }
+    }
+    /// Moves the whole uncompressed buffer into a new compressed chunk.
+    fn compress_buffer(&mut self) where T: Serialize {
+        if self.raw_window > 0 {
+            let len = self.uncompressed_buffer.len();
+            let buffer = Vec::with_capacity(self.chunk_elems());
+            let values = std::mem::replace(&mut self.uncompressed_buffer, buffer);
+            self.push_chunk(Chunk::Raw(values), len);
+            self.compress_window();
+            return;
+        }
+        let compressed = compress(&self.uncompressed_buffer, COMPRESSION_LEVEL);
+        self.push_chunk(Chunk::Compressed(compressed), self.uncompressed_buffer.len());
+        self.uncompressed_buffer.clear();
+    }
+    /// Compresses the [`Chunk::Raw`] chunks which have left the raw window, other than the open chunk.
+    fn compress_window(&mut self) where T: Serialize {
+        let window_start = self.compressed_storage.len().saturating_sub(self.raw_window);
+        let mut compressed = 0;
+        // Below the window only the open chunk is left raw, so the first compressed chunk ends the search
+        for chunk_idx in (0..window_start).rev() {
+            match &mut self.compressed_storage[chunk_idx] {
+                Chunk::Compressed(_) | Chunk::Spilled(_) => break,
+                _ if self.open_chunk == Some(chunk_idx) => {}
+                chunk => {
+                    chunk.compress(COMPRESSION_LEVEL);
+                    compressed += chunk.compressed_bytes(COMPRESSION_LEVEL).len();
+                }
+            }
+        }
+        self.spill_pushed(compressed);
+    }
+    /// Compresses the chunks in the raw window, which refills as more chunks are filled.
+    fn compress_raw(&mut self) where T: Serialize {
+        let window_start = self.compressed_storage.len().saturating_sub(self.raw_window);
+        for chunk in &mut self.compressed_storage[window_start..] {
+            chunk.compress(COMPRESSION_LEVEL);
+        }
+    }
+    /// Whether the chunk at `chunk_idx` is one of the last `raw_window` chunks.
+    fn in_raw_window(&self, chunk_idx: usize) -> bool {
+        chunk_idx + self.raw_window >= self.compressed_storage.len()
+    }
+    /// Adds a chunk of `len` elements after the existing chunks, the uncompressed buffer must be empty.
+    fn push_chunk(&mut self, chunk: Chunk<T>, len: usize) where T: Serialize {
+        self.log_chunk(&chunk, len);
+        let bytes = match &chunk {
+            Chunk::Compressed(data) => data.len(),
+            Chunk::Raw(_) | Chunk::Spilled(_) => 0,
+        };
+        let memory = budget::chunk_memory_bytes(&chunk);
+        self.compressed_storage.push(chunk);
+        self.chunk_ends.push(self.compressed_len() + len);
+        self.cache.resize(self.compressed_storage.len());
+        self.spill_pushed(bytes);
+        self.budget_pushed(memory);
     }
 
+    /// Like the end of [`push_chunk`](Self::push_chunk) for the raw chunk inserted at `chunk_idx`, which is
+    /// compressed unless it is in the raw window. It is not logged: its elements were logged with the chunk it was
+    /// split from.
+    fn inserted_chunk(&mut self, chunk_idx: usize) where T: Serialize {
+        if !self.in_raw_window(chunk_idx) {
+            self.compressed_storage[chunk_idx].compress(COMPRESSION_LEVEL);
+        }
+        let chunk = &self.compressed_storage[chunk_idx];
+        let bytes = match chunk {
+            Chunk::Compressed(data) => data.len(),
+            Chunk::Raw(_) | Chunk::Spilled(_) => 0,
+        };
+        let memory = budget::chunk_memory_bytes(chunk);
+        // The window has moved up by a chunk
+        self.compress_window();
+        self.spill_pushed(bytes);
+        self.budget_pushed(memory);
+    }
+    /// The chunk at `chunk_idx`, where the uncompressed buffer (if not empty) counts as the last chunk.
+    pub(crate) fn chunk(&self, chunk_idx: usize) -> Option<CVecChunk<'_, T>> where T: for<'a> Deserialize<'a> {
+        match self.compressed_storage.get(chunk_idx) {
+            Some(Chunk::Compressed(data)) => Some(CVecChunk::Decompressed(decompress(data))),
+            Some(Chunk::Raw(values)) => Some(CVecChunk::Borrowed(values)),
+            Some(Chunk::Spilled(spilled)) => Some(CVecChunk::Decompressed(decompress(&spilled.load()))),
+            None if chunk_idx == self.compressed_storage.len() && !self.uncompressed_buffer.is_empty() =>
+                Some(CVecChunk::Borrowed(&self.uncompressed_buffer)),
+            None => None,
+        }
+    }
+    /// Number of chunks returned by [`chunk`](Self::chunk)
+    pub(crate) fn chunk_count(&self) -> usize {
+        self.compressed_storage.len() + usize::from(!self.uncompressed_buffer.is_empty())
+    }
+    /// Number of elements in compressed chunks
+    pub(crate) fn compressed_len(&self) -> usize {
+        self.chunk_ends.last().copied().unwrap_or(0)
+    }
+    /// Index of the first element of chunk `chunk_idx`
+    pub(crate) fn chunk_start(&self, chunk_idx: usize) -> usize {
+        chunk_idx.checked_sub(1).map_or(0, |prev| self.chunk_ends[prev])
+    }
+    /// The indices in `range`, panicking like slice indexing if it is out of bounds.
+    fn resolve_range<R: RangeBounds<usize>>(&self, range: R) -> std::ops::Range<usize> {
+        let len = self.len();
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.checked_add(1).expect("attempted to index slice from after maximum usize"),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.checked_add(1).expect("attempted to index slice up to maximum usize"),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => len,
+        };
+        assert!(start <= end, "slice index starts at {start} but ends at {end}");
+        assert!(end <= len, "range end index {end} out of range for slice of length {len}");
+        start..end
+    }
     fn split(&self, idx: usize) -> Value<(usize, usize), usize> {
-        let (idx, offset) = (idx / CHUNK_ELEMS, idx % CHUNK_ELEMS);
-        if idx < self.compressed_storage.len() {
-            Compressed((idx, offset))
-        } else if idx == self.compressed_storage.len() && offset < self.uncompressed_buffer.len() {
-            Uncompressed(offset)
+        let compressed_len = self.compressed_len();
+        if idx < compressed_len {
+            let chunk_idx = self.chunk_ends.partition_point(|&end| end <= idx);
+            Compressed((chunk_idx, idx - self.chunk_start(chunk_idx)))
+        } else if idx - compressed_len < self.uncompressed_buffer.len() {
+            Uncompressed(idx - compressed_len)
         } else {
             None
         }
     }
 }
 
+impl<T, C: Cache, C2: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, const INNER_CHUNK_ELEMS: usize, const INNER_COMPRESSION_LEVEL: i32>
+    CVecInner<CVecInner<T, INNER_CHUNK_ELEMS, INNER_COMPRESSION_LEVEL, C2>, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    /// Returns an iterator over the elements of all the inner collections, front to back.
+    ///
+    /// Each chunk of the outer collection is decompressed in turn (giving up to `CHUNK_ELEMS` still compressed
+    /// inner collections), and only one chunk of an inner collection is held uncompressed at a time.
+    pub fn flattened(self) -> impl Iterator<Item = T> {
+        self.into_iter().flatten()
+    }
+    /// Moves the elements of all the inner collections to the end of `target`, see [`append`](CVecInner::append).
+    ///
+    /// Chunks of the inner collections are moved over without being recompressed when they fit within `target`'s
+    /// chunk size, only their uncompressed buffers are re-chunked.
+    pub fn flatten_into<C3: Cache, const OTHER_CHUNK_ELEMS: usize, const OTHER_COMPRESSION_LEVEL: i32>(
+        self,
+        target: &mut CVecInner<T, OTHER_CHUNK_ELEMS, OTHER_COMPRESSION_LEVEL, C3>,
+    )
+    where
+        T: Serialize,
+    {
+        for mut inner in self {
+            target.append(&mut inner);
+        }
+    }
+}
+
+/// Only implemented for [`CVecMemo`]: `index` takes `&self` and returns a reference which lives as long as that
+/// borrow, so the decompressed chunk must stay put until the collection is next mutated. The other caches hold a
+/// fixed number of chunks and evict one to read the next, which would free elements still borrowed by an earlier
+/// `v[i]`, while a cache which never evicts through `&self` grows with every chunk read, as `Memoized` does. They
+/// read through [`get`](Self::get), [`get_ref`](Self::get_ref) or [`get_mut`](Self::get_mut) instead.
+impl<T, C: Cache + MemoCacheAccess<T>, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Index<usize> for CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    type Output = T;
+
+    fn index(&self, idx: usize) -> &T {
+        match self.split(idx) {
+            Some(Either::Left((chunk_idx, chunk_offset))) => match &self.compressed_storage[chunk_idx] {
+                Chunk::Raw(values) => &values[chunk_offset],
+                chunk => self.cache.get_memoized(self.compressed_storage.len(), chunk_idx, chunk_offset, &chunk.cache_bytes().unwrap()),
+            },
+            Some(Either::Right(elem)) => &self.uncompressed_buffer[elem],
+            None => panic!("index out of bounds: the len is {} but the index is {idx}", self.len()),
+        }
+    }
+}
+
+impl<T, C: Cache + MemoCacheAccess<T>, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> IndexMut<usize> for CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Serialize + for<'a> Deserialize<'a>,
+{
+    fn index_mut(&mut self, idx: usize) -> &mut T {
+        let len = self.len();
+        self.get_mut(idx).unwrap_or_else(|| panic!("index out of bounds: the len is {len} but the index is {idx}"))
+    }
+}
+
+/// Compresses the vector chunk by chunk starting from the back, truncating it as it goes so that the memory of
+/// the source is released (each time half of it is unused) while the compressed collection is built.
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> From<Vec<T>> for CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Serialize,
+{
+    fn from(mut values: Vec<T>) -> Self {
+        let chunk_elems = default_chunk_elems::<T, CHUNK_ELEMS>();
+        let uncompressed_buffer = values.split_off(values.len() - values.len() % chunk_elems);
+        let chunks = values.len() / chunk_elems;
+        let mut compressed_storage = Vec::with_capacity(chunks);
+        while !values.is_empty() {
+            let start = values.len() - chunk_elems;
+            compressed_storage.push(Chunk::Compressed(compress(&values[start..], COMPRESSION_LEVEL)));
+            values.truncate(start);
+            // Shrinking every time could copy what is left for each chunk, halving keeps the copies linear
+            if values.capacity() > 2 * values.len() {
+                values.shrink_to_fit();
+            }
+        }
+        compressed_storage.reverse();
+        let chunk_ends = (1..=chunks).map(|chunk| chunk * chunk_elems).collect();
+        let mut cvec = Self { compressed_storage, chunk_ends, uncompressed_buffer, ..Self::default() };
+        cvec.cache.resize(chunks);
+        cvec
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{*, compression::{compress, decompress}};
+    use std::borrow::Cow;
+
+    use either::Either;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{*, compression::{compress, compress_bytes, decompress, decompress_bytes, decompress_into, STORE_THRESHOLD}};
+
+    use super::{Chunk, cache::Cache};
 
     #[test]
     fn simple_test() {
@@ -252,6 +2018,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::same_item_push)]
     fn iter_test() {
         let mut big_vec = Vec::new();
         let mut compressed_stack = CVec::new::<{ 1024 * 9 }, 0>();
@@ -274,6 +2041,1950 @@ mod tests {
         }
     }
 
+    #[test]
+    fn into_iter_order_test() {
+        let big_vec: Vec<u32> = (0..1050).collect();
+        let compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        assert!(big_vec.clone().into_iter().eq(compressed_stack.clone()));
+        assert!(big_vec.clone().into_iter().rev().eq(compressed_stack.clone().into_iter().rev()));
+        let mut big_iter = big_vec.into_iter();
+        let mut compressed_iter = compressed_stack.into_iter();
+        for i in 0..1060 {
+            if i % 4 == 0 {
+                assert_eq!(big_iter.next_back(), compressed_iter.next_back());
+            } else {
+                assert_eq!(big_iter.next(), compressed_iter.next());
+            }
+            assert_eq!(big_iter.len(), compressed_iter.len());
+        }
+    }
+
+    #[test]
+    fn migrate_test() {
+        #[derive(Serialize, Deserialize)]
+        struct Old(u32);
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct New(u64, bool);
+
+        let old: CVec<Old, 100> = (0..250).map(Old).collect();
+        let data = compress(&old, 0);
+        let bytes = crate::compression::decompress_bytes(&data);
+        let mut deserializer = postcard::Deserializer::from_bytes(&bytes);
+        let mut new: CVec<New, 100> = CVec::deserialize_migrating::<Old, _, _>(&mut deserializer, |bytes| {
+            let old: Vec<Old> = postcard::from_bytes(bytes).unwrap();
+            old.into_iter().map(|Old(x)| New(x as u64, x % 2 == 0)).collect()
+        }).unwrap();
+        assert_eq!(new.len(), 250);
+        for i in 0..250 {
+            assert_eq!(new.get_ref(i), Some(&New(i as u64, i % 2 == 0)));
+        }
+    }
+
+    #[test]
+    fn baseline_layout_test() {
+        // The layout CVec was serialized with before chunks could vary in length: the compressed chunks followed by
+        // the buffer inline
+        #[derive(Serialize)]
+        struct Baseline {
+            compressed_storage: Vec<Box<[u8]>>,
+            uncompressed_buffer: Vec<u64>,
+        }
+        let baseline = Baseline {
+            compressed_storage: (0..5).map(|chunk| compress(&(chunk * 100..(chunk + 1) * 100).collect::<Vec<u64>>(), 0)).collect(),
+            uncompressed_buffer: (500..550).collect(),
+        };
+        let bytes = postcard::to_stdvec(&baseline).unwrap();
+        let compressed_stack: CVec<u64, 100> = postcard::from_bytes(&bytes).unwrap();
+        assert!((0..550).eq(&compressed_stack));
+        assert_eq!(postcard::to_stdvec(&compressed_stack).unwrap(), bytes);
+        let mut deserializer = postcard::Deserializer::from_bytes(&bytes);
+        let migrated: CVec<u32, 100> = CVec::deserialize_migrating::<u64, _, _>(&mut deserializer, |bytes| {
+            postcard::from_bytes::<Vec<u64>>(bytes).unwrap().into_iter().map(|x| x as u32).collect()
+        }).unwrap();
+        assert!((0..550).eq(&migrated));
+    }
+
+    #[test]
+    fn stream_test() {
+        let compressed_stack: CVec<u64, 100> = (0..1050).collect();
+        let mut bytes = Vec::new();
+        compressed_stack.write_to(&mut bytes).unwrap();
+        let read_back: CVec<u64, 100> = CVec::read_from(bytes.as_slice()).unwrap();
+        assert_eq!(compressed_stack, read_back);
+        assert!(CVec::<u64, 100>::read_from(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn chunk_stream_test() {
+        let mut writer = CVecWriter::<u64, _, 100>::new(Vec::new());
+        for i in 0..1050 {
+            writer.push(i).unwrap();
+        }
+        // Only whole chunks are written before finishing
+        let written = writer.get_ref().len();
+        let bytes = writer.finish().unwrap();
+        assert!(written < bytes.len());
+        assert!((0..1050).eq(CVecReader::<u64, _>::new(bytes.as_slice()).map(Result::unwrap)));
+        let empty = CVecWriter::<u64, _, 100>::new(Vec::new()).finish().unwrap();
+        assert_eq!(CVecReader::<u64, _>::new(empty.as_slice()).count(), 0);
+        let mut truncated = CVecReader::<u64, _>::new(&bytes[..written]);
+        assert!(truncated.by_ref().take(1000).all(|x| x.is_ok()));
+        assert!(truncated.next().unwrap().is_err());
+        assert!(truncated.next().is_none());
+        // A chunk which cannot be decoded is an error rather than a panic
+        let mut corrupt = bytes.clone();
+        let chunk_len = u64::from_le_bytes(corrupt[8..16].try_into().unwrap()) as usize;
+        corrupt[16..16 + chunk_len].fill(0xFF);
+        let mut corrupt = CVecReader::<u64, _>::new(corrupt.as_slice());
+        assert_eq!(corrupt.next().unwrap().unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        assert!(corrupt.next().is_none());
+    }
+
+    #[test]
+    fn load_lazy_test() {
+        let path = std::env::temp_dir().join(format!("compressed_collections-load_lazy_test-{}.cvec", std::process::id()));
+        let compressed_stack: CVec<u64, 100> = (0..1050).collect();
+        compressed_stack.save(&path).unwrap();
+        let mut opened: CVec<u64, 100> = CVec::load_lazy(&path).unwrap();
+        assert_eq!(opened.resident_bytes(), 0);
+        assert_eq!(opened.spilled_bytes(), compressed_stack.resident_bytes());
+        assert_eq!(opened.len(), 1050);
+        assert_eq!(opened.get_ref(1040), Some(&1040));
+        assert_eq!(opened.get_ref(150), Some(&150));
+        assert_eq!(opened.resident_bytes(), 0);
+        assert!(compressed_stack.iter().eq(opened.iter()));
+        *opened.get_mut(150).unwrap() = 0;
+        opened.push(1050);
+        opened.flush();
+        assert!(opened.resident_bytes() > 0);
+        assert_eq!(opened.iter().filter(|&x| x == 0).count(), 2);
+        let loaded: CVec<u64, 100> = CVec::load(&path).unwrap();
+        assert_eq!(loaded, compressed_stack);
+        drop(opened);
+        // The file is not deleted along with the collection
+        assert!(CVec::<u64, 100>::load_lazy(&path).is_ok());
+        assert!(CVec::<u64, 10>::load_lazy(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn verify_test() {
+        let mut compressed_stack: CVec<u64, 100> = (0..1050).collect();
+        assert_eq!(compressed_stack.verify(), Ok(()));
+        if let Chunk::Compressed(data) = &mut compressed_stack.compressed_storage[2] {
+            let last = data.len() - 1;
+            data[last] ^= 0xFF;
+        }
+        compressed_stack.chunk_ends[5] -= 1;
+        let damaged = compressed_stack.verify().unwrap_err();
+        assert_eq!(damaged.iter().map(|damaged| damaged.index).collect::<Vec<_>>(), vec![2, 5, 6]);
+        assert!(matches!(damaged[0].damage, ChunkDamage::Corrupt(_)));
+        assert_eq!(damaged[1].damage, ChunkDamage::WrongLength { expected: 99, found: 100 });
+        assert_eq!(damaged[2].damage, ChunkDamage::WrongLength { expected: 101, found: 100 });
+
+        let path = std::env::temp_dir().join(format!("compressed_collections-verify_test-{}.cvec", std::process::id()));
+        let saved: CVec<u64, 100> = (0..1050).collect();
+        saved.save(&path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[10] ^= 0xFF;
+        std::fs::write(&path, bytes).unwrap();
+        let opened: CVec<u64, 100> = CVec::load_lazy(&path).unwrap();
+        let damaged = opened.verify().unwrap_err();
+        assert_eq!(damaged.len(), 1);
+        assert_eq!(damaged[0].index, 0);
+        assert!(matches!(damaged[0].damage, ChunkDamage::Unreadable(_)));
+        drop(opened);
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn fallible_test() {
+        #[derive(Debug, Clone, PartialEq, Deserialize)]
+        struct Picky(u32);
+        impl Serialize for Picky {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                if self.0 == 13 {
+                    return Err(serde::ser::Error::custom("unlucky"));
+                }
+                serializer.serialize_u32(self.0)
+            }
+        }
+        let mut compressed_stack: CVec<Picky, 10> = CVec::default();
+        for i in 0..9 {
+            assert!(compressed_stack.try_push(Picky(i)).is_ok());
+        }
+        let PushError { value, error } = compressed_stack.try_push(Picky(13)).unwrap_err();
+        assert_eq!(value, Picky(13));
+        assert!(matches!(error, Error::Serialize(_)));
+        assert_eq!(compressed_stack.len(), 9);
+        assert!(compressed_stack.try_push(Picky(9)).is_ok());
+        assert_eq!(compressed_stack.try_get(9).unwrap(), Some(Picky(9)));
+
+        let mut compressed_stack: CVec<u64, 100> = (0..250).collect();
+        if let Chunk::Compressed(data) = &mut compressed_stack.compressed_storage[1] {
+            let last = data.len() - 1;
+            data[last] ^= 0xFF;
+        }
+        assert_eq!(compressed_stack.try_get(50).unwrap(), Some(50));
+        assert_eq!(compressed_stack.try_get(249).unwrap(), Some(249));
+        assert_eq!(compressed_stack.try_get(250).unwrap(), None);
+        assert!(compressed_stack.try_get(150).is_err());
+        for i in (200..250).rev() {
+            assert_eq!(compressed_stack.try_pop().unwrap(), Some(i));
+        }
+        assert!(compressed_stack.try_pop().is_err());
+        assert_eq!(compressed_stack.len(), 200);
+        compressed_stack.truncate(100);
+        assert_eq!(compressed_stack.try_pop().unwrap(), Some(99));
+
+        // A chunk which decodes to the wrong number of elements
+        let mut compressed_stack: CVec<u64, 100> = (0..250).collect();
+        compressed_stack.compressed_storage[1] = Chunk::Compressed(compress(&(100..150).collect::<Vec<u64>>(), 0));
+        assert!(matches!(compressed_stack.try_get(120), Err(Error::Corrupt { chunk: 1 })));
+        assert!(matches!(compressed_stack.try_get(180), Err(Error::Corrupt { chunk: 1 })));
+        assert_eq!(compressed_stack.try_get(50).unwrap(), Some(50));
+
+        let path = std::env::temp_dir().join(format!("compressed_collections-fallible_test-{}.cvec", std::process::id()));
+        let saved: CVec<u64, 100> = (0..250).collect();
+        saved.save(&path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[10] ^= 0xFF;
+        std::fs::write(&path, bytes).unwrap();
+        let opened: CVec<u64, 100> = CVec::load_lazy(&path).unwrap();
+        let error = opened.try_get(50).unwrap_err();
+        assert!(matches!(error, Error::Corrupt { chunk: 0 }));
+        assert!(error.is_damage());
+        assert_eq!(std::io::Error::from(error).kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(opened.try_get(150).unwrap(), Some(150));
+        drop(opened);
+        std::fs::remove_file(&path).unwrap();
+        let error = Error::from(CVec::<u64, 100>::load(&path).unwrap_err());
+        assert!(matches!(&error, Error::Io(err) if err.kind() == std::io::ErrorKind::NotFound));
+        assert!(!error.is_damage());
+    }
+    #[test]
+    fn recovery_test() {
+        let mut compressed_stack: CVec<u64, 100> = (0..350).collect();
+        if let Chunk::Compressed(data) = &mut compressed_stack.compressed_storage[1] {
+            let last = data.len() - 1;
+            data[last] ^= 0xFF;
+        }
+        assert_eq!(compressed_stack.corrupt_chunk_policy(), CorruptChunkPolicy::Stop);
+        let values: Vec<_> = compressed_stack.try_iter().collect();
+        assert_eq!(values.len(), 101);
+        assert!(values[..100].iter().zip(0..).all(|(value, i)| *value.as_ref().unwrap() == i));
+        assert!(values[100].as_ref().unwrap_err().is_damage());
+
+        compressed_stack.set_corrupt_chunk_policy(CorruptChunkPolicy::Skip);
+        let values: Vec<_> = compressed_stack.clone().try_iter().collect();
+        assert_eq!(values.len(), 251);
+        assert!(values[100].is_err());
+        assert!(values[101..].iter().zip(200..).all(|(value, i)| *value.as_ref().unwrap() == i));
+
+        compressed_stack.set_corrupt_chunk_policy(CorruptChunkPolicy::Panic);
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| compressed_stack.try_iter().count())).is_err());
+
+        let (salvaged, damaged) = compressed_stack.salvage();
+        assert_eq!(damaged.len(), 1);
+        assert_eq!(damaged[0].index, 1);
+        assert!(matches!(damaged[0].damage, ChunkDamage::Corrupt(_)));
+        assert_eq!(salvaged.len(), 250);
+        assert!(salvaged.iter().eq((0..100).chain(200..350)));
+        assert_eq!(salvaged.verify(), Ok(()));
+    }
+    #[test]
+    fn log_test() {
+        let path = std::env::temp_dir().join(format!("compressed_collections-log_test-{}.log", std::process::id()));
+        let mut compressed_stack: CVec<u64, 100> = (0..250).collect();
+        compressed_stack.set_log(&path, LogSync::EveryChunks(2)).unwrap();
+        compressed_stack.set_raw_window(1);
+        compressed_stack.extend(250..1050);
+        compressed_stack.sync_log().unwrap();
+        assert!((0..1000).eq(CVec::<u64, 100>::recover_log(&path).unwrap()));
+        // Changes to logged elements are not recorded, pushes after them are
+        compressed_stack.truncate(50);
+        compressed_stack.extend(0..100);
+        compressed_stack.stop_log().unwrap();
+        compressed_stack.extend(0..100);
+        let recovered = CVec::<u64, 100>::recover_log(&path).unwrap();
+        assert_eq!(recovered.len(), 1100);
+        assert!((0..1000).chain(0..50).chain(0..50).eq(&recovered));
+        // A chunk cut short at the end is ignored
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!((0..1000).eq(CVec::<u64, 100>::recover_log(&path).unwrap()));
+        let mut recovered = recovered;
+        recovered.set_log(&path, LogSync::Never).unwrap();
+        assert_eq!(CVec::<u64, 100>::recover_log(&path).unwrap(), recovered);
+        assert!(compressed_stack.set_log(&path, LogSync::EveryChunks(0)).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn save_load_test() {
+        let path = std::env::temp_dir().join(format!("compressed_collections-save_load_test-{}.cvec", std::process::id()));
+        let mut compressed_stack: CVec<u64, 100> = (0..1050).collect();
+        *compressed_stack.get_mut(150).unwrap() = 0;
+        compressed_stack.save(&path).unwrap();
+        let loaded: CVec<u64, 100> = CVec::load(&path).unwrap();
+        assert!(compressed_stack.iter().eq(loaded.iter()));
+        assert_eq!(loaded.chunk_ends, compressed_stack.chunk_ends);
+        assert!(loaded.compressed_storage.iter().all(|chunk| matches!(chunk, Chunk::Compressed(_))));
+        assert!(CVec::<u64, 10>::load(&path).is_err());
+        let empty: CVec<u64, 100> = CVec::default();
+        empty.save(&path).unwrap();
+        assert_eq!(CVec::<u64, 100>::load(&path).unwrap(), empty);
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[1..]).unwrap();
+        assert!(CVec::<u64, 100>::load(&path).is_err());
+        compressed_stack.save(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[..8], b"CCVEC\0\x01\0");
+        assert_eq!(&bytes[bytes.len() - 8..], b"CCVEC\0\x01\0");
+        // A flipped bit in a chunk is caught by its checksum, when it is read for a lazily opened collection
+        let mut corrupt = bytes.clone();
+        corrupt[20] ^= 1;
+        std::fs::write(&path, &corrupt).unwrap();
+        let err = CVec::<u64, 100>::load(&path).unwrap_err();
+        assert!(err.to_string().contains("chunk 0"));
+        let mut opened = CVec::<u64, 100>::load_lazy(&path).unwrap();
+        assert_eq!(opened.get_ref(100), Some(&100));
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| opened.iter().next())).is_err());
+        // Newer versions are rejected
+        let mut newer = bytes.clone();
+        newer[6] = 2;
+        std::fs::write(&path, &newer).unwrap();
+        assert!(CVec::<u64, 100>::load(&path).unwrap_err().to_string().contains("version 2"));
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn save_load_chunk_size_test() {
+        let path = std::env::temp_dir().join(format!("compressed_collections-save_load_chunk_size_test-{}.cvec", std::process::id()));
+        let mut compressed_stack: CVecDyn<u64> = CVecDyn::with_chunk_size(ChunkSize::SizeElements(16));
+        compressed_stack.extend(0..1050);
+        compressed_stack.save(&path).unwrap();
+        // The chunk size is not in the file, `load` falls back to the default one
+        assert_eq!(CVecDyn::<u64>::load(&path).unwrap().chunk_elems(), ChunkSize::Default.elems::<u64>());
+        let mut loaded = CVecDyn::<u64>::load_with_chunk_size(&path, ChunkSize::SizeElements(16)).unwrap();
+        assert_eq!(loaded.chunk_elems(), 16);
+        assert_eq!(loaded.chunk_ends, compressed_stack.chunk_ends);
+        assert_eq!(loaded, compressed_stack);
+        loaded.extend(1050..1100);
+        assert_eq!(loaded.compressed_chunks().count(), 68);
+        let mut opened = CVecDyn::<u64>::load_lazy_with_chunk_size(&path, ChunkSize::SizeElements(16)).unwrap();
+        assert_eq!((opened.chunk_elems(), opened.get_ref(1000)), (16, Some(&1000)));
+        drop(opened);
+        assert!(CVecDyn::<u64>::load_with_chunk_size(&path, ChunkSize::SizeElements(8)).is_err());
+
+        // A chunk size in bytes is kept as well
+        let mut compressed_stack: CVecDyn<String> = CVecDyn::with_chunk_size(ChunkSize::SizeBytes(4096));
+        compressed_stack.extend((0..200).map(|i| format!("{i:0100}")));
+        compressed_stack.save(&path).unwrap();
+        let mut loaded = CVecDyn::<String>::load_with_chunk_size(&path, ChunkSize::SizeBytes(4096)).unwrap();
+        assert_eq!(loaded, compressed_stack);
+        loaded.extend((0..200).map(|i| format!("{i:0100}")));
+        compressed_stack.extend((0..200).map(|i| format!("{i:0100}")));
+        assert_eq!(loaded.chunk_ends, compressed_stack.chunk_ends);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn index_test() {
+        let mut big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVecMemo<u32, 100> = (0..1050).collect();
+        for idx in (0..1050).step_by(7) {
+            big_vec[idx] *= 3;
+            compressed_stack[idx] *= 3;
+        }
+        for idx in 0..1050 {
+            assert_eq!(big_vec[idx], compressed_stack[idx]);
+        }
+        assert_eq!(compressed_stack.compressed_chunks().count(), 10);
+        let read_back: CVecMemo<u32, 100> = decompress(&compress(&compressed_stack, 0));
+        assert_eq!(compressed_stack, read_back);
+    }
+
+    #[test]
+    fn get_mut_test() {
+        let mut big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        for idx in (0..1050).rev().step_by(3) {
+            *big_vec.get_mut(idx).unwrap() += 5;
+            *compressed_stack.get_mut(idx).unwrap() += 5;
+        }
+        assert!(compressed_stack.get_mut(1050).is_none());
+        for idx in 0..1050 {
+            assert_eq!(big_vec.get(idx), compressed_stack.get_ref(idx));
+        }
+        assert!(big_vec.iter().copied().eq(&compressed_stack));
+        compressed_stack.flush();
+        assert!(big_vec.into_iter().eq(compressed_stack));
+    }
+
+    #[test]
+    fn eq_by_elements_test() {
+        use std::hash::{BuildHasher, RandomState};
+        let hasher = RandomState::new();
+        let compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        // A chunk reopened by `get_mut`, chunks in the raw window and different chunk ends hold the same elements
+        let mut reopened: CVec<u32, 100> = (0..1050).collect();
+        *reopened.get_mut(5).unwrap() = 5;
+        let mut windowed: CVec<u32, 100> = CVec::new();
+        windowed.set_raw_window(3);
+        windowed.extend(0..1050);
+        let mut split: CVec<u32, 100> = (0..1049).collect();
+        split.insert(20, 1049);
+        split.remove(20);
+        split.push(1049);
+        for other in [&reopened, &windowed, &split] {
+            assert!(compressed_stack == *other);
+            assert_eq!(compressed_stack.cmp(other), std::cmp::Ordering::Equal);
+            assert_eq!(hasher.hash_one(&compressed_stack), hasher.hash_one(other));
+        }
+        *reopened.get_mut(500).unwrap() = 0;
+        assert!(compressed_stack != reopened && compressed_stack > reopened);
+        assert!(split.pop().is_some() && split < compressed_stack);
+    }
+
+    #[test]
+    fn insert_test() {
+        let mut big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        for (i, idx) in [0, 1050, 500, 99, 100, 101, 1000, 1049, 1048, 0].into_iter().enumerate() {
+            big_vec.insert(idx, 5000 + i as u32);
+            compressed_stack.insert(idx, 5000 + i as u32);
+        }
+        for i in 0..200 {
+            big_vec.insert(300, i);
+            compressed_stack.insert(300, i);
+        }
+        assert_eq!(big_vec.len(), compressed_stack.len());
+        for idx in 0..big_vec.len() + 1 {
+            assert_eq!(big_vec.get(idx), compressed_stack.get_ref(idx));
+        }
+        assert!(big_vec.into_iter().eq(compressed_stack));
+
+        // A chunk split off by an insert is accounted for like a pushed one
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        compressed_stack.set_raw_window(2);
+        compressed_stack.set_spill_budget(0);
+        compressed_stack.insert(350, 0);
+        assert!(matches!(compressed_stack.compressed_storage[4], Chunk::Spilled(_)));
+        compressed_stack.insert(1000, 0);
+        assert_eq!(compressed_stack.compressed_storage.len(), 12);
+        assert!(matches!(compressed_stack.compressed_storage[9], Chunk::Spilled(_)));
+        assert!(compressed_stack.compressed_storage[10..].iter().all(|chunk| matches!(chunk, Chunk::Raw(_))));
+        assert!(compressed_stack.is_consistent());
+    }
+
+    #[test]
+    fn remove_test() {
+        let mut big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        for idx in [0, 1048, 500, 99, 100, 101, 1000, 0] {
+            assert_eq!(big_vec.remove(idx), compressed_stack.remove(idx));
+        }
+        // Empty out a whole chunk
+        for _ in 0..150 {
+            assert_eq!(big_vec.remove(250), compressed_stack.remove(250));
+        }
+        assert_eq!(big_vec.len(), compressed_stack.len());
+        for idx in 0..big_vec.len() + 1 {
+            assert_eq!(big_vec.get(idx), compressed_stack.get_ref(idx));
+        }
+        compressed_stack.push(7);
+        big_vec.push(7);
+        assert!(big_vec.into_iter().eq(compressed_stack));
+    }
+
+    #[test]
+    fn extend_test() {
+        let mut big_vec: Vec<u32> = Vec::new();
+        let mut compressed_stack: CVec<u32, 100> = CVec::default();
+        for len in [0, 30, 250, 70, 1, 100, 99] {
+            let values: Vec<u32> = (0..len).collect();
+            big_vec.extend_from_slice(&values);
+            compressed_stack.extend_from_slice(&values);
+            big_vec.extend(values.iter().rev());
+            compressed_stack.extend(values.into_iter().rev());
+        }
+        assert_eq!(compressed_stack.uncompressed().len(), big_vec.len() % 100);
+        assert!(big_vec.into_iter().eq(compressed_stack));
+    }
+
+    #[test]
+    fn append_test() {
+        let mut big_vec: Vec<u32> = (0..250).collect();
+        let mut compressed_stack: CVec<u32, 100> = (0..250).collect();
+        let mut other: CVec<u32, 100> = (1000..1250).collect();
+        let mut other_small: CVec<u32, 30, 5> = (2000..2250).collect();
+        let mut other_big: CVec<u32, 200> = (3000..3250).collect();
+        compressed_stack.append(&mut other);
+        compressed_stack.append(&mut other_small);
+        compressed_stack.append(&mut other_big);
+        big_vec.extend((1000..1250).chain(2000..2250).chain(3000..3250));
+        assert!(other.is_empty() && other_small.is_empty() && other_big.is_empty());
+        assert_eq!(big_vec.len(), compressed_stack.len());
+        for idx in 0..big_vec.len() + 1 {
+            assert_eq!(big_vec.get(idx), compressed_stack.get_ref(idx));
+        }
+        assert!(big_vec.into_iter().eq(compressed_stack));
+    }
+
+    #[test]
+    fn append_moves_chunks_test() {
+        let mut compressed_stack: CVec<u32, 100> = (0..250).collect();
+        let mut other: CVec<u32, 100> = (1000..1250).collect();
+        let chunks: Vec<Vec<u8>> = other.compressed_chunks().map(Cow::into_owned).collect();
+        compressed_stack.append(&mut other);
+        // The chunks of `other` are moved as they are, after the buffer of `compressed_stack` as a short chunk
+        let moved: Vec<Vec<u8>> = compressed_stack.compressed_chunks().skip(3).map(Cow::into_owned).collect();
+        assert_eq!(chunks, moved);
+        assert!((0..250).chain(1000..1250).eq(compressed_stack));
+
+        // The chunks in the raw window of `other` are compressed, as they are not in the window of `self`
+        let mut compressed_stack: CVec<u32, 100> = (0..250).collect();
+        let mut other: CVec<u32, 100> = CVec::default();
+        other.set_raw_window(3);
+        other.extend(0..1050);
+        compressed_stack.append(&mut other);
+        assert_eq!(compressed_stack.compressed_chunks().count(), 13);
+        let frozen = compressed_stack.freeze();
+        assert!(frozen.iter().eq((0..250).chain(0..1050)));
+    }
+
+    #[test]
+    fn split_off_test() {
+        for at in [0, 1, 99, 100, 101, 550, 1000, 1049, 1050] {
+            let mut big_vec: Vec<u32> = (0..1050).collect();
+            let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+            let big_tail = big_vec.split_off(at);
+            let mut compressed_tail = compressed_stack.split_off(at);
+            assert_eq!(big_vec.len(), compressed_stack.len());
+            assert_eq!(big_tail.len(), compressed_tail.len());
+            for idx in 0..big_tail.len() + 1 {
+                assert_eq!(big_tail.get(idx), compressed_tail.get_ref(idx));
+            }
+            assert!(big_vec.into_iter().eq(compressed_stack));
+            compressed_tail.push(1050);
+            assert!(big_tail.into_iter().chain([1050]).eq(compressed_tail));
+        }
+
+        // The chunks moved out of the raw window are compressed, as they are not in the window of the tail
+        let mut compressed_stack: CVec<u32, 256> = CVec::default();
+        compressed_stack.set_raw_window(4);
+        compressed_stack.extend(0..2048);
+        let mut compressed_tail = compressed_stack.split_off(1024);
+        compressed_tail.extend(0..2048);
+        assert!(compressed_tail.compressed_storage.iter().all(|chunk| !matches!(chunk, Chunk::Raw(_))));
+        assert!((1024..2048).chain(0..2048).eq(compressed_tail));
+    }
+
+    #[test]
+    fn retain_test() {
+        let mut big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        big_vec.retain(|x| x % 3 == 0 || (200..400).contains(x));
+        compressed_stack.retain(|x| x % 3 == 0 || (200..400).contains(x));
+        assert_eq!(big_vec.len(), compressed_stack.len());
+        assert!(big_vec.into_iter().eq(compressed_stack));
+    }
+
+    #[test]
+    fn iter_mut_test() {
+        let mut big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        big_vec.iter_mut().for_each(|x| *x = *x * 7 % 13);
+        compressed_stack.iter_mut().for_each(|x| *x = *x * 7 % 13);
+        assert!(compressed_stack.compressed_storage.iter().filter(|chunk| matches!(chunk, Chunk::Raw(_))).count() <= 1);
+        assert!(big_vec.into_iter().eq(compressed_stack));
+    }
+
+    #[test]
+    fn chunks_test() {
+        let big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        *compressed_stack.get_mut(120).unwrap() += 1;
+        *compressed_stack.get_mut(120).unwrap() -= 1;
+        let chunks = compressed_stack.chunks();
+        assert_eq!(chunks.len(), 11);
+        let sums: Vec<u32> = chunks.map(|chunk| chunk.iter().sum()).collect();
+        assert_eq!(sums, big_vec.chunks(100).map(|chunk| chunk.iter().sum()).collect::<Vec<u32>>());
+        assert_eq!(CVec::<u32, 100>::default().chunks().count(), 0);
+    }
+
+    #[test]
+    fn iter_range_test() {
+        let big_vec: Vec<u32> = (0..1050).collect();
+        let compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        for (start, end) in [(0, 0), (0, 1050), (99, 101), (100, 200), (150, 1050), (1000, 1049), (1050, 1050), (320, 321)] {
+            let range = compressed_stack.iter_range(start..end);
+            assert_eq!(range.len(), end - start);
+            assert!(range.eq(big_vec[start..end].iter().copied()));
+        }
+        assert!(compressed_stack.iter_range(..=5).eq(0..=5));
+        assert!(compressed_stack.iter_range(250..=777).rev().eq((250..=777).rev()));
+        assert!(compressed_stack.iter_range(1040..).eq(1040..1050));
+    }
+
+    #[test]
+    fn double_ended_iter_test() {
+        let big_vec: Vec<u32> = (0..1050).collect();
+        let compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        assert!(compressed_stack.iter().rev().eq(big_vec.iter().rev().copied()));
+        let mut big_iter = big_vec.iter().copied();
+        let mut compressed_iter = compressed_stack.iter();
+        for i in 0..1060 {
+            if i % 3 == 0 {
+                assert_eq!(big_iter.next(), compressed_iter.next());
+            } else {
+                assert_eq!(big_iter.next_back(), compressed_iter.next_back());
+            }
+            assert_eq!(big_iter.len(), compressed_iter.len());
+        }
+    }
+
+    #[test]
+    fn binary_search_test() {
+        let big_vec: Vec<u32> = (0..1050).map(|x| x * 2).collect();
+        let mut compressed_stack: CVec<u32, 100> = big_vec.iter().copied().collect();
+        for x in 0..2110 {
+            assert_eq!(big_vec.binary_search(&x), compressed_stack.binary_search(&x));
+            assert_eq!(big_vec.binary_search_by_key(&x, |y| y + 1), compressed_stack.binary_search_by_key(&x, |y| y + 1));
+        }
+        // Searching through a chunk which is open for mutation
+        *compressed_stack.get_mut(500).unwrap() += 1;
+        assert_eq!(compressed_stack.binary_search(&1001), Ok(500));
+        assert_eq!(compressed_stack.binary_search(&1000), Err(500));
+        assert_eq!(CVec::<u32, 100>::default().binary_search(&1), Err(0));
+    }
+    #[test]
+    fn sort_test() {
+        let mut seed = 12345u64;
+        let big_vec: Vec<u32> = std::iter::repeat_with(|| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 40) as u32 % 1000
+        }).take(5050).collect();
+        let mut compressed_stack: CVec<u32, 100> = big_vec.iter().copied().collect();
+        compressed_stack.remove(1234);
+        let mut big_vec_sorted = big_vec.clone();
+        big_vec_sorted.remove(1234);
+        big_vec_sorted.sort();
+        compressed_stack.sort();
+        assert!(big_vec_sorted.iter().copied().eq(compressed_stack));
+
+        let mut pairs: Vec<(u32, usize)> = big_vec.into_iter().zip(0..).collect();
+        let mut compressed_pairs: CVec<(u32, usize), 100> = pairs.iter().copied().collect();
+        pairs.sort_by_key(|&(key, _)| key / 10);
+        compressed_pairs.sort_by_key(|&(key, _)| key / 10);
+        assert!(pairs.into_iter().eq(compressed_pairs));
+    }
+    #[test]
+    fn sort_by_test() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Item {
+            weight: f32,
+            id: u32,
+        }
+        let items = (0..1050).map(|id| Item { weight: ((id * 7919) % 1009) as f32 / 3.0, id });
+        let mut big_vec: Vec<Item> = items.clone().collect();
+        let mut compressed_stack: CVec<Item, 100> = items.clone().collect();
+        big_vec.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+        compressed_stack.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+        assert!(big_vec.iter().eq(&compressed_stack.into_iter().collect::<Vec<_>>()));
+
+        let mut compressed_stack: CVec<Item, 100> = items.collect();
+        compressed_stack.sort_unstable_by(|a, b| a.weight.total_cmp(&b.weight));
+        let sorted: Vec<Item> = compressed_stack.into_iter().collect();
+        assert!(sorted.windows(2).all(|pair| pair[0].weight <= pair[1].weight));
+        assert_eq!(sorted.len(), 1050);
+    }
+    #[test]
+    fn sort_panic_test() {
+        let values: Vec<u32> = (0..1050).map(|i| (i * 7919) % 1009).collect();
+        // Panics while sorting the chunks, and then while merging them
+        for panic_after in [10, 10_000] {
+            let mut compressed_stack: CVec<u32, 100> = values.iter().copied().collect();
+            compressed_stack.set_raw_window(2);
+            *compressed_stack.get_mut(10).unwrap() += 1;
+            let mut compares = 0;
+            let sorted = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                compressed_stack.sort_by(|a, b| {
+                    compares += 1;
+                    assert!(compares < panic_after);
+                    a.cmp(b)
+                })
+            }));
+            assert!(sorted.is_err());
+            let mut expected = values.clone();
+            expected[10] += 1;
+            assert_eq!(compressed_stack.into_vec(), expected);
+        }
+        // Runs are spilled along with the collection
+        let mut compressed_stack: CVec<u32, 100> = values.iter().copied().collect();
+        compressed_stack.set_spill_budget(0);
+        compressed_stack.sort();
+        assert_eq!(compressed_stack.resident_bytes(), 0);
+        assert!(compressed_stack.compressed_storage.iter().all(|chunk| matches!(chunk, Chunk::Spilled(_))));
+        let mut expected = values;
+        expected.sort();
+        assert_eq!(compressed_stack.into_vec(), expected);
+    }
+    #[test]
+    fn contains_test() {
+        #[derive(Serialize, Deserialize, PartialEq)]
+        struct NoClone(u32);
+        let compressed_stack: CVec<NoClone, 100> = (0..1050).map(NoClone).collect();
+        assert!(compressed_stack.contains(&NoClone(0)));
+        assert!(compressed_stack.contains(&NoClone(555)));
+        assert!(compressed_stack.contains(&NoClone(1049)));
+        assert!(!compressed_stack.contains(&NoClone(1050)));
+    }
+    #[test]
+    fn first_last_test() {
+        let mut compressed_stack: CVec<u32, 100> = CVec::default();
+        assert_eq!(compressed_stack.first(), None);
+        assert_eq!(compressed_stack.last(), None);
+        compressed_stack.extend(0..1000);
+        assert_eq!(compressed_stack.first(), Some(Either::Left(0)));
+        assert_eq!(compressed_stack.last(), Some(Either::Left(999)));
+        compressed_stack.push(1000);
+        assert_eq!(compressed_stack.last(), Some(Either::Right(&1000)));
+    }
+    #[test]
+    fn swap_test() {
+        let mut big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        for (a, b) in [(1040, 1045), (10, 20), (10, 500), (520, 30), (999, 1049), (1001, 5), (7, 7)] {
+            big_vec.swap(a, b);
+            compressed_stack.swap(a, b);
+        }
+        assert!(big_vec.into_iter().eq(compressed_stack));
+
+        // Chunks are written back in their kind: raw in the raw window, spilled to the store
+        let mut compressed_stack: CVec<u32, 100> = CVec::new();
+        compressed_stack.set_raw_window(2);
+        compressed_stack.extend(0..1050);
+        compressed_stack.swap(10, 1000);
+        assert!(matches!(compressed_stack.compressed_storage[9], Chunk::Raw(_)));
+        let store = std::sync::Arc::new(VecChunkStore::new());
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        compressed_stack.set_chunk_store(0, store.clone());
+        compressed_stack.swap(10, 500);
+        assert!(matches!(compressed_stack.compressed_storage[5], Chunk::Spilled(_)));
+        assert_eq!(compressed_stack.resident_bytes(), 0);
+        assert_eq!(compressed_stack.get_ref(10), Some(&500));
+        assert_eq!(compressed_stack.get_ref(500), Some(&10));
+
+        // A panic compressing the other chunk swaps the elements back
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Fussy(u32);
+        impl Serialize for Fussy {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                assert_ne!(self.0, 7777, "cannot serialize 7777");
+                serializer.serialize_u32(self.0)
+            }
+        }
+        let mut compressed_stack: CVec<Fussy, 100> = (0..1050).map(Fussy).collect();
+        *compressed_stack.get_mut(10).unwrap() = Fussy(7777);
+        let swapped = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| compressed_stack.swap(10, 500)));
+        assert!(swapped.is_err());
+        assert!(compressed_stack.is_consistent());
+        assert_eq!(compressed_stack.get_ref(10), Some(&Fussy(7777)));
+        assert_eq!(compressed_stack.get_ref(500), Some(&Fussy(500)));
+    }
+    #[test]
+    fn set_test() {
+        let mut big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        for idx in [0, 99, 100, 555, 1000, 1049] {
+            assert_eq!(compressed_stack.set(idx, idx as u32 * 3), std::mem::replace(&mut big_vec[idx], idx as u32 * 3));
+        }
+        assert!(big_vec.into_iter().eq(compressed_stack));
+    }
+    #[test]
+    fn clear_test() {
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        assert_eq!(compressed_stack.get_ref(5), Some(&5));
+        let capacity = compressed_stack.uncompressed().capacity();
+        compressed_stack.clear();
+        assert!(compressed_stack.is_empty());
+        assert_eq!(compressed_stack.get_ref(5), None);
+        assert_eq!(compressed_stack.uncompressed().capacity(), capacity);
+        compressed_stack.extend(0..1050);
+        assert!((0..1050).eq(compressed_stack));
+    }
+    #[test]
+    fn shrink_to_fit_test() {
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        *compressed_stack.get_mut(10).unwrap() = 10;
+        compressed_stack.shrink_to_fit();
+        assert_eq!(compressed_stack.uncompressed().capacity(), 0);
+        assert_eq!(compressed_stack.compressed_chunks().count(), 11);
+        assert_eq!(compressed_stack.len(), 1050);
+        compressed_stack.extend(1050..1100);
+        assert!((0..1100).eq(compressed_stack));
+    }
+    #[test]
+    fn reserve_test() {
+        let mut compressed_stack: CVec<u32, 100> = CVec::default();
+        compressed_stack.extend(0..50);
+        compressed_stack.reserve(1000);
+        assert!(compressed_stack.uncompressed().capacity() >= 100);
+        let storage_capacity = compressed_stack.compressed_storage.capacity();
+        assert!(storage_capacity >= 10);
+        compressed_stack.extend(50..1050);
+        assert_eq!(compressed_stack.compressed_storage.capacity(), storage_capacity);
+        assert!((0..1050).eq(compressed_stack.iter()));
+
+        assert!(compressed_stack.try_reserve(usize::MAX).is_err());
+        assert!(compressed_stack.try_reserve(usize::MAX / 2).is_err());
+        assert_eq!(compressed_stack.compressed_storage.capacity(), storage_capacity);
+        compressed_stack.try_reserve(1000).unwrap();
+        assert!(compressed_stack.compressed_storage.capacity() >= 20);
+        assert!((0..1050).eq(compressed_stack));
+
+        let error = Error::from(Vec::<u8>::new().try_reserve(usize::MAX).unwrap_err());
+        assert!(matches!(error, Error::Alloc(_)));
+        assert!(!error.is_damage());
+        assert_eq!(std::io::Error::from(error).kind(), std::io::ErrorKind::OutOfMemory);
+    }
+    #[test]
+    fn push_slice_test() {
+        let big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVec<u32, 100> = CVec::default();
+        compressed_stack.push_slice(&big_vec[..30]);
+        compressed_stack.push_slice(&big_vec[30..30]);
+        compressed_stack.push_slice(&big_vec[30..]);
+        assert_eq!(compressed_stack.uncompressed().len(), 50);
+        assert!(big_vec.into_iter().eq(compressed_stack));
+    }
+    #[test]
+    fn from_vec_test() {
+        for len in [0, 99, 100, 1050] {
+            let big_vec: Vec<u32> = (0..len).collect();
+            let compressed_stack: CVec<u32, 100> = big_vec.clone().into();
+            assert_eq!(compressed_stack.len(), len as usize);
+            assert_eq!(compressed_stack.uncompressed().len(), len as usize % 100);
+            assert_eq!(compressed_stack.into_vec(), big_vec);
+        }
+    }
+    #[test]
+    fn from_fn_test() {
+        let compressed_stack: CVec<u32, 100> = CVec::from_fn(1050, |idx| idx as u32 * 2);
+        assert!((0..1050).map(|x| x * 2).eq(compressed_stack));
+        let compressed_stack: CVec<String, 100> = CVec::repeat("abc".to_string(), 1050);
+        assert_eq!(compressed_stack.len(), 1050);
+        assert_eq!(compressed_stack.uncompressed().len(), 50);
+        assert!(compressed_stack.into_iter().all(|x| x == "abc"));
+        assert!(CVec::<u32, 100>::repeat(1, 0).is_empty());
+    }
+    #[test]
+    fn windows_test() {
+        let big_vec: Vec<u32> = (0..1050).collect();
+        let compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        for size in [1, 3, 100, 150, 1050, 1051] {
+            let mut expected = big_vec.windows(size);
+            compressed_stack.windows(size).for_each(|window| assert_eq!(Some(window), expected.next()));
+            assert_eq!(expected.next(), None);
+        }
+    }
+    #[test]
+    fn dedup_test() {
+        let mut big_vec: Vec<u32> = (0..1050).map(|x| x / 7).collect();
+        let mut compressed_stack: CVec<u32, 100> = big_vec.iter().copied().collect();
+        big_vec.dedup();
+        compressed_stack.dedup();
+        assert!(big_vec.iter().copied().eq(compressed_stack));
+
+        let mut big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        big_vec.dedup_by_key(|x| *x / 250);
+        compressed_stack.dedup_by_key(|x| *x / 250);
+        assert!(big_vec.into_iter().eq(compressed_stack));
+    }
+    #[test]
+    fn partition_test() {
+        let compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        let (even, odd) = compressed_stack.partition(|x| x % 2 == 0);
+        assert!((0..1050).step_by(2).eq(even));
+        assert!((1..1050).step_by(2).eq(odd));
+    }
+    #[test]
+    fn pop_chunk_test() {
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        assert_eq!(compressed_stack.pop_chunk(), Some((1000..1050).collect()));
+        assert_eq!(compressed_stack.pop_chunk(), Some((900..1000).collect()));
+        *compressed_stack.get_mut(850).unwrap() = 0;
+        assert_eq!(compressed_stack.pop_chunk().unwrap()[50], 0);
+        assert_eq!(compressed_stack.len(), 800);
+        assert!((0..800).eq(compressed_stack));
+    }
+    #[test]
+    fn iter_order_test() {
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        assert!((0..1050).eq(&compressed_stack));
+        let top_to_bottom: Vec<u32> = compressed_stack.iter().rev().collect();
+        let mut popped = Vec::new();
+        while let Some(x) = compressed_stack.pop() {
+            popped.push(x);
+        }
+        assert_eq!(top_to_bottom, popped);
+    }
+    #[test]
+    fn truncate_test() {
+        for len in [1050, 1049, 1000, 999, 420, 100, 1, 0] {
+            let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+            assert_eq!(compressed_stack.get_ref(1010), Some(&1010));
+            *compressed_stack.get_mut(990).unwrap() = 990;
+            compressed_stack.truncate(len);
+            assert_eq!(compressed_stack.len(), len);
+            assert_eq!(compressed_stack.get_ref(len), None);
+            assert!((0..len as u32).eq(compressed_stack.iter()));
+            compressed_stack.extend(2000..2200);
+            assert!((0..len as u32).chain(2000..2200).eq(compressed_stack));
+        }
+    }
+    #[test]
+    fn cache_budget_test() {
+        let big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVecBudgeted<u32, 100> = (0..1050).collect();
+        assert_eq!(compressed_stack.cache_budget(), DEFAULT_CACHE_BUDGET);
+        compressed_stack.set_cache_budget(3 * 100 * 4);
+        for idx in [5, 150, 250, 5, 350, 1049, 420, 5, 1050] {
+            assert_eq!(big_vec.get(idx), compressed_stack.get_ref(idx));
+            assert!(compressed_stack.cache.lines.len() <= 3);
+        }
+        // The least recently used chunks were evicted
+        assert!(compressed_stack.cache.is_cached(0) && compressed_stack.cache.is_cached(4));
+        compressed_stack.set_cache_budget(100 * 4);
+        assert_eq!(compressed_stack.cache.lines.len(), 1);
+        compressed_stack.set_cache_budget(0);
+        assert!(compressed_stack.cache.lines.is_empty());
+        assert_eq!(compressed_stack.get_ref(999), Some(&999));
+        assert!(compressed_stack.cache.is_cached(9));
+        compressed_stack.truncate(900);
+        assert!(!compressed_stack.cache.is_cached(9));
+    }
+    #[test]
+    fn cache_pool_test() {
+        let line_bytes = 100 * std::mem::size_of::<u32>();
+        let pool = CachePool::new(3 * line_bytes);
+        let big_vec: Vec<u32> = (0..1050).collect();
+        let mut a: CVecPooled<u32, 100> = CVecPooled::new_in(&pool);
+        a.extend(0..1050);
+        let mut b = a.clone();
+        assert!(b.cache_pool().ptr_eq(&CachePool::thread_default()));
+        b.set_cache_pool(&pool);
+        for idx in [5, 150, 250, 350] {
+            assert_eq!(big_vec.get(idx), a.get_ref(idx));
+            assert_eq!(big_vec.get(idx), b.get_ref(idx));
+            assert!(pool.used() <= 3 * line_bytes);
+        }
+        // The least recently used chunks were evicted, whichever collection they belong to
+        assert!(!a.cache.is_cached(2) && b.cache.is_cached(2) && a.cache.is_cached(3) && b.cache.is_cached(3));
+        assert_eq!(a.get_ref(250), Some(&250));
+        assert!(a.cache.is_cached(2) && !b.cache.is_cached(2));
+        pool.set_budget(line_bytes);
+        assert!(a.cache.is_cached(2) && pool.used() == line_bytes);
+        drop(a);
+        assert_eq!(pool.used(), 0);
+        assert_eq!(b.get_ref(999), Some(&999));
+        b.truncate(900);
+        assert_eq!(pool.used(), 0);
+    }
+    #[test]
+    fn sync_test() {
+        let compressed_stack: CVecSync<u32, 100> = (0..1050).collect();
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let compressed_stack = &compressed_stack;
+                s.spawn(move || {
+                    for idx in (t..1050).step_by(7) {
+                        assert_eq!(compressed_stack.get(idx as usize), Some(idx));
+                    }
+                });
+            }
+        });
+        assert_eq!(compressed_stack.get(1050), None);
+        fn assert_send_sync<S: Send + Sync>() {}
+        assert_send_sync::<CVecSync<String>>();
+        assert_send_sync::<std::sync::Arc<CVecSync<Vec<u8>, 0>>>();
+    }
+    #[test]
+    fn clean_flush_test() {
+        let mut compressed_stack: CVec<u32, 100> = (0..1000).collect();
+        let chunks: Vec<Vec<u8>> = compressed_stack.compressed_chunks().map(Cow::into_owned).collect();
+        #[allow(deprecated)]
+        let boxed = compressed_stack.compressed();
+        assert!(boxed.iter().map(|chunk| &**chunk).eq(chunks.iter().map(Vec::as_slice)));
+        // Only read, so the old bytes are put back
+        assert_eq!(compressed_stack.peek(), Some(&999));
+        assert!(compressed_stack.open_clean.is_some());
+        compressed_stack.flush();
+        let last = &compressed_stack.compressed_storage[9];
+        assert!(matches!(last, Chunk::Compressed(data) if **data == *chunks[9]));
+        // Mutating the chunk after reading it dirties it
+        assert_eq!(compressed_stack.peek(), Some(&999));
+        *compressed_stack.get_mut(998).unwrap() += 1;
+        assert!(compressed_stack.open_clean.is_none());
+        compressed_stack.flush();
+        let mut expected: Vec<u32> = (900..1000).collect();
+        expected[98] += 1;
+        assert_eq!(compressed_stack.compressed_chunks().nth(9).as_deref(), Some(&*compress(&expected, 0)));
+        // Popping the chunk out forgets its bytes
+        assert_eq!(compressed_stack.peek(), Some(&999));
+        assert_eq!(compressed_stack.pop(), Some(999));
+        assert_eq!(compressed_stack.pop_n(99).len(), 99);
+        assert!(compressed_stack.open_clean.is_none());
+        assert_eq!(compressed_stack.into_vec(), (0..900).collect::<Vec<_>>());
+    }
+    #[test]
+    fn prefetch_test() {
+        let mut compressed_stack: CVecPrefetch<u32, 100> = (0..1050).collect();
+        for idx in 0..1050 {
+            assert_eq!(compressed_stack.get_ref(idx as usize), Some(&idx));
+            if idx == 150 {
+                // Reading chunk 1 straight after chunk 0 started reading chunk 2 ahead
+                assert_eq!(compressed_stack.cache.pending.as_ref().map(|(chunk, _)| *chunk), Some(2));
+            }
+        }
+        // Random access does not prefetch, the background thread is kept for later scans
+        assert!(compressed_stack.cache.worker.is_some());
+        compressed_stack.cache.kill_all();
+        assert!(compressed_stack.cache.worker.is_some());
+        for idx in [500, 5, 720, 330] {
+            assert_eq!(compressed_stack.get_ref(idx as usize), Some(&idx));
+            assert!(compressed_stack.cache.pending.is_none());
+        }
+        // A prefetched chunk which changes in the meantime is not used
+        assert_eq!(compressed_stack.get_ref(450), Some(&450));
+        assert_eq!(compressed_stack.get_ref(550), Some(&550));
+        assert!(compressed_stack.cache.pending.is_some());
+        *compressed_stack.get_mut(650).unwrap() = 0;
+        assert!(compressed_stack.cache.pending.is_none());
+        compressed_stack.flush();
+        assert_eq!(compressed_stack.get_ref(650), Some(&0));
+    }
+    #[test]
+    fn pin_test() {
+        let mut compressed_stack: CVecBudgeted<u32, 100> = (0..1050).collect();
+        compressed_stack.set_cache_budget(2 * 100 * 4);
+        compressed_stack.pin_chunk(0);
+        compressed_stack.pin_chunk(9);
+        assert!(compressed_stack.cache.is_cached(0) && compressed_stack.cache.is_cached(9));
+        for idx in (100..900).step_by(50) {
+            assert_eq!(compressed_stack.get_ref(idx as usize), Some(&idx));
+        }
+        // Pinned chunks survive scans and budget changes
+        compressed_stack.set_cache_budget(0);
+        assert!(compressed_stack.cache.is_cached(0) && compressed_stack.cache.is_cached(9));
+        assert_eq!(compressed_stack.cache.lines.len(), 2);
+        compressed_stack.unpin_chunk(0);
+        assert!(!compressed_stack.cache.is_cached(0) && compressed_stack.cache.is_cached(9));
+        // The pin outlives a change to the chunk
+        *compressed_stack.get_mut(950).unwrap() = 0;
+        compressed_stack.flush();
+        assert_eq!(compressed_stack.get_ref(950), Some(&0));
+        assert_eq!(compressed_stack.get_ref(5), Some(&5));
+        assert!(compressed_stack.cache.is_cached(9));
+        compressed_stack.truncate(800);
+        compressed_stack.unpin_chunk(9);
+        assert!(compressed_stack.cache.lines.is_empty());
+    }
+    #[test]
+    #[should_panic(expected = "chunk index out of bounds")]
+    fn pin_out_of_bounds_test() {
+        let mut compressed_stack: CVecBudgeted<u32, 100> = (0..1050).collect();
+        compressed_stack.pin_chunk(10);
+    }
+    #[test]
+    fn eviction_policy_test() {
+        fn cached_after<P: EvictionPolicy>() -> Vec<usize> {
+            let mut compressed_stack: CVecBudgeted<u32, 100, 0, P> = (0..1050).collect();
+            compressed_stack.set_cache_budget(3 * 100 * 4);
+            // Chunk 0 is hot, then a scan passes over chunks 1 to 3, chunk 1 is read again and then chunk 4
+            for idx in [0, 10, 20, 150, 250, 350, 150, 450] {
+                assert_eq!(compressed_stack.get_ref(idx as usize), Some(&idx));
+            }
+            let mut cached: Vec<usize> = compressed_stack.cache.lines.keys().copied().collect();
+            cached.sort();
+            cached
+        }
+        assert_eq!(cached_after::<Lru>(), [1, 3, 4]);
+        assert_eq!(cached_after::<Lfu>(), [0, 1, 4]);
+        assert_eq!(cached_after::<Fifo>(), [2, 3, 4]);
+        assert_eq!(cached_after::<Clock>(), [0, 1, 4]);
+    }
+    #[test]
+    fn warm_test() {
+        let mut compressed_stack: CVecBudgeted<u32, 100> = (0..1050).collect();
+        compressed_stack.warm(150..=300);
+        let mut cached: Vec<usize> = compressed_stack.cache.lines.keys().copied().collect();
+        cached.sort();
+        assert_eq!(cached, [1, 2, 3]);
+        compressed_stack.warm(1000..);
+        compressed_stack.warm(999..999);
+        assert_eq!(compressed_stack.cache.lines.len(), 3);
+        compressed_stack.warm(..);
+        assert_eq!(compressed_stack.cache.lines.len(), 10);
+        // Single chunk caches keep the last chunk warmed
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        compressed_stack.warm(..250);
+        assert!(compressed_stack.cache.is_cached(2));
+        assert_eq!(compressed_stack.get_ref(210), Some(&210));
+    }
+    #[test]
+    fn raw_window_test() {
+        let is_raw = |chunk: &Chunk<u32>| matches!(chunk, Chunk::Raw(_));
+        let mut compressed_stack: CVec<u32, 100> = CVec::new();
+        compressed_stack.set_raw_window(3);
+        compressed_stack.extend(0..1050);
+        let raw: Vec<bool> = compressed_stack.compressed_storage.iter().map(is_raw).collect();
+        assert_eq!(raw, [false, false, false, false, false, false, false, true, true, true]);
+        // Mutating a chunk in the window leaves it raw, one outside it is compressed once another chunk is opened
+        *compressed_stack.get_mut(850).unwrap() = 0;
+        *compressed_stack.get_mut(150).unwrap() = 0;
+        *compressed_stack.get_mut(950).unwrap() = 0;
+        assert!(is_raw(&compressed_stack.compressed_storage[8]) && !is_raw(&compressed_stack.compressed_storage[1]));
+        assert_eq!(compressed_stack.pop_n(150).len(), 150);
+        assert_eq!(compressed_stack.compressed_storage.iter().filter(|chunk| is_raw(chunk)).count(), 2);
+        compressed_stack.extend(900..1050);
+        compressed_stack.set_raw_window(1);
+        assert_eq!(compressed_stack.compressed_storage.iter().filter(|chunk| is_raw(chunk)).count(), 1);
+        // Reading the bytes out compresses the window on the fly, leaving it raw
+        assert_eq!(compressed_stack.compressed_chunks().count(), 10);
+        assert!(is_raw(compressed_stack.compressed_storage.last().unwrap()));
+        compressed_stack.extend(1050..1150);
+        assert!(is_raw(compressed_stack.compressed_storage.last().unwrap()));
+        let mut expected: Vec<u32> = (0..1150).collect();
+        expected[150] = 0;
+        expected[850] = 0;
+        assert_eq!(compressed_stack.into_vec(), expected);
+    }
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_iter_test() {
+        use rayon::prelude::*;
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        *compressed_stack.get_mut(150).unwrap() = 0;
+        let mut expected: Vec<u32> = (0..1050).collect();
+        expected[150] = 0;
+        assert_eq!(compressed_stack.par_iter().collect::<Vec<_>>(), expected);
+        assert_eq!(compressed_stack.par_iter().map(u64::from).sum::<u64>(), expected.iter().map(|&x| u64::from(x)).sum());
+        assert_eq!(compressed_stack.into_par_iter().collect::<Vec<_>>(), expected);
+        let compressed_stack: CVecSync<u32, 100> = (0..1000).collect();
+        assert_eq!(compressed_stack.par_iter().filter(|x| x % 3 == 0).count(), 334);
+    }
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_test() {
+        use std::{future::poll_fn, pin::Pin};
+        use futures_core::Stream;
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let mut compressed_stack = CVec::new::<100, 0>();
+            for i in 0..1050u32 {
+                compressed_stack.push_async(i).await;
+            }
+            assert_eq!(compressed_stack, (0..1050).collect());
+            let mut stream = compressed_stack.stream_async();
+            assert_eq!(stream.size_hint(), (1050, Some(1050)));
+            let mut streamed = Vec::new();
+            while let Some(x) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+                streamed.push(x);
+                assert_eq!(stream.size_hint().0, 1050 - streamed.len());
+            }
+            assert!((0..1050).eq(streamed));
+            for i in (0..1050).rev() {
+                assert_eq!(compressed_stack.pop_async().await, Some(i));
+            }
+            assert_eq!(compressed_stack.pop_async().await, None);
+            let mut compressed_stack = CVec::new::<100, 0>();
+            compressed_stack.set_raw_window(2);
+            for i in 0..1050u32 {
+                compressed_stack.push_async(i).await;
+            }
+            assert_eq!(compressed_stack.compressed_storage.iter().filter(|chunk| matches!(chunk, Chunk::Raw(_))).count(), 2);
+            let mut popped = compressed_stack.clone();
+            assert!((0..1050).rev().eq(std::iter::from_fn(|| compressed_stack.pop())));
+            // The raw chunks of the window are popped without decompressing
+            for i in (0..1050).rev() {
+                assert_eq!(popped.pop_async().await, Some(i));
+            }
+            assert!(popped.is_empty());
+            // As is the last chunk once `peek` reopened it
+            let mut compressed_stack: CVec<u32> = (0..2048).collect();
+            assert_eq!(compressed_stack.peek(), Some(&2047));
+            assert_eq!(compressed_stack.pop_async().await, Some(2047));
+            assert_eq!(compressed_stack.len(), 2047);
+        });
+    }
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_cancel_test() {
+        use std::{future::{poll_fn, Future}, pin::pin, sync::mpsc, task::Poll};
+        // The only blocking thread is kept busy, so the compression of the chunk is still pending when it is dropped
+        let runtime = tokio::runtime::Builder::new_current_thread().max_blocking_threads(1).build().unwrap();
+        runtime.block_on(async {
+            let (release, wait) = mpsc::channel::<()>();
+            let busy = tokio::task::spawn_blocking(move || wait.recv());
+            let mut compressed_stack = CVec::new::<100, 0>();
+            compressed_stack.extend(0..99u32);
+            {
+                let mut push = pin!(compressed_stack.push_async(99));
+                assert!(poll_fn(|cx| Poll::Ready(push.as_mut().poll(cx))).await.is_pending());
+            }
+            release.send(()).unwrap();
+            busy.await.unwrap().unwrap();
+            assert!(compressed_stack.is_consistent());
+            assert_eq!(compressed_stack.uncompressed().len(), 0);
+            assert!(matches!(compressed_stack.compressed_storage[0], Chunk::Raw(_)));
+            for i in 100..250 {
+                compressed_stack.push_async(i).await;
+            }
+            assert!(compressed_stack.compressed_storage.iter().all(|chunk| matches!(chunk, Chunk::Compressed(_))));
+            assert_eq!(compressed_stack.get_ref(150), Some(&150));
+            assert!((0..250).eq(&compressed_stack));
+        });
+    }
+    #[test]
+    fn maintain_test() {
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).map(|i| i % 7).collect();
+        compressed_stack.retain(|&x| x != 0);
+        compressed_stack.set_raw_window(1);
+        compressed_stack.extend(0..100);
+        *compressed_stack.get_mut(10).unwrap() = 10;
+        let expected: Vec<u32> = compressed_stack.iter().collect();
+        let bytes = compressed_stack.resident_bytes();
+        let mut steps = 0;
+        while compressed_stack.maintain(11) {
+            steps += 1;
+        }
+        assert!(steps > 0);
+        assert!(!compressed_stack.maintain(11));
+        // The chunk open for mutation and the one in the raw window are left as they are
+        assert!(matches!(compressed_stack.compressed_storage[0], Chunk::Raw(_)));
+        assert!(matches!(compressed_stack.compressed_storage.last(), Some(Chunk::Raw(_))));
+        assert!(compressed_stack.compressed_storage.len() < 11);
+        assert!(compressed_stack.chunk_ends.windows(2).all(|ends| ends[1] - ends[0] <= 100));
+        let maintained_bytes = compressed_stack.resident_bytes();
+        assert!(maintained_bytes < bytes);
+        assert!(compressed_stack.iter().eq(expected.iter().copied()));
+        assert_eq!(compressed_stack.get_ref(500), Some(&expected[500]));
+        compressed_stack.push(1);
+        assert!(!compressed_stack.maintain(11));
+
+        let maintained = Maintained::new(compressed_stack, 11, std::time::Duration::from_millis(1));
+        for i in 0..1000 {
+            maintained.lock().push(i);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let compressed_stack = maintained.into_inner();
+        assert!(compressed_stack.iter().eq(expected.into_iter().chain([1]).chain(0..1000)));
+    }
+    #[test]
+    fn appender_test() {
+        let appender = CVecAppender::<u32, 100>::new();
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let appender = &appender;
+                s.spawn(move || {
+                    let mut shard = appender.shard();
+                    shard.extend((0..1050).map(|i| t * 1050 + i));
+                    assert_eq!(shard.len(), 1050);
+                });
+            }
+        });
+        drop(appender.shard());
+        let compressed_stack: CVec<u32, 100> = appender.finish();
+        // Only the four leftover buffers of 50 elements were pushed again
+        assert_eq!(compressed_stack.compressed_storage.len(), 42);
+        assert!(compressed_stack.chunk_ends.iter().enumerate().all(|(i, &end)| end == (i + 1) * 100));
+        let mut shards: Vec<Vec<u32>> = Vec::new();
+        for x in compressed_stack.iter().take(4000) {
+            match shards.iter_mut().find(|shard| shard.last() == Some(&x.wrapping_sub(1))) {
+                Some(shard) => shard.push(x),
+                None => shards.push(vec![x]),
+            }
+        }
+        assert_eq!(shards.len(), 4);
+        assert!(shards.iter().all(|shard| shard.len() == 1000 && shard[0] % 1050 == 0));
+        let mut values = compressed_stack.into_vec();
+        values.sort();
+        assert!((0..4200).eq(values));
+    }
+    #[test]
+    fn iter_pipelined_test() {
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        compressed_stack.set_raw_window(2);
+        compressed_stack.extend(1050..1250);
+        *compressed_stack.get_mut(150).unwrap() = 0;
+        let mut expected: Vec<u32> = (0..1250).collect();
+        expected[150] = 0;
+        let mut iter = compressed_stack.iter_pipelined();
+        assert_eq!(iter.len(), 1250);
+        assert!(iter.by_ref().take(500).eq(expected[..500].iter().copied()));
+        assert_eq!(iter.len(), 750);
+        assert!(iter.eq(expected[500..].iter().copied()));
+        assert_eq!(CVec::<u32, 100>::default().iter_pipelined().next(), None);
+    }
+    #[test]
+    fn fast_clear_test() {
+        let mut compressed_stack: CVec<String, 100> = (0..1050).map(|i| i.to_string()).collect();
+        compressed_stack.set_raw_window(2);
+        assert_eq!(compressed_stack.get_ref(5).map(String::as_str), Some("5"));
+        let freeing = compressed_stack.fast_clear();
+        assert!(compressed_stack.is_empty());
+        assert_eq!(compressed_stack.get_ref(5), None);
+        compressed_stack.extend((0..150).map(|i| i.to_string()));
+        assert!(compressed_stack.iter().eq((0..150).map(|i| i.to_string())));
+        freeing.join().unwrap();
+        compressed_stack.drop_in_background().join().unwrap();
+    }
+    #[test]
+    fn memory_budget_test() {
+        let mut compressed_stack: CVec<u64, 100> = CVec::default();
+        compressed_stack.set_memory_budget(4096, OverflowPolicy::Reject);
+        assert_eq!(compressed_stack.memory_budget(), Some((4096, &OverflowPolicy::Reject)));
+        let mut pushed = 0u64;
+        while compressed_stack.try_push(pushed.wrapping_mul(0x9E37_79B9_7F4A_7C15)).is_ok() {
+            pushed += 1;
+        }
+        assert!(compressed_stack.memory_bytes() <= 4096);
+        assert!(compressed_stack.len() > 100);
+        assert!(matches!(compressed_stack.try_push(0), Err(PushError { value: 0, error: Error::BudgetExceeded })));
+        compressed_stack.clear_memory_budget();
+        assert!(compressed_stack.try_push(0).is_ok());
+
+        let mut compressed_stack: CVec<u64, 100> = (0..1050).collect();
+        let uncached = compressed_stack.memory_bytes();
+        assert_eq!(compressed_stack.get_ref(150), Some(&150));
+        assert_eq!(compressed_stack.memory_bytes(), uncached + 800);
+        compressed_stack.set_memory_budget(uncached + 400, OverflowPolicy::EvictCache);
+        assert_eq!(compressed_stack.memory_bytes(), uncached);
+
+        let dir = std::env::temp_dir().join(format!("compressed_collections-memory_budget_test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut compressed_stack: CVec<u64, 100> = CVec::default();
+        compressed_stack.set_memory_budget(4096, OverflowPolicy::Spill(dir.clone()));
+        compressed_stack.extend((0..10_050).map(|i| i * i));
+        assert!(compressed_stack.memory_bytes() <= 4096);
+        assert!(compressed_stack.spilled_bytes() > 0);
+        assert!((0..10_050).map(|i| i * i).eq(compressed_stack.iter()));
+        drop(compressed_stack);
+        std::fs::remove_dir(&dir).unwrap();
+
+        let values = || (0..5000u32).map(|i| format!("value {}", i % 97));
+        let mut compressed_stack: CVec<String, 100> = values().collect();
+        let before = compressed_stack.memory_bytes();
+        compressed_stack.set_memory_budget(before - 1, OverflowPolicy::Recompress(11));
+        let after = compressed_stack.memory_bytes();
+        assert!(after < before);
+        assert!(values().eq(compressed_stack.iter()));
+        compressed_stack.extend(values());
+        assert!(compressed_stack.memory_bytes() < 2 * after);
+    }
+    #[test]
+    fn spill_test() {
+        let dir = std::env::temp_dir().join(format!("compressed_collections-spill_test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut compressed_stack: CVec<u64, 100> = CVec::default();
+        compressed_stack.set_spill_budget_in(2048, &dir);
+        assert_eq!(compressed_stack.spill_budget(), Some(2048));
+        compressed_stack.extend((0..10_050).map(|i| i * i));
+        let expected: Vec<u64> = (0..10_050).map(|i| i * i).collect();
+        assert!(compressed_stack.resident_bytes() <= 2048);
+        assert!(compressed_stack.spilled_bytes() > 0);
+        assert!(matches!(compressed_stack.compressed_storage[0], Chunk::Spilled(_)));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        assert!(compressed_stack.iter().eq(expected.iter().copied()));
+        assert!(compressed_stack.iter().rev().eq(expected.iter().rev().copied()));
+        assert!(compressed_stack.iter_pipelined().eq(expected.iter().copied()));
+        assert_eq!(compressed_stack.get_ref(150), Some(&expected[150]));
+        assert_eq!(compressed_stack.get_ref(151), Some(&expected[151]));
+        assert_eq!(compressed_stack.get_uncached(152).unwrap().left(), Some(expected[152]));
+        // Reading leaves the chunk on disk, as does reading its bytes out, mutating brings it back
+        let resident = compressed_stack.resident_bytes();
+        let chunks: Vec<Vec<u8>> = compressed_stack.compressed_chunks().map(Cow::into_owned).collect();
+        assert_eq!(chunks.len(), 100);
+        assert!(decompress::<Vec<u64>>(&chunks[1]).iter().eq(&expected[100..200]));
+        assert_eq!(compressed_stack.resident_bytes(), resident);
+        assert!(matches!(compressed_stack.compressed_storage[1], Chunk::Spilled(_)));
+        *compressed_stack.get_mut(150).unwrap() = 0;
+        assert!(matches!(compressed_stack.compressed_storage[1], Chunk::Raw(_)));
+        compressed_stack.flush();
+        assert!(matches!(compressed_stack.compressed_storage[1], Chunk::Compressed(_)));
+        let cloned = compressed_stack.clone();
+        let serialized = postcard::to_stdvec(&compressed_stack).unwrap();
+        let mut deserialized: CVec<u64, 100> = postcard::from_bytes(&serialized).unwrap();
+        drop(compressed_stack);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        assert!(cloned.iter().eq(deserialized.iter()));
+        assert_eq!(deserialized.get_ref(150), Some(&0));
+        assert_eq!(format!("{cloned:?}").matches("spilled").count(), cloned.compressed_storage.iter().filter(|chunk| matches!(chunk, Chunk::Spilled(_))).count());
+        let frozen = cloned.freeze();
+        assert!(frozen.iter().eq(deserialized.iter()));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_test() {
+        let dir = std::env::temp_dir().join(format!("compressed_collections-mmap_test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut compressed_stack: CVec<u32, 100> = CVec::default();
+        compressed_stack.map_storage_in(&dir);
+        compressed_stack.extend(0..500);
+        // Read the chunks written so far, then more are appended past the end of the mapping
+        assert!((0..500).eq(compressed_stack.iter()));
+        compressed_stack.extend(500..1050);
+        assert_eq!(compressed_stack.resident_bytes(), 0);
+        assert!(compressed_stack.compressed_storage.iter().all(|chunk| matches!(chunk, Chunk::Spilled(_))));
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                let clone = compressed_stack.clone();
+                s.spawn(move || assert!((0..1050).eq(clone.iter())));
+            }
+        });
+        assert_eq!(compressed_stack.get_ref(1000), Some(&1000));
+        drop(compressed_stack);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+    #[test]
+    fn chunk_store_test() {
+        use std::sync::Arc;
+        let store = Arc::new(VecChunkStore::new());
+        let mut compressed_stack: CVec<u64, 100> = (0..550).collect();
+        compressed_stack.set_chunk_store(0, store.clone());
+        assert_eq!(store.len(), 5);
+        assert_eq!(compressed_stack.resident_bytes(), 0);
+        compressed_stack.extend(550..1050);
+        assert_eq!(store.len(), 10);
+        assert!((0..1050).eq(compressed_stack.iter()));
+        assert_eq!(compressed_stack.get_ref(150), Some(&150));
+        // Opening a chunk brings it back, which drops it from the store
+        *compressed_stack.get_mut(150).unwrap() = 0;
+        assert_eq!(store.len(), 9);
+        let cloned = compressed_stack.clone();
+        compressed_stack.truncate(500);
+        assert_eq!(store.len(), 9);
+        drop(cloned);
+        compressed_stack.flush();
+        assert_eq!(store.len(), 4);
+        assert_eq!(compressed_stack.pop(), Some(499));
+        assert_eq!(store.len(), 3);
+        drop(compressed_stack);
+        assert!(store.is_empty());
+
+        // Chunks stay in memory if the store fails
+        struct Failing;
+        impl ChunkStore for Failing {
+            fn put_chunk(&self, _data: &[u8]) -> std::io::Result<u64> {
+                Err(std::io::ErrorKind::Other.into())
+            }
+            fn get_chunk(&self, _key: u64) -> std::io::Result<Box<[u8]>> {
+                unreachable!()
+            }
+            fn drop_chunk(&self, _key: u64) {}
+        }
+        let mut compressed_stack: CVec<u64, 100> = (0..1050).collect();
+        compressed_stack.set_chunk_store(0, Arc::new(Failing));
+        assert_eq!(compressed_stack.spilled_bytes(), 0);
+        assert!((0..1050).eq(compressed_stack.iter()));
+
+        // Chunks which cannot be read back are an error when their bytes are read out
+        struct Forgetful;
+        impl ChunkStore for Forgetful {
+            fn put_chunk(&self, _data: &[u8]) -> std::io::Result<u64> {
+                Ok(0)
+            }
+            fn get_chunk(&self, _key: u64) -> std::io::Result<Box<[u8]>> {
+                Err(std::io::ErrorKind::NotFound.into())
+            }
+            fn drop_chunk(&self, _key: u64) {}
+        }
+        let mut compressed_stack: CVec<u64, 100> = (0..1050).collect();
+        compressed_stack.set_chunk_store(0, Arc::new(Forgetful));
+        let err = compressed_stack.try_compressed_chunks().find_map(Result::err).unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(compressed_stack.try_compressed_chunks().count(), 10);
+    }
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn arrow_test() {
+        use arrow_array::types::{Int32Type, Int64Type};
+        use arrow_array::Int64Array;
+
+        let compressed_stack: CVec<i64, 100> = (0..1050).collect();
+        let array = compressed_stack.to_arrow::<Int64Type>();
+        assert_eq!(array.len(), 1050);
+        assert_eq!(array.value(1049), 1049);
+        assert_eq!(CVec::<i64, 100>::from_arrow(&array).unwrap(), compressed_stack);
+        assert!(CVec::<i64, 100>::from_arrow(&Int64Array::from(vec![Some(1), None])).is_err());
+
+        let stream = compressed_stack.write_arrow_ipc::<Int64Type, _>(Vec::new(), "values").unwrap();
+        let read_back: CVec<i64, 100> = CVec::read_arrow_ipc::<Int64Type, _>(stream.as_slice()).unwrap();
+        assert_eq!(read_back, compressed_stack);
+        assert!(CVec::<i32, 100>::read_arrow_ipc::<Int32Type, _>(stream.as_slice()).is_err());
+        let empty = CVec::<i64, 100>::default().write_arrow_ipc::<Int64Type, _>(Vec::new(), "values").unwrap();
+        assert!(CVec::<i64, 100>::read_arrow_ipc::<Int64Type, _>(empty.as_slice()).unwrap().is_empty());
+    }
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn encryption_test() {
+        use std::sync::Arc;
+
+        let path = std::env::temp_dir().join(format!("compressed_collections-encryption_test-{}.cvec", std::process::id()));
+        let (key, other) = (EncryptionKey::new([7; 32]), EncryptionKey::new([8; 32]));
+        let compressed_stack: CVec<u64, 100> = (0..1050).collect();
+        compressed_stack.save_encrypted(&path, &key).unwrap();
+        let loaded: CVec<u64, 100> = CVec::load_encrypted(&path, &key).unwrap();
+        assert_eq!(loaded.len(), 1050);
+        assert!(loaded.iter().eq(compressed_stack.iter()));
+        assert!(CVec::<u64, 100>::load_encrypted(&path, &other).is_err());
+        assert!(CVec::<u64, 100>::load(&path).is_err());
+        assert!(CVec::<u64, 100>::load_lazy(&path).is_err());
+        CVec::<u64, 100>::rotate_key(&path, &key, &other).unwrap();
+        assert!(CVec::<u64, 100>::load_encrypted(&path, &key).is_err());
+        assert!(CVec::<u64, 100>::rotate_key(&path, &key, &other).is_err());
+        assert!(CVec::<u64, 100>::load_encrypted(&path, &other).unwrap().iter().eq(compressed_stack.iter()));
+        // Tampering is caught by the tag even when the checksum is fixed up
+        let mut bytes = std::fs::read(&path).unwrap();
+        let entry = bytes.len() - 32 - 11 * 21;
+        let len = u64::from_le_bytes(bytes[entry + 8..entry + 16].try_into().unwrap()) as usize;
+        bytes[40] ^= 1;
+        let checksum = crate::format::crc32(&bytes[8..8 + len]);
+        bytes[entry + 17..entry + 21].copy_from_slice(&checksum.to_le_bytes());
+        std::fs::write(&path, bytes).unwrap();
+        assert!(CVec::<u64, 100>::load_encrypted(&path, &other).is_err());
+        compressed_stack.save(&path).unwrap();
+        assert!(CVec::<u64, 100>::load_encrypted(&path, &key).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let store = Arc::new(VecChunkStore::new());
+        let mut spilled: CVec<u64, 100> = CVec::default();
+        assert!(spilled.set_spill_key(Some(key.clone())).is_err());
+        spilled.set_chunk_store(0, store.clone());
+        spilled.set_spill_key(Some(key)).unwrap();
+        spilled.extend(0..1050);
+        assert_eq!(spilled.resident_bytes(), 0);
+        let sealed = spilled.spilled_bytes();
+        assert!(spilled.iter().eq(0..1050));
+        spilled.set_spill_key(Some(other)).unwrap();
+        assert_eq!(store.len(), 10);
+        assert!(spilled.iter().eq(0..1050));
+        spilled.set_spill_budget(0);
+        spilled.set_spill_key(None).unwrap();
+        assert!(store.is_empty());
+        assert!(spilled.spilled_bytes() < sealed);
+        assert!(spilled.iter().eq(0..1050));
+    }
+    #[test]
+    fn spilled_cache_test() {
+        // Another thread releasing the pooled caches evicts the line after `is_cached` could be checked
+        let mut pooled: CVecPooled<u32, 1024> = CVecPooled::new();
+        pooled.set_spill_budget(0);
+        pooled.extend(0..4096);
+        assert_eq!(pooled.get_ref(10), Some(&10));
+        assert_eq!(pooled.get_ref(2000), Some(&2000));
+        std::thread::spawn(release_caches).join().unwrap();
+        assert_eq!(pooled.get_ref(10), Some(&10));
+
+        // Threads reading other chunks replace the single line of the shared cache
+        let mut shared: CVecSync<u32, 64> = CVecSync::new();
+        shared.set_spill_budget(0);
+        shared.extend(0..(4 * 64 + 1));
+        let shared = std::sync::Arc::new(shared);
+        let readers: Vec<_> = (0..4).map(|chunk| {
+            let shared = shared.clone();
+            std::thread::spawn(move || (0..1000).all(|i| shared.get(chunk * 64 + i % 64) == Some((chunk * 64 + i % 64) as u32)))
+        }).collect();
+        assert!(readers.into_iter().all(|reader| reader.join().unwrap()));
+    }
+    #[test]
+    fn panic_safety_test() {
+        #[derive(Clone, Debug, PartialEq, Deserialize)]
+        struct Bomb(u32);
+        impl Serialize for Bomb {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                assert_ne!(self.0, 13, "boom");
+                self.0.serialize(serializer)
+            }
+        }
+        let mut compressed_stack: CVec<Bomb, 10> = (0..19).map(Bomb).collect();
+        let pushed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| compressed_stack.push(Bomb(19))));
+        assert!(pushed.is_err());
+        assert!(compressed_stack.is_consistent());
+        assert_eq!(compressed_stack.len(), 19);
+        assert_eq!(compressed_stack.pop(), Some(Bomb(18)));
+        compressed_stack.push(Bomb(18));
+        compressed_stack.set_raw_window(1);
+        let pushed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            compressed_stack.push(Bomb(19));
+            compressed_stack.extend((20..30).map(Bomb));
+        }));
+        assert!(pushed.is_err());
+        assert!(compressed_stack.is_consistent());
+        assert_eq!(compressed_stack.len(), 29);
+        assert!(compressed_stack.iter().map(|bomb| bomb.0).eq(0..29));
+
+        let mut compressed_stack: CVec<u64, 100> = (0..200).collect();
+        if let Chunk::Compressed(data) = &mut compressed_stack.compressed_storage[1] {
+            let last = data.len() - 1;
+            data[last] ^= 0xFF;
+        }
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| compressed_stack.pop())).is_err());
+        assert!(compressed_stack.is_consistent());
+        assert_eq!(compressed_stack.len(), 200);
+        assert!(matches!(compressed_stack.compressed_storage[1], Chunk::Compressed(_)));
+        compressed_stack.chunk_ends[1] = 300;
+        assert!(!compressed_stack.is_consistent());
+    }
+    #[test]
+    fn deserialize_validation_test() {
+        fn reserialize(compressed_stack: &CVec<u64, 100>) -> Result<CVec<u64, 100>, postcard::Error> {
+            postcard::from_bytes(&postcard::to_stdvec(compressed_stack).unwrap())
+        }
+        let compressed_stack: CVec<u64, 100> = (0..250).collect();
+        assert_eq!(reserialize(&compressed_stack).unwrap(), compressed_stack);
+
+        // The chunk ends are rebuilt from the chunks, which must hold between 1 and a chunk size of elements
+        let mut oversized = compressed_stack.clone();
+        oversized.compressed_storage[1] = Chunk::Compressed(compress(&(100..250).collect::<Vec<u64>>(), 0));
+        assert!(reserialize(&oversized).is_err());
+        let mut empty = compressed_stack.clone();
+        empty.compressed_storage.push(Chunk::Compressed(compress(&Vec::<u64>::new(), 0)));
+        assert!(reserialize(&empty).is_err());
+        let mut short = compressed_stack.clone();
+        short.compressed_storage[0] = Chunk::Compressed(compress(&(0..50).collect::<Vec<u64>>(), 0));
+        assert!(reserialize(&short).unwrap().iter().eq((0..50).chain(100..250)));
+        let mut long_buffer = compressed_stack.clone();
+        long_buffer.uncompressed_buffer.extend(250..300);
+        assert!(reserialize(&long_buffer).is_err());
+        let mut corrupt = compressed_stack.clone();
+        if let Chunk::Compressed(data) = &mut corrupt.compressed_storage[1] {
+            let last = data.len() - 1;
+            data[last] ^= 0xFF;
+        }
+        assert!(reserialize(&corrupt).is_err());
+    }
+    #[test]
+    fn store_test() {
+        // Short chunks are stored as they are, behind a 3 byte header and a 1 byte trailer
+        let bytes: Vec<u8> = (0..STORE_THRESHOLD).map(|i| (i * 7) as u8).collect();
+        for len in 0..STORE_THRESHOLD {
+            let stored = compress_bytes(&bytes[..len], 11);
+            assert_eq!(stored.len(), len + if len == 0 { 1 } else { 4 });
+            assert_eq!(decompress_bytes(&stored), &bytes[..len]);
+        }
+        assert!(compress_bytes(&vec![0; STORE_THRESHOLD], 11).len() < 16);
+
+        let mut compressed_stack: CVec<u32, 1024, 11> = (0..1100).collect();
+        compressed_stack.shrink_to_fit();
+        let Chunk::Compressed(tail) = &compressed_stack.compressed_storage[1] else { panic!() };
+        assert_eq!(tail.len(), postcard::to_stdvec(&(1024..1100).collect::<Vec<u32>>()).unwrap().len() + 4);
+        assert!(compressed_stack.iter().eq(0..1100));
+    }
+    #[test]
+    fn decompress_reuse_test() {
+        let mut values: Vec<u32> = Vec::with_capacity(200);
+        let allocation = values.as_ptr();
+        decompress_into(&compress(&(0..100).collect::<Vec<u32>>(), 0), &mut values);
+        assert!(values.iter().copied().eq(0..100));
+        assert_eq!(values.as_ptr(), allocation);
+
+        let mut compressed_stack: CVec<u32, 100> = (0..300).collect();
+        let allocation = compressed_stack.uncompressed().as_ptr();
+        assert_eq!(compressed_stack.pop(), Some(299));
+        assert_eq!(compressed_stack.uncompressed().as_ptr(), allocation);
+        let mut iter = compressed_stack.iter();
+        assert!(iter.by_ref().take(150).eq(0..150));
+        assert!(iter.by_ref().rev().take(120).eq((179..299).rev()));
+        assert!(iter.eq(150..179));
+        // Changing the cached chunk keeps the cache line's allocation for the next chunk read
+        let allocation: *const u32 = compressed_stack.get_ref(150).unwrap();
+        *compressed_stack.get_mut(150).unwrap() = 7;
+        compressed_stack.flush();
+        assert!(!compressed_stack.cache.is_cached(1));
+        assert_eq!(compressed_stack.cache.cached_bytes(), 100 * std::mem::size_of::<u32>());
+        assert_eq!(compressed_stack.get_ref(50).unwrap() as *const u32, allocation);
+        assert_eq!(compressed_stack.get_ref(150), Some(&7));
+
+        // Decompressing the outer chunks decompresses the inner ones while the scratch buffer is in use
+        let nested: CVec<CVec<u32, 10>, 4> = (0..10).map(|i| (0..(i * 5)).collect()).collect();
+        let deserialized: CVec<CVec<u32, 10>, 4> = postcard::from_bytes(&postcard::to_stdvec(&nested).unwrap()).unwrap();
+        assert!(deserialized.iter().map(|inner| inner.len()).eq((0..10).map(|i| i * 5)));
+    }
+    #[test]
+    fn get_uncached_test() {
+        thread_local! {
+            static DESERIALIZED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+        }
+        #[derive(Clone, Debug, PartialEq, Serialize)]
+        struct Counted(u64);
+        impl<'de> Deserialize<'de> for Counted {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                DESERIALIZED.with(|count| count.set(count.get() + 1));
+                u64::deserialize(deserializer).map(Counted)
+            }
+        }
+        let compressed_stack: CVec<Counted, 100> = (0..250).map(Counted).collect();
+        assert_eq!(compressed_stack.get_uncached(110).unwrap().left(), Some(Counted(110)));
+        assert_eq!(DESERIALIZED.with(|count| count.get()), 11);
+        assert_eq!(compressed_stack.get_uncached(199).unwrap().left(), Some(Counted(199)));
+        assert_eq!(compressed_stack.get_uncached(0).unwrap().left(), Some(Counted(0)));
+        assert_eq!(compressed_stack.get_uncached(249).unwrap().right(), Some(&Counted(249)));
+        assert!(compressed_stack.get_uncached(250).is_none());
+
+        let deque: Deque<u64, 100> = (0..1000).collect();
+        assert!(deque.get_uncached(450).unwrap().is_left());
+        assert!((0..1000).step_by(7).all(|i| deque.get_uncached(i).unwrap().either(|value| value, |value| *value) == i as u64));
+    }
+    #[test]
+    fn indexed_test() {
+        thread_local! {
+            static DESERIALIZED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+        }
+        #[derive(Clone, Debug, PartialEq, Serialize)]
+        struct Counted(String);
+        impl<'de> Deserialize<'de> for Counted {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                DESERIALIZED.with(|count| count.set(count.get() + 1));
+                String::deserialize(deserializer).map(Counted)
+            }
+        }
+        let expected: Vec<Counted> = (0..1050).map(|i| Counted("x".repeat(i % 37) + &i.to_string())).collect();
+        let mut compressed_stack: CVecIndexed<Counted, 100> = CVecIndexed::new();
+        compressed_stack.extend(expected.iter().cloned());
+        assert_eq!(compressed_stack.get_ref(537), Some(&expected[537]));
+        DESERIALIZED.with(|count| count.set(0));
+        assert_eq!(compressed_stack.get_ref(500), Some(&expected[500]));
+        assert_eq!(compressed_stack.get_ref(537), Some(&expected[537]));
+        assert_eq!(compressed_stack.get_ref(599), Some(&expected[599]));
+        // Elements 500, 532 to 537 and 596 to 599
+        assert_eq!(DESERIALIZED.with(|count| count.get()), 1 + 6 + 4);
+        assert!(compressed_stack.memory_bytes() > 0);
+
+        let mut expected = expected;
+        compressed_stack.set(537, Counted("changed".to_string()));
+        expected[537] = Counted("changed".to_string());
+        compressed_stack.insert(510, Counted("inserted".to_string()));
+        expected.insert(510, Counted("inserted".to_string()));
+        compressed_stack.flush();
+        for i in (0..expected.len()).step_by(7) {
+            assert_eq!(compressed_stack.get_ref(i), Some(&expected[i]));
+        }
+        compressed_stack.truncate(520);
+        compressed_stack.extend(expected[520..].iter().rev().cloned());
+        for (i, value) in expected[..520].iter().chain(expected[520..].iter().rev()).enumerate() {
+            assert_eq!(compressed_stack.get_ref(i), Some(value));
+        }
+    }
+    #[test]
+    fn freeze_test() {
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        compressed_stack.set_raw_window(2);
+        *compressed_stack.get_mut(150).unwrap() = 0;
+        let mut expected: Vec<u32> = (0..1050).collect();
+        expected[150] = 0;
+        let frozen = compressed_stack.freeze();
+        assert_eq!(frozen.len(), 1050);
+        assert_eq!(frozen.iter().len(), 1050);
+        assert!(frozen.iter().eq(expected.iter().copied()));
+        let other: FrozenCVec<u32> = CVec::<u32, 100>::from_iter(0..10).freeze();
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let (frozen, other, expected) = (frozen.clone(), &other, &expected);
+                s.spawn(move || {
+                    for idx in (t..1051).step_by(3) {
+                        assert_eq!(frozen.get(idx), expected.get(idx).copied());
+                        // Reading another frozen collection in between does not mix up their cached chunks
+                        assert_eq!(other.get(idx % 10), Some((idx % 10) as u32));
+                    }
+                });
+            }
+        });
+        fn assert_send_sync<S: Send + Sync>() {}
+        assert_send_sync::<FrozenCVec<std::rc::Rc<u32>>>();
+        assert!(CVec::<u32, 100>::default().freeze().is_empty());
+    }
+    #[test]
+    fn rollback_test() {
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        let outer = compressed_stack.checkpoint();
+        compressed_stack.extend(1050..1120);
+        let inner = compressed_stack.checkpoint();
+        compressed_stack.extend(1120..3000);
+        compressed_stack.rollback(inner);
+        assert!((0..1120).eq(compressed_stack.iter()));
+        compressed_stack.extend(5000..5500);
+        compressed_stack.rollback(outer);
+        assert!((0..1050).eq(compressed_stack.iter()));
+        compressed_stack.rollback(outer);
+        assert_eq!(compressed_stack.len(), 1050);
+    }
+    #[test]
+    fn pop_n_test() {
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        assert_eq!(compressed_stack.pop_n(0), Vec::<u32>::new());
+        assert_eq!(compressed_stack.pop_n(20), (1030..1050).collect::<Vec<_>>());
+        assert_eq!(compressed_stack.pop_n(275), (755..1030).collect::<Vec<_>>());
+        assert_eq!(compressed_stack.len(), 755);
+        compressed_stack.push(755);
+        assert!((0..756).eq(compressed_stack.iter()));
+        assert_eq!(compressed_stack.pop_n(2000), (0..756).collect::<Vec<_>>());
+        assert!(compressed_stack.is_empty());
+    }
+    #[test]
+    fn nth_test() {
+        let big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        *compressed_stack.get_mut(420).unwrap() = 420;
+        for skip in [0, 1, 99, 100, 250, 1000, 1049, 1050, 2000] {
+            assert!(big_vec.iter().copied().skip(skip).eq(compressed_stack.iter().skip(skip)));
+            assert!(big_vec.iter().copied().skip(skip).eq(compressed_stack.clone().into_iter().skip(skip)));
+        }
+        let mut big_iter = big_vec.into_iter();
+        let mut iter = compressed_stack.iter();
+        let mut into_iter = compressed_stack.clone().into_iter();
+        for (n, back) in [(3, false), (150, true), (0, true), (420, false), (90, true), (7, false), (500, false)] {
+            let expected = if back { big_iter.next_back() } else { big_iter.nth(n) };
+            assert_eq!(if back { iter.next_back() } else { iter.nth(n) }, expected);
+            assert_eq!(if back { into_iter.next_back() } else { into_iter.nth(n) }, expected);
+            assert_eq!(iter.len(), big_iter.len());
+            assert_eq!(into_iter.len(), big_iter.len());
+        }
+    }
+    #[test]
+    fn map_chunks_test() {
+        let compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        let maxes: Vec<u32> = compressed_stack.map_chunks(|chunk| *chunk.iter().max().unwrap()).collect();
+        assert_eq!(maxes, (1..=10).map(|x| x * 100 - 1).chain([1049]).collect::<Vec<_>>());
+        let count = compressed_stack.fold_chunks(0, |count, chunk| count + chunk.iter().filter(|x| *x % 3 == 0).count());
+        assert_eq!(count, 350);
+    }
+    #[test]
+    fn dyn_chunk_size_test() {
+        let mut compressed_stack: CVecDyn<u32> = CVecDyn::with_chunk_size(ChunkSize::SizeElements(100));
+        compressed_stack.extend(0..1050);
+        compressed_stack.insert(10, 10);
+        assert_eq!(compressed_stack.chunk_elems(), 100);
+        assert_eq!(compressed_stack.uncompressed().len(), 50);
+        assert_eq!(compressed_stack.compressed_chunks().count(), 11);
+        let big_vec: Vec<u32> = (0..1050).collect();
+        let mut clone = compressed_stack.clone();
+        assert_eq!(clone.remove(10), 10);
+        assert!(big_vec.iter().copied().eq(clone));
+
+        let serialized = postcard::to_stdvec(&compressed_stack).unwrap();
+        let deserialized: CVecDyn<u32> = postcard::from_bytes(&serialized).unwrap();
+        assert_eq!(deserialized.chunk_elems(), ChunkSize::Default.elems::<u32>());
+        assert_eq!(deserialized, compressed_stack);
+        assert_eq!(CVec::<u32, 100>::default().chunk_elems(), 100);
+    }
+    #[test]
+    fn byte_chunk_size_test() {
+        // 170 `String`s fit in 4096 bytes, but only 41 of these serialize to less
+        let long = |i: usize| format!("{i:099}");
+        let mut compressed_stack: CVecDyn<String> = CVecDyn::with_chunk_size(ChunkSize::SizeBytes(4096));
+        assert_eq!(compressed_stack.chunk_elems(), 170);
+        compressed_stack.extend((0..100).map(long));
+        assert_eq!(compressed_stack.compressed_chunks().count(), 2);
+        assert_eq!(compressed_stack.uncompressed().len(), 18);
+        assert_eq!(compressed_stack.pop(), Some(long(99)));
+        assert_eq!(compressed_stack.pop(), Some(long(98)));
+        compressed_stack.extend((98..123).map(long));
+        assert_eq!(compressed_stack.compressed_chunks().count(), 3);
+        assert_eq!(compressed_stack.uncompressed().len(), 0);
+        assert!(compressed_stack.iter().eq((0..123).map(long)));
+
+        let mut sliced: CVecDyn<String> = CVecDyn::with_chunk_size(ChunkSize::SizeBytes(4096));
+        sliced.extend_from_slice(&(0..123).map(long).collect::<Vec<_>>());
+        assert_eq!(sliced, compressed_stack);
+        // Collections split off keep the chunk size in bytes
+        let mut tail = sliced.split_off(41);
+        tail.extend((123..170).map(long));
+        assert_eq!(tail.compressed_chunks().count(), 3);
+        assert_eq!(tail.uncompressed().len(), 6);
+        let mut short: CVecDyn<String> = CVecDyn::with_chunk_size(ChunkSize::SizeBytes(4096));
+        short.resize(1000, String::from("a"));
+        assert_eq!(short.compressed_chunks().count(), 5);
+        assert!(short.is_consistent());
+        // Inserting into the buffer flushes it at the same size as pushing, counting the inserted element
+        let mut inserted: CVecDyn<String> = CVecDyn::with_chunk_size(ChunkSize::SizeBytes(4096));
+        for i in 0..123 {
+            inserted.insert(inserted.len() - inserted.uncompressed().len() / 2, long(i));
+        }
+        assert_eq!(inserted.chunk_ends, compressed_stack.chunk_ends);
+        assert_eq!(inserted.uncompressed().len(), 0);
+        assert!(inserted.is_consistent());
+    }
+    #[test]
+    fn peek_test() {
+        let mut compressed_stack: CVec<u32, 100> = (0..1000).collect();
+        assert_eq!(compressed_stack.peek(), Some(&999));
+        compressed_stack.push(1000);
+        assert_eq!(compressed_stack.peek(), Some(&1000));
+        while let Some(&x) = compressed_stack.peek() {
+            assert_eq!(compressed_stack.pop(), Some(x));
+        }
+        assert!(compressed_stack.is_empty());
+        let mut compressed_stack: CVecUncached<u32, 100> = (0..1000).collect();
+        assert_eq!(compressed_stack.peek(), Some(&999));
+        assert!(matches!(compressed_stack.compressed_storage.last(), Some(Chunk::Raw(_))));
+        assert_eq!(compressed_stack.pop_chunk(), Some((900..1000).collect()));
+        assert_eq!(compressed_stack.peek(), Some(&899));
+    }
+    #[test]
+    fn get_many_test() {
+        let big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        *compressed_stack.get_mut(150).unwrap() = 150;
+        let indices = [1049, 3, 150, 3, 999, 0, 1000, 151, 42];
+        let expected: Vec<u32> = indices.iter().map(|&idx| big_vec[idx]).collect();
+        assert_eq!(compressed_stack.get_many(&indices), Some(expected));
+        assert_eq!(compressed_stack.get_many(&[]), Some(vec![]));
+        assert_eq!(compressed_stack.get_many(&[1, 1050]), None);
+    }
+    #[test]
+    fn resize_test() {
+        let mut big_vec: Vec<u32> = (0..1050).collect();
+        let mut compressed_stack: CVec<u32, 100> = (0..1050).collect();
+        for (len, value) in [(1060, 1), (1030, 2), (1500, 3), (1507, 4), (950, 5), (0, 6), (420, 7)] {
+            big_vec.resize(len, value);
+            compressed_stack.resize(len, value);
+            assert_eq!(compressed_stack.len(), len);
+        }
+        assert!(big_vec.into_iter().eq(compressed_stack.clone()));
+        compressed_stack.truncate(1000);
+        compressed_stack.truncate(150);
+        assert_eq!(compressed_stack.len(), 150);
+        assert!(std::iter::repeat_n(7, 150).eq(compressed_stack));
+
+        let compressed_stack: CVecDyn<u32> = CVecDyn::with_chunk_size(ChunkSize::SizeElements(100));
+        let (mut left, right) = compressed_stack.partition(|_| true);
+        left.resize(250, 0);
+        assert_eq!(right.chunk_elems(), 100);
+        assert_eq!(left.split_off(10).compressed_chunks().count(), 2);
+    }
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn index_out_of_bounds_test() {
+        let compressed_stack: CVecMemo<u32, 100> = (0..1050).collect();
+        let _ = compressed_stack[1050];
+    }
+
+    #[test]
+    fn flatten_test() {
+        let nested = || (0..30).map(|j| (j * 100..j * 100 + j * 7).collect::<CVec<u32, 50>>());
+        let big_vec: Vec<u32> = (0..30).flat_map(|j| j * 100..j * 100 + j * 7).collect();
+        let compressed_nested: CVec<CVec<u32, 50>, 10> = nested().collect();
+        assert!(big_vec.iter().copied().eq(compressed_nested.flattened()));
+
+        let compressed_nested: CVec<CVec<u32, 50>, 10> = nested().collect();
+        let mut compressed_stack: CVec<u32, 100> = CVec::default();
+        compressed_nested.flatten_into(&mut compressed_stack);
+        assert!(big_vec.into_iter().eq(compressed_stack));
+    }
     #[test]
     fn rec_test() {
         let mut compressed_vec = CVec::new::<{ 8 * 10 - 1 }, 11>();
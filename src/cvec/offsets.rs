@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::compression::{take_element, with_decompressed};
+use super::cache::{Cache, CacheAccess, ChunkBytes};
+
+/// The number of elements between two offsets in the index of a chunk, see [`Indexed`]
+pub(super) const OFFSET_GROUP: usize = 16;
+
+/// Keeps, for every chunk read, where every [`OFFSET_GROUP`]th element starts in its decompressed bytes, so that
+/// reading an element only deserializes it and the (at most 15) elements before it in its group.
+///
+/// Chunks are still decompressed on every read, but their elements are not all deserialized: worth it for elements
+/// which are large or costly to deserialize, e.g. strings or vectors. The index of a chunk is built the first time
+/// it is read, by deserializing it once, and dropped when the chunk changes.
+pub struct Indexed<T> {
+    /// For each chunk read, the byte offsets of elements `0`, `OFFSET_GROUP`, `2 * OFFSET_GROUP`, ...
+    offsets: HashMap<usize, Box<[usize]>>,
+    /// The element read last, which `get_ref` hands out a reference to
+    last: Option<T>,
+}
+
+impl<T> Default for Indexed<T> {
+    fn default() -> Self {
+        Self { offsets: HashMap::new(), last: None }
+    }
+}
+
+// Chunks are never held decompressed, so `is_cached` stays false and their bytes are always passed in
+impl<T> Cache for Indexed<T> {
+    fn kill_all(&mut self) {
+        self.offsets.clear();
+    }
+    fn kill(&mut self, index: usize) {
+        self.offsets.remove(&index);
+    }
+    fn resize(&mut self, chunks: usize) {
+        self.offsets.retain(|&index, _| index < chunks);
+    }
+    fn cached_bytes(&self) -> usize {
+        let offsets: usize = self.offsets.values().map(|offsets| std::mem::size_of_val(&**offsets)).sum();
+        offsets + self.last.as_ref().map_or(0, std::mem::size_of_val)
+    }
+}
+
+impl<T> CacheAccess<T> for Indexed<T>
+where for<'a> T: Deserialize<'a>
+{
+    fn get_compressed(&mut self, index: usize, offset: usize, data: &ChunkBytes<'_>) -> &T {
+        let value = with_decompressed(data, |bytes| {
+            let offsets = self.offsets.entry(index).or_insert_with(|| element_offsets::<T>(bytes));
+            let mut rest = &bytes[offsets[offset / OFFSET_GROUP]..];
+            for _ in 0..offset % OFFSET_GROUP {
+                rest = take_element::<T>(rest).1;
+            }
+            take_element::<T>(rest).0
+        });
+        self.last.insert(value.unwrap()) // Only errors if the bytes were damaged after being compressed
+    }
+}
+
+/// The offsets of every [`OFFSET_GROUP`]th element in the decompressed bytes of a chunk, found by deserializing
+/// each of its elements.
+fn element_offsets<T>(bytes: &[u8]) -> Box<[usize]>
+where for<'a> T: Deserialize<'a>
+{
+    // A chunk is a sequence: its length, then its elements one after the other
+    let (len, mut rest) = take_element::<usize>(bytes);
+    (0..len)
+        .filter_map(|idx| {
+            let offset = bytes.len() - rest.len();
+            rest = take_element::<T>(rest).1;
+            (idx % OFFSET_GROUP == 0).then_some(offset)
+        })
+        .collect()
+}
@@ -0,0 +1,83 @@
+use either::Either;
+use rayon::iter::plumbing::UnindexedConsumer;
+use rayon::prelude::*;
+use serde::Deserialize;
+
+use super::cache::{Cache, Cached};
+use super::inner::Chunk;
+use super::iterator::CVecChunk;
+use super::CVecInner;
+
+/// Parallel iterator over clones of the elements of a CVec, returned by `CVec::par_iter`
+///
+/// Work is split at chunk granularity, each worker decompresses the chunks it is handed itself. Elements are
+/// yielded bottom to top, like [`CVec::iter`](crate::CVec::iter), so e.g. `collect` keeps them in order.
+pub struct CVecParIter<'i, T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0, C: Cache = Cached<T, CHUNK_ELEMS>> {
+    inner: &'i CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>,
+}
+
+impl<'i, T, C: Cache + Sync, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> ParallelIterator for CVecParIter<'i, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Clone + Send + Sync + for<'a> Deserialize<'a>,
+{
+    type Item = T;
+
+    fn drive_unindexed<Co: UnindexedConsumer<T>>(self, consumer: Co) -> Co::Result {
+        let inner = self.inner;
+        (0..inner.chunk_count()).into_par_iter()
+            .flat_map_iter(|chunk_idx| match inner.chunk(chunk_idx).unwrap() {
+                CVecChunk::Decompressed(values) => Either::Left(values.into_iter()),
+                CVecChunk::Borrowed(values) => Either::Right(values.iter().cloned()),
+            })
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<'i, T, C: Cache + Sync, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> IntoParallelIterator for &'i CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Clone + Send + Sync + for<'a> Deserialize<'a>,
+{
+    type Item = T;
+    type Iter = CVecParIter<'i, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        CVecParIter { inner: self }
+    }
+}
+
+/// Owning parallel iterator over a CVec, yields elements bottom to top
+///
+/// Each worker decompresses the chunks it is handed, which are freed as they are decompressed.
+pub struct CVecIntoParIter<T> {
+    chunks: Vec<Chunk<T>>,
+    buffer: Vec<T>,
+}
+
+impl<T> ParallelIterator for CVecIntoParIter<T>
+where
+    T: Send + for<'a> Deserialize<'a>,
+{
+    type Item = T;
+
+    fn drive_unindexed<Co: UnindexedConsumer<T>>(self, consumer: Co) -> Co::Result {
+        self.chunks.into_par_iter()
+            .flat_map_iter(Chunk::into_values)
+            .chain(self.buffer)
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> IntoParallelIterator for CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Send + for<'a> Deserialize<'a>,
+{
+    type Item = T;
+    type Iter = CVecIntoParIter<T>;
+
+    fn into_par_iter(mut self) -> Self::Iter {
+        CVecIntoParIter {
+            chunks: std::mem::take(&mut self.compressed_storage),
+            buffer: std::mem::take(&mut self.uncompressed_buffer),
+        }
+    }
+}
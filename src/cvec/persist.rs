@@ -0,0 +1,311 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::stream::{invalid_data, read_exact_bytes, read_len, write_len};
+use super::spill::{SpillFile, SpilledChunk};
+use crate::format::{crc32, CODEC_BROTLI, CODEC_SEALED, MAGIC, VERSION};
+use super::{cache::Cache, inner::{CVec as CVecInner, Chunk}, ChunkSize};
+
+/// The byte length of the header: the magic bytes and the version
+pub(super) const HEADER_LEN: u64 = 8;
+/// The byte length of an entry of the chunk table
+const ENTRY_LEN: u64 = 21;
+/// The byte length of the footer
+const FOOTER_LEN: u64 = 32;
+
+/// An entry of the chunk table
+pub(super) struct ChunkEntry {
+    pub(super) len: usize,
+    pub(super) codec: u8,
+    pub(super) checksum: u32,
+}
+
+/// What the chunk table and footer say about the tail
+pub(super) struct TailEntry {
+    pub(super) len: usize,
+    pub(super) checksum: u32,
+}
+
+fn header() -> [u8; HEADER_LEN as usize] {
+    let mut header = [0; HEADER_LEN as usize];
+    header[..6].copy_from_slice(&MAGIC);
+    header[6..].copy_from_slice(&VERSION.to_le_bytes());
+    header
+}
+
+/// Checks the magic bytes and that the version can be read.
+fn check_header(header: &[u8]) -> io::Result<()> {
+    if header[..6] != MAGIC {
+        return Err(invalid_data("not a compressed_collections container"));
+    }
+    let version = u16::from_le_bytes([header[6], header[7]]);
+    if version == 0 || version > VERSION {
+        return Err(invalid_data(format!("unsupported format version {version}")));
+    }
+    Ok(())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Rejects chunks which can only be read with `CVec::load_encrypted`.
+fn check_unsealed(chunk_idx: usize, entry: &ChunkEntry) -> io::Result<()> {
+    if entry.codec == CODEC_SEALED {
+        return Err(invalid_data(format!("chunk {chunk_idx} is encrypted, it can only be read with `CVec::load_encrypted`")));
+    }
+    Ok(())
+}
+
+/// Writes a container holding `chunks`, each given by its element count, codec id and bytes, and the encoded
+/// `tail`. See [`format`](crate::format).
+pub(super) fn write_parts<W, B>(writer: &mut W, chunks: impl IntoIterator<Item = io::Result<(usize, u8, B)>>, tail: &[u8]) -> io::Result<()>
+where
+    W: Write,
+    B: AsRef<[u8]>,
+{
+    writer.write_all(&header())?;
+    let mut table = Vec::new();
+    let mut count = 0;
+    for chunk in chunks {
+        let (elems, codec, data) = chunk?;
+        let data = data.as_ref();
+        writer.write_all(data)?;
+        write_len(&mut table, elems)?;
+        write_len(&mut table, data.len())?;
+        table.push(codec);
+        table.extend_from_slice(&crc32(data).to_le_bytes());
+        count += 1;
+    }
+    writer.write_all(tail)?;
+    writer.write_all(&table)?;
+    write_len(writer, count)?;
+    write_len(writer, tail.len())?;
+    writer.write_all(&crc32(tail).to_le_bytes())?;
+    writer.write_all(&crc32(&table).to_le_bytes())?;
+    writer.write_all(&header())?;
+    writer.flush()
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// Writes the collection to a file at `path`, replacing it if it exists. The file can be read back with
+    /// [`load`](Self::load) or [`load_lazy`](Self::load_lazy), its layout is described in [`format`](crate::format).
+    ///
+    /// Compressed chunks are written as-is, only a chunk which is uncompressed (open for mutation or in the raw
+    /// window) gets compressed. The file is synced to disk before this returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let path = std::env::temp_dir().join(format!("save_example-{}.cvec", std::process::id()));
+    /// let compressed_stack: CVec<u32> = (0..(16 * 1024)).collect();
+    /// compressed_stack.save(&path).unwrap();
+    /// let loaded: CVec<u32> = CVec::load(&path).unwrap();
+    /// assert_eq!(loaded, compressed_stack);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()>
+    where
+        T: Serialize,
+    {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_container(&mut writer)?;
+        writer.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()
+    }
+    /// Writes the collection as a container, see [`format`](crate::format).
+    pub(crate) fn write_container<W: Write>(&self, writer: &mut W) -> io::Result<()>
+    where
+        T: Serialize,
+    {
+        let tail = postcard::to_stdvec(&self.uncompressed_buffer).map_err(invalid_data)?;
+        let chunks = self.compressed_storage.iter().enumerate().map(|(chunk_idx, chunk)| {
+            Ok((self.chunk_ends[chunk_idx] - self.chunk_start(chunk_idx), CODEC_BROTLI, chunk.compressed_bytes(COMPRESSION_LEVEL)))
+        });
+        write_parts(writer, chunks, &tail)
+    }
+    /// Reads a collection written by [`save`](Self::save). The chunks are taken over as they are, without
+    /// decompressing or recompressing them, but their checksums are checked.
+    ///
+    /// The chunk size is not stored in the file, so a [`CVecDyn`](super::CVecDyn) gets the default chunk size, see
+    /// [`load_with_chunk_size`](Self::load_with_chunk_size) to keep the one it was saved with.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        Self::read_container(&mut BufReader::new(File::open(path)?), Self::default())
+    }
+    /// Reads a collection written by [`write_container`](Self::write_container) into `cvec`, which is empty.
+    pub(crate) fn read_container<R: Read + Seek>(reader: &mut R, cvec: Self) -> io::Result<Self>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let (mut cvec, entries, tail) = Self::read_index(reader, cvec)?;
+        reader.seek(SeekFrom::Start(HEADER_LEN))?;
+        for (chunk_idx, entry) in entries.into_iter().enumerate() {
+            check_unsealed(chunk_idx, &entry)?;
+            let chunk = read_exact_bytes(reader, entry.len)?;
+            if crc32(&chunk) != entry.checksum {
+                return Err(invalid_data(format!("chunk {chunk_idx} does not match its checksum")));
+            }
+            cvec.compressed_storage.push(Chunk::Compressed(chunk));
+        }
+        cvec.read_tail(reader, tail)?;
+        Ok(cvec)
+    }
+    /// Opens a collection written by [`save`](Self::save) without reading its chunks, only the index and the
+    /// uncompressed tail are read. Each chunk is read from the file whenever it is accessed, as if it had been
+    /// spilled (see [`set_spill_budget`](Self::set_spill_budget)), so opening is quick however large the file is
+    /// and memory use grows with what is accessed. The checksum of a chunk is checked whenever it is read.
+    ///
+    /// The file is kept open until the collection and all its clones are dropped, and must not be changed or
+    /// replaced in the meantime (e.g. by saving to the same path): reading a chunk which was not yet read then
+    /// panics, as it no longer matches its checksum.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let path = std::env::temp_dir().join(format!("load_lazy_example-{}.cvec", std::process::id()));
+    /// let compressed_stack: CVec<u32> = (0..(16 * 1024)).collect();
+    /// compressed_stack.save(&path).unwrap();
+    /// let mut opened: CVec<u32> = CVec::load_lazy(&path).unwrap();
+    /// assert_eq!(opened.resident_bytes(), 0);
+    /// assert_eq!(opened.get_ref(1000), Some(&1000));
+    /// # drop(opened);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn load_lazy(path: impl AsRef<Path>) -> io::Result<Self>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        Self::open_lazy(path.as_ref(), Self::default())
+    }
+    /// Opens a collection written by [`save`](Self::save) into `cvec`, which is empty, see
+    /// [`load_lazy`](Self::load_lazy).
+    fn open_lazy(path: &Path, cvec: Self) -> io::Result<Self>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let mut reader = BufReader::new(File::open(path)?);
+        let (mut cvec, entries, tail) = Self::read_index(&mut reader, cvec)?;
+        let file = Arc::new(SpillFile::open(path)?);
+        for (chunk_idx, entry) in entries.iter().enumerate() {
+            check_unsealed(chunk_idx, entry)?;
+        }
+        let mut offset = HEADER_LEN;
+        cvec.compressed_storage = entries.into_iter().map(|entry| {
+            let chunk = Chunk::Spilled(SpilledChunk::new(Arc::clone(&file), offset, entry.len, Some(entry.checksum)));
+            offset += entry.len as u64;
+            chunk
+        }).collect();
+        reader.seek(SeekFrom::Start(offset))?;
+        cvec.read_tail(&mut reader, tail)?;
+        Ok(cvec)
+    }
+    /// Reads and checks the header, chunk table and footer of a container. Returns `cvec`, which is empty, with the
+    /// chunk ends set, the chunk table and the tail's entry.
+    pub(super) fn read_index<R: Read + Seek>(reader: &mut R, mut cvec: Self) -> io::Result<(Self, Vec<ChunkEntry>, TailEntry)> {
+        let mut header = [0; HEADER_LEN as usize];
+        reader.rewind()?;
+        reader.read_exact(&mut header)?;
+        check_header(&header)?;
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        let footer_start = file_len.checked_sub(FOOTER_LEN).filter(|&start| start >= HEADER_LEN);
+        let footer_start = footer_start.ok_or_else(|| invalid_data("file is too short"))?;
+        reader.seek(SeekFrom::Start(footer_start))?;
+        let chunks = read_len(reader)?;
+        let tail = TailEntry { len: read_len(reader)?, checksum: read_u32(reader)? };
+        let table_checksum = read_u32(reader)?;
+        reader.read_exact(&mut header)?;
+        check_header(&header)?;
+        let table_start = (chunks as u64).checked_mul(ENTRY_LEN).and_then(|table_len| footer_start.checked_sub(table_len));
+        let table_start = table_start.filter(|&start| start >= HEADER_LEN).ok_or_else(|| invalid_data("chunk table is longer than the file"))?;
+        reader.seek(SeekFrom::Start(table_start))?;
+        let table = read_exact_bytes(reader, (footer_start - table_start) as usize)?;
+        if crc32(&table) != table_checksum {
+            return Err(invalid_data("chunk table does not match its checksum"));
+        }
+        let mut entries = Vec::with_capacity(chunks);
+        let mut data_len = tail.len as u64;
+        let mut table = table.as_ref();
+        for _ in 0..chunks {
+            let elems = read_len(&mut table)?;
+            if elems == 0 || elems > cvec.chunk_elems() {
+                return Err(invalid_data("chunk length does not match the chunk size"));
+            }
+            let len = read_len(&mut table)?;
+            let mut codec = [0];
+            table.read_exact(&mut codec)?;
+            let codec = codec[0];
+            if codec != CODEC_BROTLI && codec != CODEC_SEALED {
+                return Err(invalid_data(format!("unsupported codec {codec}")));
+            }
+            let checksum = read_u32(&mut table)?;
+            data_len = data_len.checked_add(len as u64).ok_or_else(|| invalid_data("chunk is longer than the file"))?;
+            cvec.chunk_ends.push(cvec.chunk_ends.last().copied().unwrap_or(0) + elems);
+            entries.push(ChunkEntry { len, codec, checksum });
+        }
+        if HEADER_LEN + data_len != table_start {
+            return Err(invalid_data("chunk table does not match the file length"));
+        }
+        Ok((cvec, entries, tail))
+    }
+    /// Reads the uncompressed tail, which starts at the current position of `reader`.
+    pub(super) fn read_tail<R: Read>(&mut self, reader: &mut R, tail: TailEntry) -> io::Result<()>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let bytes = read_exact_bytes(reader, tail.len)?;
+        if crc32(&bytes) != tail.checksum {
+            return Err(invalid_data("uncompressed tail does not match its checksum"));
+        }
+        self.uncompressed_buffer = postcard::from_bytes(&bytes).map_err(invalid_data)?;
+        if self.uncompressed_buffer.len() >= self.chunk_elems() {
+            return Err(invalid_data("uncompressed tail is longer than a chunk"));
+        }
+        Ok(())
+    }
+}
+
+impl<T, C: Cache, const COMPRESSION_LEVEL: i32> CVecInner<T, 0, COMPRESSION_LEVEL, C> {
+    /// Like [`load`](Self::load), but the collection gets chunks of `chunk_size` rather than the default size. It
+    /// must hold at least as many elements as the chunks the file was saved with.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::{CVecDyn, ChunkSize};
+    ///
+    /// let path = std::env::temp_dir().join(format!("load_with_chunk_size_example-{}.cvec", std::process::id()));
+    /// let mut compressed_stack: CVecDyn<u32> = CVecDyn::with_chunk_size(ChunkSize::SizeElements(16));
+    /// compressed_stack.extend(0..100);
+    /// compressed_stack.save(&path).unwrap();
+    /// let loaded: CVecDyn<u32> = CVecDyn::load_with_chunk_size(&path, ChunkSize::SizeElements(16)).unwrap();
+    /// assert_eq!(loaded.chunk_elems(), 16);
+    /// assert_eq!(loaded, compressed_stack);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn load_with_chunk_size(path: impl AsRef<Path>, chunk_size: ChunkSize) -> io::Result<Self>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        Self::read_container(&mut BufReader::new(File::open(path)?), Self::with_chunk_size(chunk_size))
+    }
+    /// Like [`load_lazy`](Self::load_lazy), but the collection gets chunks of `chunk_size`, see
+    /// [`load_with_chunk_size`](Self::load_with_chunk_size).
+    pub fn load_lazy_with_chunk_size(path: impl AsRef<Path>, chunk_size: ChunkSize) -> io::Result<Self>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        Self::open_lazy(path.as_ref(), Self::with_chunk_size(chunk_size))
+    }
+}
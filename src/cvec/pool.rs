@@ -0,0 +1,259 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::Deserialize;
+
+use crate::compression::decompress;
+use super::cache::{Cache, CacheAccess, ChunkBytes, DEFAULT_CACHE_BUDGET};
+
+/// A budget of decompressed memory shared by many collections, see [`CVecPooled`](crate::CVecPooled).
+///
+/// Once the decompressed chunks of all collections in the pool use more than the budget, the least recently read
+/// chunk of any collection is evicted. Like [`CVecBudgeted`](crate::CVecBudgeted), only `size_of::<T>()` per element
+/// is counted. Handles are cheap to clone and all refer to the same pool.
+#[derive(Clone)]
+pub struct CachePool(Rc<RefCell<PoolState>>);
+
+struct PoolState {
+    budget: usize,
+    used: usize,
+    /// Incremented on every access, to order the lines by when they were last used
+    clock: u64,
+    /// Handed out to each collection which joins the pool
+    next_id: usize,
+    /// The value of `RELEASE_EPOCH` when the lines were last dropped by [`release_caches`]
+    epoch: usize,
+    /// The lines of every collection, keyed by the collection's id and the chunk index
+    lines: HashMap<(usize, usize), PoolLine>,
+}
+
+struct PoolLine {
+    data: Rc<dyn Any>,
+    bytes: usize,
+    last_used: u64,
+}
+
+thread_local! {
+    static THREAD_POOL: CachePool = CachePool::new(DEFAULT_CACHE_BUDGET);
+    /// Every pool created on this thread, for [`release_caches`]
+    static THREAD_POOLS: RefCell<Vec<Weak<RefCell<PoolState>>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Incremented by every [`release_caches`], pools on other threads drop their lines once they see it change
+static RELEASE_EPOCH: AtomicUsize = AtomicUsize::new(0);
+
+type ReleaseHook = Box<dyn Fn() + Send + Sync>;
+static RELEASE_HOOKS: Mutex<Vec<ReleaseHook>> = Mutex::new(Vec::new());
+
+/// Drops the decompressed chunks of every [`CachePool`], e.g. when the allocator signals memory pressure, then runs
+/// the hooks registered with [`on_release_caches`].
+///
+/// The pools of the calling thread are emptied straight away, those of other threads the next time they are used.
+/// Each pooled collection keeps the one chunk it read last until it reads another. Collections with a cache of
+/// their own are not reachable from here, register a hook which calls their
+/// [`release_cache`](crate::CVec::release_cache) instead.
+pub fn release_caches() {
+    RELEASE_EPOCH.fetch_add(1, Ordering::Relaxed);
+    let pools: Vec<_> = THREAD_POOLS.with(|pools| pools.borrow().iter().filter_map(Weak::upgrade).collect());
+    for pool in pools {
+        CachePool(pool).release();
+    }
+    for hook in RELEASE_HOOKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).iter() {
+        hook();
+    }
+}
+
+/// Registers a hook which [`release_caches`] runs, from whichever thread calls it. The hook must not call
+/// `release_caches` itself.
+pub fn on_release_caches(hook: impl Fn() + Send + Sync + 'static) {
+    RELEASE_HOOKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(Box::new(hook));
+}
+
+impl CachePool {
+    /// Creates an empty pool which keeps at most `budget` bytes of chunks decompressed.
+    pub fn new(budget: usize) -> Self {
+        let epoch = RELEASE_EPOCH.load(Ordering::Relaxed);
+        let state = Rc::new(RefCell::new(PoolState { budget, used: 0, clock: 0, next_id: 0, epoch, lines: HashMap::new() }));
+        THREAD_POOLS.with(|pools| {
+            let mut pools = pools.borrow_mut();
+            pools.retain(|pool| pool.strong_count() > 0);
+            pools.push(Rc::downgrade(&state));
+        });
+        Self(state)
+    }
+    /// The pool collections join when they are created with `new`, cloned or deserialized, one per thread with a
+    /// budget of [`DEFAULT_CACHE_BUDGET`] bytes.
+    pub fn thread_default() -> Self {
+        THREAD_POOL.with(Clone::clone)
+    }
+    /// The budget in bytes
+    pub fn budget(&self) -> usize {
+        self.0.borrow().budget
+    }
+    /// Sets the budget in bytes, evicting chunks straight away if they no longer fit.
+    pub fn set_budget(&self, bytes: usize) {
+        let mut state = self.0.borrow_mut();
+        state.budget = bytes;
+        let evicted = state.evict(None);
+        drop(state);
+        drop(evicted);
+    }
+    /// The number of bytes of decompressed chunks held by the pool
+    pub fn used(&self) -> usize {
+        let mut state = self.0.borrow_mut();
+        let released = state.sync_release();
+        let used = state.used;
+        drop(state);
+        drop(released);
+        used
+    }
+    /// Drops all decompressed chunks in the pool, see also [`release_caches`].
+    pub fn release(&self) {
+        let released = std::mem::take(&mut self.0.borrow_mut().lines);
+        self.0.borrow_mut().used = 0;
+        drop(released);
+    }
+    /// Whether both handles refer to the same pool
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+    pub(super) fn join<T>(&self) -> Pooled<T> {
+        let mut state = self.0.borrow_mut();
+        let id = state.next_id;
+        state.next_id += 1;
+        Pooled { pool: self.clone(), id, current: None }
+    }
+}
+
+// Evicted lines are returned rather than dropped, so that the pool is no longer borrowed when their elements are
+// dropped, which may themselves be pooled collections.
+impl PoolState {
+    /// Drops all lines if [`release_caches`] was called since they were last dropped.
+    fn sync_release(&mut self) -> HashMap<(usize, usize), PoolLine> {
+        let epoch = RELEASE_EPOCH.load(Ordering::Relaxed);
+        if self.epoch == epoch {
+            return HashMap::new();
+        }
+        self.epoch = epoch;
+        self.used = 0;
+        std::mem::take(&mut self.lines)
+    }
+    /// Evicts the least recently used lines, other than `keep`, until the budget is met.
+    fn evict(&mut self, keep: Option<(usize, usize)>) -> Vec<Rc<dyn Any>> {
+        let mut evicted = Vec::new();
+        while self.used > self.budget {
+            let victim = self.lines.iter()
+                .filter(|(&key, _)| Some(key) != keep)
+                .min_by_key(|(_, line)| line.last_used)
+                .map(|(&key, _)| key);
+            let Some(victim) = victim else { break };
+            evicted.extend(self.remove(victim));
+        }
+        evicted
+    }
+    fn remove(&mut self, key: (usize, usize)) -> Option<Rc<dyn Any>> {
+        let line = self.lines.remove(&key)?;
+        self.used -= line.bytes;
+        Some(line.data)
+    }
+    /// Removes the lines of collection `id` whose index matches `pred`.
+    fn remove_where(&mut self, id: usize, pred: impl Fn(usize) -> bool) -> Vec<Rc<dyn Any>> {
+        let removed: Vec<_> = self.lines.keys().copied().filter(|&(line_id, index)| line_id == id && pred(index)).collect();
+        removed.into_iter().filter_map(|key| self.remove(key)).collect()
+    }
+}
+
+/// The cache of a collection in a [`CachePool`]
+pub struct Pooled<T> {
+    pool: CachePool,
+    id: usize,
+    /// The line read last, kept alive even if the pool evicts it so that references to it stay valid
+    current: Option<(usize, Rc<Box<[T]>>)>,
+}
+
+impl<T> Pooled<T> {
+    pub(super) fn pool(&self) -> &CachePool {
+        &self.pool
+    }
+    fn forget_current(&mut self, pred: impl Fn(usize) -> bool) -> Option<Rc<Box<[T]>>> {
+        match self.current {
+            Some((index, _)) if pred(index) => self.current.take().map(|(_, line)| line),
+            _ => None,
+        }
+    }
+}
+
+impl<T> Default for Pooled<T> {
+    fn default() -> Self {
+        CachePool::thread_default().join()
+    }
+}
+
+impl<T> Drop for Pooled<T> {
+    fn drop(&mut self) {
+        let removed = self.pool.0.borrow_mut().remove_where(self.id, |_| true);
+        drop(removed);
+    }
+}
+
+impl<T> Cache for Pooled<T> {
+    fn is_cached(&self, index: usize) -> bool {
+        self.pool.0.borrow().lines.contains_key(&(self.id, index))
+    }
+    fn kill_all(&mut self) {
+        let removed = self.pool.0.borrow_mut().remove_where(self.id, |_| true);
+        let current = self.forget_current(|_| true);
+        drop((removed, current));
+    }
+    fn kill(&mut self, index: usize) {
+        let removed = self.pool.0.borrow_mut().remove((self.id, index));
+        let current = self.forget_current(|i| i == index);
+        drop((removed, current));
+    }
+    fn resize(&mut self, chunks: usize) {
+        let removed = self.pool.0.borrow_mut().remove_where(self.id, |index| index >= chunks);
+        let current = self.forget_current(|index| index >= chunks);
+        drop((removed, current));
+    }
+    fn cached_bytes(&self) -> usize {
+        self.pool.0.borrow().lines.iter().filter(|((id, _), _)| *id == self.id).map(|(_, line)| line.bytes).sum()
+    }
+}
+
+impl<T: 'static> CacheAccess<T> for Pooled<T>
+where for<'a> T: Deserialize<'a>
+{
+    fn get_compressed(&mut self, index: usize, offset: usize, data: &ChunkBytes<'_>) -> &T {
+        let key = (self.id, index);
+        let mut state = self.pool.0.borrow_mut();
+        let released = state.sync_release();
+        state.clock += 1;
+        let clock = state.clock;
+        let line = match state.lines.get_mut(&key) {
+            Some(line) => {
+                line.last_used = clock;
+                Rc::clone(&line.data).downcast::<Box<[T]>>().unwrap()
+            }
+            None => {
+                let line = match &self.current {
+                    // Evicted by another collection, but still alive here
+                    Some((current, line)) if *current == index => line.clone(),
+                    _ => Rc::new(decompress::<Vec<T>>(data).into_boxed_slice()),
+                };
+                let bytes = std::mem::size_of_val::<[T]>(&line);
+                state.used += bytes;
+                state.lines.insert(key, PoolLine { data: line.clone(), bytes, last_used: clock });
+                line
+            }
+        };
+        let evicted = state.evict(Some(key));
+        drop(state);
+        let previous = self.current.replace((index, line));
+        drop((evicted, previous, released));
+        &self.current.as_ref().unwrap().1[offset]
+    }
+}
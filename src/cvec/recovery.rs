@@ -0,0 +1,149 @@
+use std::{slice, vec};
+
+use either::Either;
+use serde::{Deserialize, Serialize};
+
+use super::cache::Cache;
+use super::verify::DamagedChunk;
+use super::{CVecChunk, CVecInner};
+use crate::Error;
+
+/// What [`CVec::try_iter`](crate::CVec::try_iter) does when a chunk fails to decode, see
+/// `CVec::set_corrupt_chunk_policy`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CorruptChunkPolicy {
+    /// The error is yielded in place of the chunk's elements and iteration goes on with the next chunk
+    Skip,
+    /// The error is yielded and iteration ends
+    #[default]
+    Stop,
+    /// The iterator panics, like [`iter`](crate::CVec::iter) always does
+    Panic,
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// What [`try_iter`](Self::try_iter) does when a chunk fails to decode, see
+    /// [`set_corrupt_chunk_policy`](Self::set_corrupt_chunk_policy).
+    pub fn corrupt_chunk_policy(&self) -> CorruptChunkPolicy {
+        self.corrupt_policy
+    }
+    /// Sets what [`try_iter`](Self::try_iter) does when a chunk fails to decode (or, if it was spilled, to be read
+    /// back). The default is [`CorruptChunkPolicy::Stop`]. The policy is not serialized, and is kept by clones.
+    pub fn set_corrupt_chunk_policy(&mut self, policy: CorruptChunkPolicy) {
+        self.corrupt_policy = policy;
+    }
+    /// Returns an iterator over clones of the elements which yields an [`Error`] for each chunk that fails to
+    /// decode, rather than panicking like [`iter`](Self::iter). What comes after the error depends on the
+    /// [`corrupt_chunk_policy`](Self::corrupt_chunk_policy). Chunks are decompressed without going through the
+    /// cache.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let compressed_stack: CVec<u32> = (0..(16 * 1024)).collect();
+    /// let sum: Result<u64, _> = compressed_stack.try_iter().map(|value| value.map(u64::from)).sum();
+    /// assert_eq!(sum.unwrap(), (0..(16 * 1024)).sum());
+    /// ```
+    pub fn try_iter(&self) -> CVecTryIter<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+    where
+        T: Clone + for<'a> Deserialize<'a>,
+    {
+        CVecTryIter { chunk_idx: 0, inner: self, values: Either::Right([].iter()), done: false }
+    }
+    /// Copies everything which can still be read out of a damaged collection into a new one, returning it along
+    /// with the chunks whose elements were lost, bottom of the stack first.
+    ///
+    /// Chunks which decode to a different number of elements than expected are reported, but their elements are
+    /// kept. The new collection has no damage (so its [`verify`](Self::verify) succeeds), its settings such as the
+    /// cache, spill or memory budget are the defaults.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let compressed_stack: CVec<u32> = (0..(16 * 1024)).collect();
+    /// let (salvaged, damaged) = compressed_stack.salvage();
+    /// assert!(damaged.is_empty());
+    /// assert_eq!(salvaged, compressed_stack);
+    /// ```
+    pub fn salvage(&self) -> (Self, Vec<DamagedChunk>)
+    where
+        T: Clone + Serialize + for<'a> Deserialize<'a>,
+    {
+        let mut salvaged = Self::default();
+        let mut damaged = Vec::new();
+        for index in 0..self.compressed_storage.len() {
+            match self.read_chunk(index) {
+                Ok(values) => {
+                    if let Err(damage) = self.check_chunk_len(index, values.len()) {
+                        damaged.push(DamagedChunk { index, damage });
+                    }
+                    salvaged.extend_from_slice(&values);
+                }
+                Err(damage) => damaged.push(DamagedChunk { index, damage }),
+            }
+        }
+        salvaged.extend_from_slice(&self.uncompressed_buffer);
+        (salvaged, damaged)
+    }
+}
+
+/// Iterator over clones of the elements of a CVec which reports chunks that fail to decode, returned by
+/// `CVec::try_iter`
+pub struct CVecTryIter<'i, T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> {
+    /// The next chunk to decode
+    chunk_idx: usize,
+    inner: &'i CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>,
+    values: Either<vec::IntoIter<T>, slice::Iter<'i, T>>,
+    done: bool,
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Iterator for CVecTryIter<'_, T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Clone + for<'a> Deserialize<'a>,
+{
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let value = match &mut self.values {
+                Either::Left(values) => values.next(),
+                Either::Right(values) => values.next().cloned(),
+            };
+            if let Some(value) = value {
+                return Some(Ok(value));
+            }
+            if self.done {
+                return None;
+            }
+            let chunk_idx = self.chunk_idx;
+            self.chunk_idx += 1;
+            match self.inner.try_chunk(chunk_idx) {
+                Ok(Some(CVecChunk::Decompressed(values))) => self.values = Either::Left(values.into_iter()),
+                Ok(Some(CVecChunk::Borrowed(values))) => self.values = Either::Right(values.iter()),
+                Ok(None) => self.done = true,
+                Err(err) => match self.inner.corrupt_policy {
+                    CorruptChunkPolicy::Skip => return Some(Err(err)),
+                    CorruptChunkPolicy::Stop => {
+                        self.done = true;
+                        return Some(Err(err));
+                    }
+                    CorruptChunkPolicy::Panic => panic!("chunk {chunk_idx} could not be read: {err}"),
+                },
+            }
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let buffered = self.values.as_ref().either(|values| values.len(), |values| values.len());
+        if self.done {
+            return (buffered, Some(buffered));
+        }
+        // Each chunk left may yield an error instead of its elements
+        let chunks = self.inner.chunk_count().saturating_sub(self.chunk_idx);
+        let elems = self.inner.len().saturating_sub(self.inner.chunk_start(self.chunk_idx.min(self.inner.compressed_storage.len())));
+        (buffered, Some(buffered + elems + chunks))
+    }
+}
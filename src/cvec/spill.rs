@@ -0,0 +1,442 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::cache::Cache;
+#[cfg(feature = "encryption")]
+use super::encryption::EncryptionKey;
+use crate::format::crc32;
+use super::inner::Chunk;
+use super::store::{ChunkStore, StoredKey};
+use super::CVecInner;
+
+/// Handed out to each spill file, so that the files of different collections in a process never clash
+static NEXT_FILE: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary file which chunks are appended to, it is deleted once no chunk refers to it anymore. Files opened by
+/// `CVec::load_lazy` are read the same way, but are only read and never deleted.
+pub(super) struct SpillFile {
+    path: PathBuf,
+    file: Mutex<File>,
+    temporary: bool,
+    /// Chunks are read through a mapping of the file, which is remapped once a chunk past its end is read
+    #[cfg(feature = "mmap")]
+    map: Mutex<Option<Arc<memmap2::Mmap>>>,
+}
+
+impl SpillFile {
+    fn create(dir: &Path) -> io::Result<Self> {
+        let id = NEXT_FILE.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("compressed_collections-{}-{id}.spill", std::process::id()));
+        let file = File::options().read(true).write(true).create_new(true).open(&path)?;
+        Ok(Self::new(path, file, true))
+    }
+    /// Opens an existing file to read chunks from.
+    pub(super) fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self::new(path.to_path_buf(), file, false))
+    }
+    fn new(path: PathBuf, file: File, temporary: bool) -> Self {
+        Self {
+            path,
+            file: Mutex::new(file),
+            temporary,
+            #[cfg(feature = "mmap")]
+            map: Mutex::new(None),
+        }
+    }
+    fn append(self: &Arc<Self>, data: &[u8]) -> io::Result<SpilledChunk> {
+        let mut file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let offset = file.seek(SeekFrom::End(0))?;
+        if let Err(err) = file.write_all(data) {
+            // Leave no partial chunk behind for the next append to start after
+            let _ = file.set_len(offset);
+            return Err(err);
+        }
+        Ok(SpilledChunk::new(Arc::clone(self), offset, data.len(), None))
+    }
+    fn read(&self, offset: u64, len: usize) -> io::Result<Box<[u8]>> {
+        // Files opened by `CVec::load_lazy` could be changed by others while mapped, so they are never mapped
+        #[cfg(feature = "mmap")]
+        if self.temporary {
+            let mapped = self.mapping(offset + len as u64)?;
+            return Ok(mapped[offset as usize..][..len].into());
+        }
+        let mut file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        file.seek(SeekFrom::Start(offset))?;
+        let mut data = vec![0; len].into_boxed_slice();
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+    /// Returns a mapping of the file which covers at least its first `end` bytes.
+    #[cfg(feature = "mmap")]
+    fn mapping(&self, end: u64) -> io::Result<Arc<memmap2::Mmap>> {
+        let mut map = self.map.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(mapped) = map.as_ref().filter(|mapped| mapped.len() as u64 >= end) {
+            return Ok(Arc::clone(mapped));
+        }
+        let file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // SAFETY: the file was created by this process and is only ever appended to, so the bytes of a chunk never
+        // change once written. A failed append truncates the file, but only past the end of every chunk.
+        let mapped = unsafe { memmap2::Mmap::map(&*file)? };
+        Ok(Arc::clone(map.insert(Arc::new(mapped))))
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Where the bytes of a spilled chunk are
+#[derive(Clone)]
+enum Location {
+    File { file: Arc<SpillFile>, offset: u64 },
+    Store(Arc<StoredKey>),
+}
+
+/// The location of a compressed chunk which was moved out of memory, to a spill file or a [`ChunkStore`]
+#[derive(Clone)]
+pub(super) struct SpilledChunk {
+    location: Location,
+    len: usize,
+    /// The CRC-32 of the bytes, for chunks of a file opened by `CVec::load_lazy` or in a chunk store
+    checksum: Option<u32>,
+    /// The key the bytes are sealed with, see `CVec::set_spill_key`
+    #[cfg(feature = "encryption")]
+    key: Option<Arc<EncryptionKey>>,
+}
+
+impl SpilledChunk {
+    /// The `len` bytes at `offset` in `file`, which are checked against `checksum` whenever they are read
+    pub(super) fn new(file: Arc<SpillFile>, offset: u64, len: usize, checksum: Option<u32>) -> Self {
+        Self::at(Location::File { file, offset }, len, checksum)
+    }
+    fn at(location: Location, len: usize, checksum: Option<u32>) -> Self {
+        Self {
+            location,
+            len,
+            checksum,
+            #[cfg(feature = "encryption")]
+            key: None,
+        }
+    }
+    /// Reads the compressed bytes back from where they were moved to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if they can no longer be read (or do not match their checksum), the chunk is lost in that case.
+    pub(super) fn load(&self) -> Box<[u8]> {
+        self.try_load().unwrap_or_else(|err| panic!("failed to read spilled chunk from {}: {err}", self.describe()))
+    }
+    /// Like [`load`](Self::load), but returns an error rather than panicking. Bytes which do not match their
+    /// checksum (or cannot be decrypted) are an [`io::ErrorKind::InvalidData`] error.
+    pub(super) fn try_load(&self) -> io::Result<Box<[u8]>> {
+        let data = match &self.location {
+            Location::File { file, offset } => file.read(*offset, self.len)?,
+            Location::Store(stored) => stored.store.get_chunk(stored.key)?,
+        };
+        if data.len() != self.len || self.checksum.is_some_and(|checksum| crc32(&data) != checksum) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "chunk does not match its checksum"));
+        }
+        #[cfg(feature = "encryption")]
+        if let Some(key) = &self.key {
+            return key.open(&data, &[]);
+        }
+        Ok(data)
+    }
+    pub(super) fn describe(&self) -> String {
+        match &self.location {
+            Location::File { file, .. } => file.path.display().to_string(),
+            Location::Store(stored) => format!("key {} of a chunk store", stored.key),
+        }
+    }
+    /// The number of bytes on disk (or in the chunk store)
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// Where chunks are spilled to
+#[derive(Clone)]
+pub(super) struct SpillTarget {
+    place: SpillPlace,
+    /// Chunks are sealed with this before they are written, see `CVec::set_spill_key`
+    #[cfg(feature = "encryption")]
+    key: Option<Arc<EncryptionKey>>,
+}
+
+#[derive(Clone)]
+enum SpillPlace {
+    /// A temporary file in `dir`, which is created on the first spill
+    Dir { dir: PathBuf, file: Option<Arc<SpillFile>> },
+    Store(Arc<dyn ChunkStore>),
+}
+
+impl SpillTarget {
+    pub(super) fn dir(dir: PathBuf) -> Self {
+        Self::new(SpillPlace::Dir { dir, file: None })
+    }
+    fn new(place: SpillPlace) -> Self {
+        Self {
+            place,
+            #[cfg(feature = "encryption")]
+            key: None,
+        }
+    }
+    /// Keeps sealing chunks with the key of `old`, the target this one replaces.
+    #[cfg_attr(not(feature = "encryption"), allow(unused_mut, unused_variables))]
+    fn replacing(mut self, old: Option<&SpillTarget>) -> Self {
+        #[cfg(feature = "encryption")]
+        {
+            self.key = old.and_then(|old| old.key.clone());
+        }
+        self
+    }
+    /// A target in the same place (and sealing with the same key) which spills to a new file of its own, created
+    /// now so that its clones share it.
+    fn fresh(&self) -> io::Result<Self> {
+        let place = match &self.place {
+            SpillPlace::Dir { dir, .. } => SpillPlace::Dir { dir: dir.clone(), file: Some(Arc::new(SpillFile::create(dir)?)) },
+            SpillPlace::Store(store) => SpillPlace::Store(Arc::clone(store)),
+        };
+        let mut target = self.clone();
+        target.place = place;
+        Ok(target)
+    }
+    fn put(&mut self, data: &[u8]) -> io::Result<SpilledChunk> {
+        #[cfg(feature = "encryption")]
+        if let Some(key) = &self.key {
+            let spilled = self.place.put(&key.seal(data, &[]))?;
+            return Ok(SpilledChunk { key: Some(Arc::clone(key)), ..spilled });
+        }
+        self.place.put(data)
+    }
+}
+
+impl SpillPlace {
+    fn put(&mut self, data: &[u8]) -> io::Result<SpilledChunk> {
+        match self {
+            SpillPlace::Dir { dir, file } => {
+                let file = match file {
+                    Some(file) => file,
+                    None => file.insert(Arc::new(SpillFile::create(dir)?)),
+                };
+                file.append(data)
+            }
+            SpillPlace::Store(store) => {
+                let key = store.put_chunk(data)?;
+                let stored = StoredKey { store: Arc::clone(store), key };
+                Ok(SpilledChunk::at(Location::Store(Arc::new(stored)), data.len(), Some(crc32(data))))
+            }
+        }
+    }
+}
+
+/// Where and when a collection spills its chunks, see `CVec::set_spill_budget`. Clones share the spill file.
+#[derive(Clone)]
+pub(super) struct Spill {
+    budget: usize,
+    target: SpillTarget,
+    /// The compressed bytes held in memory when they were last counted, plus those of the chunks pushed since
+    resident: usize,
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// The number of bytes of compressed chunks which are kept in memory before chunks are spilled to disk, see
+    /// [`set_spill_budget`](Self::set_spill_budget).
+    pub fn spill_budget(&self) -> Option<usize> {
+        self.spill.as_ref().map(|spill| spill.budget)
+    }
+    /// Spills compressed chunks to a temporary file once more than `bytes` of them are held in memory, which turns
+    /// running out of memory into reading from disk.
+    ///
+    /// Chunks are spilled from the bottom of the stack up until at most half the budget is left in memory, so the
+    /// spill file is written in large batches. Spilled chunks are read back on access: reading one (e.g. with `get`
+    /// or `iter`) leaves it on disk, while opening one (e.g. with `get_mut` or `peek`) brings it back into memory.
+    /// Uncompressed chunks (the uncompressed buffer, the raw window and the chunk open for mutation) are never
+    /// spilled, and neither are decompressed chunks held by the cache.
+    ///
+    /// The file is created in [`std::env::temp_dir`] on the first spill and deleted once the collection and all
+    /// its clones are dropped. With the `mmap` feature it is read through a memory map, so concurrent readers do not
+    /// wait on each other and chunks which are read often are served straight from the OS page cache (files opened
+    /// with [`load_lazy`](Self::load_lazy) are not mapped, as they may be changed by others). Its space is not
+    /// reused, so a collection which is mutated heavily after spilling should be rebuilt from time to time. If
+    /// writing to the file fails the chunks simply stay in memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let mut compressed_stack = CVec::new::<1024, 0>();
+    /// compressed_stack.set_spill_budget(16 * 1024);
+    /// for i in 0..(1024 * 1024) {
+    ///     compressed_stack.push(i);
+    /// }
+    /// assert!(compressed_stack.resident_bytes() <= 16 * 1024);
+    /// assert!((0..(1024 * 1024)).eq(&compressed_stack));
+    /// ```
+    pub fn set_spill_budget(&mut self, bytes: usize) {
+        self.set_spill_budget_in(bytes, std::env::temp_dir());
+    }
+    /// Like [`set_spill_budget`](Self::set_spill_budget), but the spill file is created in `dir`. Chunks which
+    /// were already spilled stay where they are.
+    pub fn set_spill_budget_in(&mut self, bytes: usize, dir: impl Into<PathBuf>) {
+        let dir = dir.into();
+        // Keep spilling to the same file unless the directory changed
+        let target = match self.spill.take() {
+            Some(Spill { target, .. }) if matches!(&target.place, SpillPlace::Dir { dir: old_dir, .. } if *old_dir == dir) => target,
+            old => SpillTarget::dir(dir).replacing(old.as_ref().map(|spill| &spill.target)),
+        };
+        self.spill = Some(Spill { budget: bytes, target, resident: 0 });
+        self.spill_over_budget(true);
+    }
+    /// Like [`set_spill_budget`](Self::set_spill_budget), but chunks are moved to `store` rather than to a file. With
+    /// a budget of 0 every compressed chunk is moved to the store as soon as it is compressed.
+    ///
+    /// Chunks are dropped from the store once no collection refers to them anymore, e.g. when they are mutated (and
+    /// so brought back into memory) or popped. Their checksums are checked whenever they are read back.
+    pub fn set_chunk_store(&mut self, bytes: usize, store: Arc<dyn ChunkStore>) {
+        let target = SpillTarget::new(SpillPlace::Store(store)).replacing(self.spill.as_ref().map(|spill| &spill.target));
+        self.spill = Some(Spill { budget: bytes, target, resident: 0 });
+        self.spill_over_budget(true);
+    }
+    /// Keeps every compressed chunk in a memory-mapped file in [`std::env::temp_dir`] rather than in memory, each is
+    /// written out as soon as it is compressed. This is a spill budget of 0, see
+    /// [`set_spill_budget`](Self::set_spill_budget).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let mut compressed_stack = CVec::new::<1024, 0>();
+    /// compressed_stack.map_storage();
+    /// compressed_stack.extend(0..(64 * 1024));
+    /// assert_eq!(compressed_stack.resident_bytes(), 0);
+    /// assert!((0..(64 * 1024)).eq(&compressed_stack));
+    /// ```
+    #[cfg(feature = "mmap")]
+    pub fn map_storage(&mut self) {
+        self.set_spill_budget(0);
+    }
+    /// Like [`map_storage`](Self::map_storage), but the file is created in `dir`.
+    #[cfg(feature = "mmap")]
+    pub fn map_storage_in(&mut self, dir: impl Into<PathBuf>) {
+        self.set_spill_budget_in(0, dir);
+    }
+    /// Encrypts the chunks spilled from now on with `key` (see [`EncryptionKey`](crate::EncryptionKey)), or stops
+    /// encrypting them for `None`. Chunks which were already spilled are read back and spilled again with the new
+    /// key one at a time, which is how the key is rotated.
+    ///
+    /// Set the key before the collection exceeds its spill budget (e.g. right after
+    /// [`set_spill_budget`](Self::set_spill_budget)) so that no chunk is ever written in the clear. The key is kept
+    /// when the budget, directory or chunk store is changed, but chunks spilled by
+    /// [`OverflowPolicy::Spill`](crate::OverflowPolicy::Spill) are not encrypted. Fails if no spill budget or chunk
+    /// store is set, or if a chunk cannot be read back or spilled again, in which case the chunks before it have the
+    /// new key already.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::{CVec, EncryptionKey};
+    ///
+    /// let mut compressed_stack = CVec::new::<1024, 0>();
+    /// compressed_stack.set_spill_budget(0);
+    /// compressed_stack.set_spill_key(Some(EncryptionKey::new([1; 32]))).unwrap();
+    /// compressed_stack.extend(0..(16 * 1024));
+    /// compressed_stack.set_spill_key(Some(EncryptionKey::new([2; 32]))).unwrap();
+    /// assert_eq!(compressed_stack.resident_bytes(), 0);
+    /// assert!((0..(16 * 1024)).eq(&compressed_stack));
+    /// ```
+    #[cfg(feature = "encryption")]
+    pub fn set_spill_key(&mut self, key: Option<EncryptionKey>) -> io::Result<()> {
+        let Some(spill) = &mut self.spill else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no spill budget or chunk store is set"));
+        };
+        let key = key.map(Arc::new);
+        spill.target.key = key.clone();
+        for chunk in &mut self.compressed_storage {
+            let Chunk::Spilled(spilled) = chunk else { continue };
+            let current = match (&spilled.key, &key) {
+                (Some(old), Some(new)) => Arc::ptr_eq(old, new),
+                (None, None) => true,
+                _ => false,
+            };
+            if !current {
+                let data = spilled.try_load()?;
+                *spilled = spill.target.put(&data)?;
+            }
+        }
+        Ok(())
+    }
+    /// The number of bytes of compressed chunks held in memory, not counting spilled chunks.
+    pub fn resident_bytes(&self) -> usize {
+        self.compressed_storage.iter().map(|chunk| match chunk {
+            Chunk::Compressed(data) => data.len(),
+            Chunk::Raw(_) | Chunk::Spilled(_) => 0,
+        }).sum()
+    }
+    /// The number of bytes of compressed chunks on disk (or in the chunk store), in the spill file or (for a
+    /// collection opened with [`load_lazy`](Self::load_lazy)) not yet read from the file it was opened from.
+    pub fn spilled_bytes(&self) -> usize {
+        self.compressed_storage.iter().map(|chunk| match chunk {
+            Chunk::Spilled(spilled) => spilled.len(),
+            Chunk::Compressed(_) | Chunk::Raw(_) => 0,
+        }).sum()
+    }
+    /// Counts a newly pushed chunk towards the spill budget, spilling if the budget is exceeded.
+    /// The spill settings of a temporary collection built from this one, e.g. a run of `sort`: if this collection
+    /// spills, each chunk is spilled to a new file in the same place as soon as it is compressed, which is deleted
+    /// along with the temporary collection. Its chunks stay in memory if the file cannot be created.
+    pub(super) fn temporary_spill(&self) -> Option<Spill> {
+        let target = self.spill.as_ref()?.target.fresh().ok()?;
+        Some(Spill { budget: 0, target, resident: 0 })
+    }
+    pub(super) fn spill_pushed(&mut self, bytes: usize) {
+        if let Some(spill) = &mut self.spill {
+            spill.resident += bytes;
+            self.spill_over_budget(false);
+        }
+    }
+    /// Spills chunks from the bottom up until at most half the budget is held in memory, if the budget is exceeded
+    /// (or if `recount`, the resident bytes are counted again first).
+    fn spill_over_budget(&mut self, recount: bool) {
+        match &self.spill {
+            Some(spill) if recount || spill.resident > spill.budget => {}
+            _ => return,
+        }
+        // Pushed chunks were counted as they came, but others may have been mutated or reloaded since
+        let resident = self.resident_bytes();
+        let Some(spill) = &mut self.spill else { return };
+        spill.resident = resident;
+        if spill.resident <= spill.budget {
+            return;
+        }
+        let excess = spill.resident - spill.budget / 2;
+        spill.resident -= spill_chunks(&mut self.compressed_storage, &mut spill.target, excess);
+    }
+}
+
+/// Spills compressed chunks from the bottom up until at least `excess` bytes were spilled or none are left. Returns
+/// the number of bytes spilled, chunks stay in memory if writing them fails.
+pub(super) fn spill_chunks<T>(chunks: &mut [Chunk<T>], target: &mut SpillTarget, excess: usize) -> usize {
+    let mut spilled_bytes = 0;
+    for chunk in chunks {
+        if spilled_bytes >= excess {
+            break;
+        }
+        if let Chunk::Compressed(data) = chunk {
+            let Ok(spilled) = target.put(data) else { break };
+            spilled_bytes += data.len();
+            *chunk = Chunk::Spilled(spilled);
+        }
+    }
+    spilled_bytes
+}
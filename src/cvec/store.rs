@@ -0,0 +1,103 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+
+/// Holds the compressed chunks which a CVec moves out of memory, see `CVec::set_chunk_store`
+///
+/// The store decides where the bytes live (on disk, in an object store, in a tiered cache, ...), the collection
+/// only keeps the key it was handed. A store can be shared by many collections, and is called from whichever thread
+/// accesses a chunk.
+pub trait ChunkStore: Send + Sync {
+    /// Stores the compressed bytes of a chunk, returning the key to get them back by.
+    fn put_chunk(&self, data: &[u8]) -> io::Result<u64>;
+    /// Returns the bytes stored under `key`.
+    fn get_chunk(&self, key: u64) -> io::Result<Box<[u8]>>;
+    /// Forgets the bytes stored under `key`, once no collection (or clone of one) refers to them anymore.
+    fn drop_chunk(&self, key: u64);
+}
+
+/// A [`ChunkStore`] which keeps the chunks in memory, in a Vec indexed by key
+///
+/// Chunks moved here are still held in memory, so this mostly serves as the reference implementation, and as the
+/// in-memory tier of a store of your own.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use compressed_collections::{CVec, VecChunkStore};
+///
+/// let store = Arc::new(VecChunkStore::new());
+/// let mut compressed_stack = CVec::new::<1024, 0>();
+/// compressed_stack.set_chunk_store(0, store.clone());
+/// compressed_stack.extend(0..(16 * 1024));
+/// assert_eq!(store.len(), 16);
+/// assert!((0..(16 * 1024)).eq(&compressed_stack));
+/// drop(compressed_stack);
+/// assert!(store.is_empty());
+/// ```
+#[derive(Default)]
+pub struct VecChunkStore {
+    state: Mutex<VecStoreState>,
+}
+
+#[derive(Default)]
+struct VecStoreState {
+    chunks: Vec<Option<Box<[u8]>>>,
+    /// The keys of dropped chunks, which are handed out again
+    free: Vec<usize>,
+}
+
+impl VecChunkStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// The number of chunks held
+    pub fn len(&self) -> usize {
+        let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.chunks.len() - state.free.len()
+    }
+    /// Whether no chunks are held
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ChunkStore for VecChunkStore {
+    fn put_chunk(&self, data: &[u8]) -> io::Result<u64> {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let key = match state.free.pop() {
+            Some(key) => key,
+            None => {
+                state.chunks.push(None);
+                state.chunks.len() - 1
+            }
+        };
+        state.chunks[key] = Some(data.into());
+        Ok(key as u64)
+    }
+    fn get_chunk(&self, key: u64) -> io::Result<Box<[u8]>> {
+        let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let chunk = usize::try_from(key).ok().and_then(|key| state.chunks.get(key)).and_then(Option::as_ref);
+        chunk.cloned().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no chunk stored under key {key}")))
+    }
+    fn drop_chunk(&self, key: u64) {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Ok(key) = usize::try_from(key) else { return };
+        if state.chunks.get_mut(key).and_then(Option::take).is_some() {
+            state.free.push(key);
+        }
+    }
+}
+
+/// A chunk held by a [`ChunkStore`], which is dropped from the store along with the last reference to it
+pub(super) struct StoredKey {
+    pub(super) store: Arc<dyn ChunkStore>,
+    pub(super) key: u64,
+}
+
+impl Drop for StoredKey {
+    fn drop(&mut self) {
+        self.store.drop_chunk(self.key);
+    }
+}
@@ -0,0 +1,221 @@
+use std::io::{self, Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::{cache::Cache, inner::{default_chunk_elems, CVec as CVecInner, Chunk}};
+use crate::compression::{compress, try_decompress};
+
+// Stream layout (all integers little endian):
+// - `u64` number of compressed chunks
+// - for each chunk: `u64` number of elements, `u64` byte length, then the compressed bytes
+// - `u64` byte length of the uncompressed tail, then its postcard encoding
+//
+// Chunk stream layout, written by `CVecWriter` without knowing the number of chunks up front:
+// - for each chunk: `u64` number of elements (at least 1), `u64` byte length, then the compressed bytes
+// - `u64` 0 to mark the end
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// Writes the collection to `writer` one chunk at a time, without building the whole serialized form in memory.
+    ///
+    /// Compressed chunks are written as-is, only a chunk which is currently open for mutation gets compressed. The output can be read back with
+    /// [`read_from`](Self::read_from).
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()>
+    where
+        T: Serialize,
+    {
+        write_len(&mut writer, self.compressed_storage.len())?;
+        for (chunk_idx, chunk) in self.compressed_storage.iter().enumerate() {
+            write_len(&mut writer, self.chunk_ends[chunk_idx] - self.chunk_start(chunk_idx))?;
+            let chunk = chunk.compressed_bytes(COMPRESSION_LEVEL);
+            write_len(&mut writer, chunk.len())?;
+            writer.write_all(&chunk)?;
+        }
+        let tail = postcard::to_stdvec(&self.uncompressed_buffer).map_err(invalid_data)?;
+        write_len(&mut writer, tail.len())?;
+        writer.write_all(&tail)?;
+        writer.flush()
+    }
+    /// Reads a collection written by [`write_to`](Self::write_to), holding at most one chunk of the input in memory
+    /// beyond the collection itself.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let mut cvec = Self::default();
+        let chunks = read_len(&mut reader)?;
+        for _ in 0..chunks {
+            let elems = read_len(&mut reader)?;
+            if elems == 0 || elems > cvec.chunk_elems() {
+                return Err(invalid_data("chunk length does not match the chunk size"));
+            }
+            let chunk = read_bytes(&mut reader)?;
+            cvec.chunk_ends.push(cvec.compressed_len() + elems);
+            cvec.compressed_storage.push(Chunk::Compressed(chunk));
+        }
+        let tail = read_bytes(&mut reader)?;
+        cvec.uncompressed_buffer = postcard::from_bytes(&tail).map_err(invalid_data)?;
+        if cvec.uncompressed_buffer.len() >= cvec.chunk_elems() {
+            return Err(invalid_data("uncompressed tail is longer than a chunk"));
+        }
+        Ok(cvec)
+    }
+}
+
+/// Compresses elements into chunks as they are pushed and writes each chunk straight to `W`
+///
+/// At most one chunk of elements is held in memory. The chunks can be read back with a [`CVecReader`] (e.g. on the
+/// other end of a network connection) as soon as they are written. [`finish`](Self::finish) has to be called to
+/// write the last chunk and mark the end, a stream which was not finished reads as truncated.
+///
+/// # Examples
+///
+/// ```
+/// use compressed_collections::{CVecReader, CVecWriter};
+///
+/// let mut writer = CVecWriter::<u32, _>::new(Vec::new());
+/// for i in 0..(16 * 1024) {
+///     writer.push(i).unwrap();
+/// }
+/// let bytes = writer.finish().unwrap();
+/// let reader = CVecReader::<u32, _>::new(bytes.as_slice());
+/// assert!((0..(16 * 1024)).eq(reader.map(Result::unwrap)));
+/// ```
+pub struct CVecWriter<T, W: Write, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> {
+    writer: W,
+    buffer: Vec<T>,
+    chunk_elems: usize,
+}
+
+impl<T, W: Write, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecWriter<T, W, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+    /// Creates a writer which writes chunks to `writer`, using the default chunk size if `CHUNK_ELEMS` is 0.
+    pub fn new(writer: W) -> Self {
+        Self { writer, buffer: Vec::new(), chunk_elems: default_chunk_elems::<T, CHUNK_ELEMS>() }
+    }
+    /// Appends an element, writing out its chunk once it is filled.
+    pub fn push(&mut self, value: T) -> io::Result<()>
+    where
+        T: Serialize,
+    {
+        self.buffer.push(value);
+        if self.buffer.len() >= self.chunk_elems {
+            self.write_chunk()?;
+        }
+        Ok(())
+    }
+    /// Writes the partially filled last chunk and the end marker, flushes the writer and returns it.
+    pub fn finish(mut self) -> io::Result<W>
+    where
+        T: Serialize,
+    {
+        if !self.buffer.is_empty() {
+            self.write_chunk()?;
+        }
+        write_len(&mut self.writer, 0)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+    /// The underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+    fn write_chunk(&mut self) -> io::Result<()>
+    where
+        T: Serialize,
+    {
+        let chunk = compress(self.buffer.as_slice(), COMPRESSION_LEVEL);
+        write_len(&mut self.writer, self.buffer.len())?;
+        write_len(&mut self.writer, chunk.len())?;
+        self.writer.write_all(&chunk)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+/// Iterator over the elements of a chunk stream written by a [`CVecWriter`], reading one chunk at a time from `R`
+///
+/// Yields an error (and then ends) if the stream cannot be read, is truncated or holds chunks which cannot be
+/// decoded or do not match their element counts.
+pub struct CVecReader<T, R: Read> {
+    reader: R,
+    values: std::vec::IntoIter<T>,
+    done: bool,
+}
+
+impl<T, R: Read> CVecReader<T, R> {
+    /// Creates a reader which reads chunks from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self { reader, values: Vec::new().into_iter(), done: false }
+    }
+    /// Returns the underlying reader, positioned after the last chunk read so far.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+    fn read_chunk(&mut self) -> io::Result<Option<Vec<T>>>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let elems = read_len(&mut self.reader)?;
+        if elems == 0 {
+            return Ok(None);
+        }
+        let chunk = read_bytes(&mut self.reader)?;
+        let values: Vec<T> = try_decompress(&chunk).map_err(invalid_data)?;
+        if values.len() != elems {
+            return Err(invalid_data("chunk does not hold the number of elements in its header"));
+        }
+        Ok(Some(values))
+    }
+}
+
+impl<T: for<'a> Deserialize<'a>, R: Read> Iterator for CVecReader<T, R> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<io::Result<T>> {
+        loop {
+            if let Some(value) = self.values.next() {
+                return Some(Ok(value));
+            }
+            if self.done {
+                return None;
+            }
+            match self.read_chunk() {
+                Ok(Some(values)) => self.values = values.into_iter(),
+                Ok(None) => self.done = true,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+pub(super) fn write_len<W: Write>(writer: &mut W, len: usize) -> io::Result<()> {
+    writer.write_all(&(len as u64).to_le_bytes())
+}
+
+pub(super) fn read_len<R: Read>(reader: &mut R) -> io::Result<usize> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    usize::try_from(u64::from_le_bytes(bytes)).map_err(invalid_data)
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Box<[u8]>> {
+    let len = read_len(reader)?;
+    read_exact_bytes(reader, len)
+}
+
+/// Reads exactly `len` bytes.
+pub(super) fn read_exact_bytes<R: Read>(reader: &mut R, len: usize) -> io::Result<Box<[u8]>> {
+    let mut bytes = Vec::new();
+    // Don't trust `len` for the allocation size, a corrupt stream would otherwise allocate arbitrarily much
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes.into_boxed_slice())
+}
+
+pub(super) fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
@@ -0,0 +1,155 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use serde::Deserialize;
+
+use super::cache::Cache;
+use super::inner::Chunk;
+use super::{CVecChunk, CVecInner};
+use crate::compression::{try_decompress_bytes, try_deserialize};
+
+/// What is wrong with a chunk found by `CVec::verify`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChunkDamage {
+    /// The bytes of a spilled or lazily loaded chunk could not be read, or do not match their checksum
+    Unreadable(String),
+    /// The bytes are not a brotli stream of serialized elements
+    Corrupt(String),
+    /// The chunk holds a different number of elements than the collection expects
+    WrongLength {
+        /// The number of elements the collection expects in the chunk
+        expected: usize,
+        /// The number of elements found in the chunk
+        found: usize,
+    },
+}
+
+impl fmt::Display for ChunkDamage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unreadable(err) => write!(f, "unreadable: {err}"),
+            Self::Corrupt(err) => write!(f, "corrupt: {err}"),
+            Self::WrongLength { expected, found } => write!(f, "holds {found} elements instead of {expected}"),
+        }
+    }
+}
+
+/// A damaged chunk found by `CVec::verify`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DamagedChunk {
+    /// The index of the chunk, the bottom chunk of the stack being 0
+    pub index: usize,
+    /// What is wrong with it
+    pub damage: ChunkDamage,
+}
+
+impl fmt::Display for DamagedChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "chunk {} is {}", self.index, self.damage)
+    }
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// Checks every chunk, returning the damaged ones (bottom of the stack first) if any are.
+    ///
+    /// Each chunk is read back (checking its checksum if it has one), decompressed and deserialized, and must hold
+    /// as many elements as the collection expects. Nothing is cached or changed, so this is worth calling once
+    /// before trusting a collection loaded with e.g. [`load_lazy`](Self::load_lazy), whose chunks are otherwise only
+    /// checked (and panic on damage) when first accessed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let compressed_stack: CVec<u32> = (0..(16 * 1024)).collect();
+    /// assert_eq!(compressed_stack.verify(), Ok(()));
+    /// ```
+    pub fn verify(&self) -> Result<(), Vec<DamagedChunk>>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let damaged: Vec<DamagedChunk> = (0..self.compressed_storage.len())
+            .filter_map(|index| self.verify_chunk(index).err().map(|damage| DamagedChunk { index, damage }))
+            .collect();
+        if damaged.is_empty() {
+            Ok(())
+        } else {
+            Err(damaged)
+        }
+    }
+    /// Checks the bookkeeping of the collection without decoding any chunk: that there is a chunk end for every
+    /// chunk, that each chunk holds at least one element and no more than a chunk holds, and that the uncompressed
+    /// buffer is shorter than a chunk.
+    ///
+    /// This holds after any operation, including ones which panicked: e.g. a [`push`](Self::push) whose chunk
+    /// panicked while being compressed (such as in `T::serialize`) is undone, and a [`pop`](Self::pop) whose chunk
+    /// panicked while being decompressed leaves the chunk in place. So a collection can be used again after
+    /// catching a panic, as long as this returns `true`; otherwise what can still be read is best recovered with
+    /// [`salvage`](Self::salvage). It does not check that the chunks decode, see [`verify`](Self::verify).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compressed_collections::CVec;
+    ///
+    /// let compressed_stack: CVec<u32> = (0..(16 * 1024 + 1)).collect();
+    /// assert!(compressed_stack.is_consistent());
+    /// ```
+    pub fn is_consistent(&self) -> bool {
+        self.check_structure().is_ok()
+    }
+    /// The reason [`is_consistent`](Self::is_consistent) fails, if it does.
+    pub(super) fn check_structure(&self) -> Result<(), String> {
+        if self.chunk_ends.len() != self.compressed_storage.len() {
+            return Err(format!("{} chunk ends for {} chunks", self.chunk_ends.len(), self.compressed_storage.len()));
+        }
+        for (index, chunk) in self.compressed_storage.iter().enumerate() {
+            let (start, end) = (self.chunk_start(index), self.chunk_ends[index]);
+            if end <= start || end - start > self.chunk_elems() {
+                return Err(format!("chunk {index} ends at {end}, {start} being its start"));
+            }
+            if let Chunk::Raw(values) = chunk {
+                if values.len() != end - start {
+                    return Err(DamagedChunk { index, damage: ChunkDamage::WrongLength { expected: end - start, found: values.len() } }.to_string());
+                }
+            }
+        }
+        if self.open_chunk.is_some_and(|chunk_idx| !matches!(self.compressed_storage.get(chunk_idx), Some(Chunk::Raw(_)))) {
+            return Err("the open chunk is not decompressed".to_string());
+        }
+        if self.uncompressed_buffer.len() >= self.chunk_elems() {
+            return Err("uncompressed buffer is longer than a chunk".to_string());
+        }
+        Ok(())
+    }
+    fn verify_chunk(&self, chunk_idx: usize) -> Result<(), ChunkDamage>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        self.check_chunk_len(chunk_idx, self.read_chunk(chunk_idx)?.len())
+    }
+    /// Reads back (checking its checksum if it has one), decompresses and deserializes the chunk at `chunk_idx`.
+    pub(super) fn read_chunk(&self, chunk_idx: usize) -> Result<CVecChunk<'_, T>, ChunkDamage>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let data = match &self.compressed_storage[chunk_idx] {
+            Chunk::Raw(values) => return Ok(CVecChunk::Borrowed(values)),
+            Chunk::Compressed(data) => Cow::Borrowed(&**data),
+            Chunk::Spilled(spilled) => Cow::Owned(spilled.try_load().map_err(|err| ChunkDamage::Unreadable(err.to_string()))?.into_vec()),
+        };
+        let decompressed = try_decompress_bytes(&data).map_err(|err| ChunkDamage::Corrupt(err.to_string()))?;
+        let values = try_deserialize(&decompressed).map_err(|err| ChunkDamage::Corrupt(err.to_string()))?;
+        Ok(CVecChunk::Decompressed(values))
+    }
+    /// Checks that the chunk at `chunk_idx` holds `found` elements, as many as the collection expects.
+    pub(super) fn check_chunk_len(&self, chunk_idx: usize, found: usize) -> Result<(), ChunkDamage> {
+        // Damaged chunk ends may go backwards, which leaves the chunk no elements
+        let expected = self.chunk_ends[chunk_idx].saturating_sub(self.chunk_start(chunk_idx));
+        if found != expected || expected == 0 {
+            return Err(ChunkDamage::WrongLength { expected, found });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+
+use either::Either;
+use serde::{Deserialize, Serialize};
+
+use crate::compression::decompress;
+use crate::cvec::{cache::{Cache, Cached}, CompressedElem};
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "T: Serialize", deserialize = "T: for<'a> Deserialize<'a>"))]
+pub struct Deque<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0, C: Cache = Cached<T, CHUNK_ELEMS>> {
+    pub(super) uncompressed_buffer_front: VecDeque<T>,
+    /// Compressed chunks, each holding exactly `CHUNK_ELEMS` elements
+    pub(super) compressed_storage: VecDeque<Box<[u8]>>,
+    pub(super) uncompressed_buffer_back: VecDeque<T>,
+    /// The length past which pushing an element evicts elements from the opposite end
+    pub(super) max_len: Option<usize>,
+    #[serde(skip)]
+    pub(super) cache: C,
+    /// The id the cache knows the first chunk of `compressed_storage` by, chunk ids count up from front to back
+    /// and stay the same as chunks are pushed or popped at the front
+    #[serde(skip)]
+    pub(super) front_chunk_id: usize,
+}
+
+impl<T: Clone, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> Clone for Deque<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    fn clone(&self) -> Self {
+        Self {
+            uncompressed_buffer_front: self.uncompressed_buffer_front.clone(),
+            compressed_storage: self.compressed_storage.clone(),
+            uncompressed_buffer_back: self.uncompressed_buffer_back.clone(),
+            max_len: self.max_len,
+            cache: C::default(),
+            front_chunk_id: self.front_chunk_id,
+        }
+    }
+}
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> Deque<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    /// The elements front to back, as runs borrowed from the buffers and each compressed chunk decompressed in turn
+    fn runs(&self) -> impl Iterator<Item = Either<&[T], Vec<T>>> where T: for<'a> Deserialize<'a> {
+        let (front_a, front_b) = self.uncompressed_buffer_front.as_slices();
+        let (back_a, back_b) = self.uncompressed_buffer_back.as_slices();
+        let chunks = self.compressed_storage.iter().map(|data| Either::Right(decompress::<Vec<T>>(data)));
+        [front_a, front_b].into_iter().map(Either::Left)
+            .chain(chunks)
+            .chain([back_a, back_b].into_iter().map(Either::Left))
+    }
+    /// Calls `f` on equal length runs of the elements of both collections, front to back, until it returns `Some`,
+    /// like `CVec` does.
+    fn zip_runs<R>(&self, other: &Self, mut f: impl FnMut(&[T], &[T]) -> Option<R>) -> Option<R> where T: for<'a> Deserialize<'a> {
+        let (mut runs, mut other_runs) = (self.runs(), other.runs());
+        let (mut run, mut other_run) = (runs.next(), other_runs.next());
+        let (mut offset, mut other_offset) = (0, 0);
+        while let (Some(values), Some(other_values)) = (&run, &other_run) {
+            let len = (values.len() - offset).min(other_values.len() - other_offset);
+            if let Some(result) = f(&values[offset..offset + len], &other_values[other_offset..other_offset + len]) {
+                return Some(result);
+            }
+            offset += len;
+            other_offset += len;
+            if offset == values.len() {
+                (run, offset) = (runs.next(), 0);
+            }
+            if other_offset == other_values.len() {
+                (other_run, other_offset) = (other_runs.next(), 0);
+            }
+        }
+        None
+    }
+}
+
+/// Deques are compared by their elements front to back, like a `VecDeque`, whichever end they were pushed onto.
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> PartialEq for Deque<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: PartialEq + for<'a> Deserialize<'a>,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.zip_runs(other, |a, b| (a != b).then_some(())).is_none()
+    }
+}
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> Eq for Deque<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Eq + for<'a> Deserialize<'a>,
+{}
+
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> PartialOrd for Deque<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: PartialOrd + for<'a> Deserialize<'a>,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        let ord = self.zip_runs(other, |a, b| match a.partial_cmp(b) {
+            Some(std::cmp::Ordering::Equal) => None,
+            ord => Some(ord),
+        });
+        ord.unwrap_or_else(|| self.len().partial_cmp(&other.len()))
+    }
+}
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> Ord for Deque<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Ord + for<'a> Deserialize<'a>,
+{
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.zip_runs(other, |a, b| Some(a.cmp(b)).filter(|ord| ord.is_ne()))
+            .unwrap_or_else(|| self.len().cmp(&other.len()))
+    }
+}
+
+/// Hashes the length and then every element front to back, so equal deques hash the same.
+impl<T, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> std::hash::Hash for Deque<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: std::hash::Hash + for<'a> Deserialize<'a>,
+{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for run in self.runs() {
+            run.iter().for_each(|value| value.hash(state));
+        }
+    }
+}
+
+impl<T: std::fmt::Debug, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, C: Cache> std::fmt::Debug for Deque<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut f = f.debug_list();
+        f.entries(&self.uncompressed_buffer_front);
+        if !self.compressed_storage.is_empty() {
+            let compressed: Vec<&[u8]> = self.compressed_storage.iter().map(|data| &**data).collect();
+            f.entry(&CompressedElem::<T>(&compressed, compressed.len() * CHUNK_ELEMS, std::marker::PhantomData));
+        }
+        f.entries(&self.uncompressed_buffer_back);
+        f.finish()
+    }
+}
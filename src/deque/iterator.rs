@@ -0,0 +1,175 @@
+use std::collections::vec_deque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::compression::decompress;
+use crate::cvec::cache::{Cache, Cached, Uncached};
+use super::DequeInner;
+
+// Owned Iterator
+
+/// Owning iterator over a Deque, yields elements front to back
+///
+/// Chunks are decompressed one at a time as they are reached, from either end.
+pub struct DequeIntoIter<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0, C: Cache = Cached<T, CHUNK_ELEMS>>(DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>);
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Iterator for DequeIntoIter<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop_front()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len(), Some(self.0.len()))
+    }
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> DoubleEndedIterator for DequeIntoIter<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.pop_back()
+    }
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> ExactSizeIterator for DequeIntoIter<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: for<'a> Deserialize<'a>,
+{}
+
+/// Iterator over the elements removed by `Deque::drain`, the compressed chunks among them are only decompressed
+/// once they are reached
+pub type DequeDrain<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> = DequeIntoIter<T, CHUNK_ELEMS, COMPRESSION_LEVEL, Uncached>;
+
+// Owned IntoIterator
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> IntoIterator for DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    type Item = T;
+    type IntoIter = DequeIntoIter<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        DequeIntoIter(self)
+    }
+}
+
+// Shared borrow Iterator
+
+/// Iterator over clones of the elements of a Deque, returned by `Deque::iter`
+///
+/// Chunks are decompressed one at a time as they are reached, from either end.
+pub struct DequeIter<'i, T> {
+    remaining: usize,
+    front: vec_deque::Iter<'i, T>,
+    chunks: vec_deque::Iter<'i, Box<[u8]>>,
+    /// The chunk being iterated over from the front
+    chunk_front: std::vec::IntoIter<T>,
+    /// The chunk being iterated over from the back
+    chunk_back: std::vec::IntoIter<T>,
+    back: vec_deque::Iter<'i, T>,
+}
+
+impl<'i, T> DequeIter<'i, T> {
+    pub(super) fn new<C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>(inner: &'i DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>) -> Self {
+        DequeIter {
+            remaining: inner.len(),
+            front: inner.uncompressed_buffer_front.iter(),
+            chunks: inner.compressed_storage.iter(),
+            chunk_front: Default::default(),
+            chunk_back: Default::default(),
+            back: inner.uncompressed_buffer_back.iter(),
+        }
+    }
+}
+
+impl<'i, T> Iterator for DequeIter<'i, T>
+where
+    T: Clone + for<'a> Deserialize<'a>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.front.next().cloned().or_else(|| loop {
+            if let Some(x) = self.chunk_front.next() {
+                break Some(x);
+            }
+            match self.chunks.next() {
+                Some(data) => self.chunk_front = decompress::<Vec<T>>(data).into_iter(),
+                None => break self.chunk_back.next().or_else(|| self.back.next().cloned()),
+            }
+        });
+        self.remaining -= next.is_some() as usize;
+        next
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'i, T> DoubleEndedIterator for DequeIter<'i, T>
+where
+    T: Clone + for<'a> Deserialize<'a>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let next = self.back.next_back().cloned().or_else(|| loop {
+            if let Some(x) = self.chunk_back.next_back() {
+                break Some(x);
+            }
+            match self.chunks.next_back() {
+                Some(data) => self.chunk_back = decompress::<Vec<T>>(data).into_iter(),
+                None => break self.chunk_front.next_back().or_else(|| self.front.next_back().cloned()),
+            }
+        });
+        self.remaining -= next.is_some() as usize;
+        next
+    }
+}
+
+impl<'i, T> ExactSizeIterator for DequeIter<'i, T>
+where
+    T: Clone + for<'a> Deserialize<'a>,
+{}
+
+// Shared borrow IntoIterator
+
+impl<'i, T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> IntoIterator for &'i DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Clone + for<'a> Deserialize<'a>,
+{
+    type Item = T;
+    type IntoIter = DequeIter<'i, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        DequeIter::new(self)
+    }
+}
+
+// Extend
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Extend<T> for DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Serialize
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.extend_back(iter);
+    }
+}
+
+// FromIterator
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> FromIterator<T> for DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>
+where
+    T: Serialize
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut c = Self::default();
+        c.extend(iter);
+        c
+    }
+}
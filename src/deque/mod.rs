@@ -0,0 +1,846 @@
+mod inner;
+mod iterator;
+
+use std::collections::VecDeque;
+use std::ops::{Bound, RangeBounds};
+
+use either::Either;
+use serde::{Deserialize, Serialize};
+
+use crate::cvec::{cache::{Cache, CacheAccess, Cached, ChunkBytes, RcCached, SharedCacheAccess, Uncached}, Value};
+use crate::compression::{compress, decompress, decompress_nth};
+use self::inner::Deque as DequeInner;
+pub use self::iterator::{DequeDrain, DequeIntoIter, DequeIter};
+
+/// Where an element of a [`Deque`] lives
+enum Position {
+    Front(usize),
+    /// The chunk index and the offset within it
+    Compressed(usize, usize),
+    Back(usize),
+}
+
+/// A deque which automatically compresses itself over a certain size
+///
+/// Elements pushed onto either end are kept in an uncompressed buffer at that end. Once it holds two chunks worth of
+/// elements the inner `CHUNK_ELEMS` are compressed into a chunk, so each buffer holds less than `2 * CHUNK_ELEMS`
+/// elements. Popping from an end with an empty buffer decompresses the chunk at that end. Since a buffer is only
+/// compressed when full and only refilled when empty, alternating pushes and pops at one end never compress or
+/// decompress a chunk more than once per `CHUNK_ELEMS` operations.
+///
+/// # Examples
+///
+/// ```
+/// use compressed_collections::Deque;
+///
+/// let mut compressed_deque = Deque::new::<1024, 0>();
+/// for _ in 0..(1024 * 4) {
+///     compressed_deque.push_back(1);
+/// }
+/// while let Some(x) = compressed_deque.pop_front() {
+///     assert!(x == 1);
+/// }
+/// ```
+///
+/// # Panics
+///
+/// This function should not panic (except on out of memory conditions). If it does, please submit an issue.
+pub type Deque<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> = DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, Cached<T, CHUNK_ELEMS>>;
+
+impl<T> Deque<T, 0, 0> {
+    pub fn new<const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>() -> Deque<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        DequeInner::default()
+    }
+}
+
+/// A deque which automatically compresses itself over a certain size, with a decompression cache that can be read
+/// through a shared reference
+///
+/// See [`Deque`], this additionally allows cloning elements out with [`get`](DequeRc::get) from `&self`.
+pub type DequeRc<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> = DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, RcCached<T, CHUNK_ELEMS>>;
+
+impl<T> DequeRc<T, 0, 0> {
+    pub fn new<const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>() -> DequeRc<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        DequeInner::default()
+    }
+}
+
+/// A deque which automatically compresses itself over a certain size, without a decompression cache
+///
+/// See [`Deque`].
+pub type DequeUncached<T, const CHUNK_ELEMS: usize = 1024, const COMPRESSION_LEVEL: i32 = 0> = DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, Uncached>;
+
+impl<T> DequeUncached<T, 0, 0> {
+    pub fn new<const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32>() -> DequeUncached<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        DequeInner::default()
+    }
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> Default for DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    fn default() -> Self {
+        let () = Self::CHUNK_ELEMS_CHECK;
+        let () = Self::COMPRESSION_LEVEL_CHECK;
+        Self {
+            uncompressed_buffer_front: Default::default(),
+            compressed_storage: Default::default(),
+            uncompressed_buffer_back: Default::default(),
+            max_len: None,
+            cache: Default::default(),
+            front_chunk_id: 0,
+        }
+    }
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C> {
+    const CHUNK_ELEMS_CHECK: () = assert!(CHUNK_ELEMS > 0, "Chunks must hold at least one element");
+    const COMPRESSION_LEVEL_CHECK: () = assert!(COMPRESSION_LEVEL >= 0 && COMPRESSION_LEVEL <= 11, "Compression level must be between 0 and 11");
+
+    /// Creates an empty deque which never holds more than `max_len` elements.
+    ///
+    /// Pushing onto a full deque evicts the elements at the opposite end: those in the uncompressed buffer one at a
+    /// time, otherwise the whole outermost chunk is dropped without being decompressed (leaving the deque up to
+    /// `CHUNK_ELEMS - 1` elements short of `max_len`). Use [`push_back_evicting`](Self::push_back_evicting) or
+    /// [`push_front_evicting`](Self::push_front_evicting) to get hold of the evicted elements.
+    pub fn with_max_len(max_len: usize) -> Self {
+        Self { max_len: Some(max_len), ..Self::default() }
+    }
+    /// The maximum number of elements the deque holds, see [`with_max_len`](Self::with_max_len).
+    pub fn max_len(&self) -> Option<usize> {
+        self.max_len
+    }
+    /// Appends an element to the back of the deque.
+    ///
+    /// If this takes a bounded deque past its maximum length, elements are evicted from the front.
+    pub fn push_back(&mut self, value: T)
+    where
+        T: Serialize,
+    {
+        drop(self.push_back_evicting(value));
+    }
+    /// Appends an element to the front of the deque.
+    ///
+    /// If this takes a bounded deque past its maximum length, elements are evicted from the back.
+    pub fn push_front(&mut self, value: T)
+    where
+        T: Serialize,
+    {
+        drop(self.push_front_evicting(value));
+    }
+    /// Appends an element to the back of the deque, returning the elements evicted from the front to keep a bounded
+    /// deque within its maximum length (see [`with_max_len`](Self::with_max_len)).
+    pub fn push_back_evicting(&mut self, value: T) -> DequeUncached<T, CHUNK_ELEMS, COMPRESSION_LEVEL>
+    where
+        T: Serialize,
+    {
+        self.uncompressed_buffer_back.push_back(value);
+        if self.uncompressed_buffer_back.len() >= 2 * CHUNK_ELEMS {
+            // Compress the half next to the compressed storage, the elements closest to the back stay uncompressed
+            let compressed = compress(&self.uncompressed_buffer_back.make_contiguous()[..CHUNK_ELEMS], COMPRESSION_LEVEL);
+            self.push_back_chunk(compressed);
+            self.uncompressed_buffer_back.drain(..CHUNK_ELEMS);
+        }
+        match self.max_len {
+            Some(max_len) if self.len() > max_len => self.evict_front(self.len() - max_len),
+            _ => DequeInner::default(),
+        }
+    }
+    /// Appends an element to the front of the deque, returning the elements evicted from the back to keep a bounded
+    /// deque within its maximum length (see [`with_max_len`](Self::with_max_len)).
+    pub fn push_front_evicting(&mut self, value: T) -> DequeUncached<T, CHUNK_ELEMS, COMPRESSION_LEVEL>
+    where
+        T: Serialize,
+    {
+        self.uncompressed_buffer_front.push_front(value);
+        if self.uncompressed_buffer_front.len() >= 2 * CHUNK_ELEMS {
+            // Compress the half next to the compressed storage, the elements closest to the front stay uncompressed
+            let compressed = compress(&self.uncompressed_buffer_front.make_contiguous()[CHUNK_ELEMS..], COMPRESSION_LEVEL);
+            self.push_front_chunk(compressed);
+            self.uncompressed_buffer_front.truncate(CHUNK_ELEMS);
+        }
+        match self.max_len {
+            Some(max_len) if self.len() > max_len => self.evict_back(self.len() - max_len),
+            _ => DequeInner::default(),
+        }
+    }
+    /// Appends all elements of `iter` to the back of the deque, in order.
+    ///
+    /// Elements are compressed a whole chunk at a time as the back buffer fills, like repeated
+    /// [`push_back`](Self::push_back) calls.
+    pub fn extend_back<I: IntoIterator<Item = T>>(&mut self, iter: I)
+    where
+        T: Serialize,
+    {
+        let iter = iter.into_iter();
+        let additional = iter.size_hint().0.min(2 * CHUNK_ELEMS - self.uncompressed_buffer_back.len());
+        self.uncompressed_buffer_back.reserve(additional);
+        for value in iter {
+            self.push_back(value);
+        }
+    }
+    /// Prepends all elements of `iter` to the front of the deque, one at a time, so they end up in reverse order
+    /// like repeated [`push_front`](Self::push_front) calls.
+    ///
+    /// Elements are compressed a whole chunk at a time as the front buffer fills.
+    pub fn extend_front<I: IntoIterator<Item = T>>(&mut self, iter: I)
+    where
+        T: Serialize,
+    {
+        let iter = iter.into_iter();
+        let additional = iter.size_hint().0.min(2 * CHUNK_ELEMS - self.uncompressed_buffer_front.len());
+        self.uncompressed_buffer_front.reserve(additional);
+        for value in iter {
+            self.push_front(value);
+        }
+    }
+    /// Removes the last element from the deque and returns it, or None if it is empty.
+    pub fn pop_back(&mut self) -> Option<T>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        if self.uncompressed_buffer_back.is_empty() {
+            if let Some(data) = self.pop_back_chunk() {
+                self.uncompressed_buffer_back = decompress(&data);
+            } else {
+                self.uncompressed_buffer_back = std::mem::take(&mut self.uncompressed_buffer_front);
+            }
+        }
+        self.uncompressed_buffer_back.pop_back()
+    }
+    /// Removes the first element from the deque and returns it, or None if it is empty.
+    pub fn pop_front(&mut self) -> Option<T>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        if self.uncompressed_buffer_front.is_empty() {
+            if let Some(data) = self.pop_front_chunk() {
+                self.uncompressed_buffer_front = decompress(&data);
+            } else {
+                self.uncompressed_buffer_front = std::mem::take(&mut self.uncompressed_buffer_back);
+            }
+        }
+        self.uncompressed_buffer_front.pop_front()
+    }
+    /// Rotates the deque `n` places to the left, so that the first `n` elements move to the back.
+    ///
+    /// When the buffers at both ends line up with the chunk boundaries (the length is a multiple of `CHUNK_ELEMS`)
+    /// whole chunks are moved from one end to the other without being decompressed, otherwise elements are popped
+    /// and pushed one at a time. Rotates the other way if that moves fewer elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n > len`.
+    pub fn rotate_left(&mut self, n: usize)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        let len = self.len();
+        assert!(n <= len, "rotation (is {n}) should be <= len (is {len})");
+        if n <= len / 2 {
+            self.rotate_left_inner(n);
+        } else {
+            self.rotate_right_inner(len - n);
+        }
+    }
+    /// Rotates the deque `n` places to the right, so that the last `n` elements move to the front.
+    ///
+    /// See [`rotate_left`](Self::rotate_left).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n > len`.
+    pub fn rotate_right(&mut self, n: usize)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        let len = self.len();
+        assert!(n <= len, "rotation (is {n}) should be <= len (is {len})");
+        if n <= len / 2 {
+            self.rotate_right_inner(n);
+        } else {
+            self.rotate_left_inner(len - n);
+        }
+    }
+    /// Removes the elements in `range` from the deque, returning them as an iterator.
+    ///
+    /// The elements are removed straight away, even if the iterator is not consumed. Whole chunks inside the range
+    /// are handed to the iterator as they are, so chunks which are never iterated over are never decompressed,
+    /// which makes discarding a long prefix cheap. The shorter of the two sides around the range is moved back
+    /// into place, recompressing it unless it lines up with the chunk boundaries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or its start is greater than its end.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> DequeDrain<T, CHUNK_ELEMS, COMPRESSION_LEVEL>
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        let len = self.len();
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.checked_add(1).expect("attempted to index deque from after maximum usize"),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.checked_add(1).expect("attempted to index deque up to maximum usize"),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => len,
+        };
+        assert!(start <= end, "drain index starts at {start} but ends at {end}");
+        assert!(end <= len, "range end index {end} out of range for deque of length {len}");
+        let drained = if start <= len - end {
+            let head = self.take_front(start);
+            let drained = self.take_front(end - start);
+            self.prepend_front(head);
+            drained
+        } else {
+            let tail = self.take_back(len - end);
+            let drained = self.take_back(end - start);
+            self.append_back(tail);
+            drained
+        };
+        drained.into_iter()
+    }
+    /// Decompresses the whole deque into a `Vec`, front to back. Chunks are freed as they are decompressed, so at most
+    /// one chunk exists both compressed and uncompressed at a time.
+    pub fn into_vec(mut self) -> Vec<T> where T: for<'a> Deserialize<'a> {
+        let mut values = Vec::with_capacity(self.len());
+        values.extend(std::mem::take(&mut self.uncompressed_buffer_front));
+        while let Some(data) = self.compressed_storage.pop_front() {
+            values.append(&mut decompress(&data));
+        }
+        values.extend(std::mem::take(&mut self.uncompressed_buffer_back));
+        values
+    }
+    /// Returns the number of elements in the deque, also referred to as its ‘length’.
+    pub fn len(&self) -> usize {
+        self.uncompressed_buffer_front.len() + self.compressed_storage.len() * CHUNK_ELEMS + self.uncompressed_buffer_back.len()
+    }
+    /// Returns true if the deque has a length of 0.
+    pub fn is_empty(&self) -> bool {
+        self.uncompressed_buffer_front.is_empty() && self.compressed_storage.is_empty() && self.uncompressed_buffer_back.is_empty()
+    }
+    #[must_use]
+    pub fn get_uncached(&self, idx: usize) -> Value<T, &T> where T: for<'a> Deserialize<'a> {
+        match self.position(idx)? {
+            Position::Front(elem) => Some(Either::Right(&self.uncompressed_buffer_front[elem])),
+            Position::Compressed(chunk_idx, chunk_offset) =>
+                Some(Either::Left(decompress_nth(&self.compressed_storage[chunk_idx], chunk_offset))),
+            Position::Back(elem) => Some(Either::Right(&self.uncompressed_buffer_back[elem])),
+        }
+    }
+    /// Returns a reference to the element at `idx` (counted from the front), or `None` if it is out of bounds.
+    ///
+    /// An element in the compressed middle of the deque is read through the cache, which keeps its chunk
+    /// decompressed for the following accesses.
+    #[must_use]
+    pub fn get_ref(&mut self, idx: usize) -> Option<&T> where T: for<'a> Deserialize<'a>, C: CacheAccess<T> {
+        match self.position(idx)? {
+            Position::Front(elem) => Some(&self.uncompressed_buffer_front[elem]),
+            Position::Compressed(chunk_idx, chunk_offset) =>
+                Some(self.cache.get_compressed(self.chunk_id(chunk_idx), chunk_offset, &ChunkBytes::new(&self.compressed_storage[chunk_idx]))),
+            Position::Back(elem) => Some(&self.uncompressed_buffer_back[elem]),
+        }
+    }
+    #[must_use]
+    pub fn get(&self, idx: usize) -> Option<T> where T: for<'a> Deserialize<'a> + Clone, C: SharedCacheAccess<T> {
+        match self.position(idx)? {
+            Position::Front(elem) => Some(self.uncompressed_buffer_front[elem].clone()),
+            Position::Compressed(chunk_idx, chunk_offset) =>
+                Some(self.cache.get_shared(self.chunk_id(chunk_idx), chunk_offset, &ChunkBytes::new(&self.compressed_storage[chunk_idx]))),
+            Position::Back(elem) => Some(self.uncompressed_buffer_back[elem].clone()),
+        }
+    }
+    /// Returns a reference to the first element, reading it through the cache if it is compressed.
+    pub fn peek_front(&mut self) -> Option<&T> where T: for<'a> Deserialize<'a>, C: CacheAccess<T> {
+        self.get_ref(0)
+    }
+    /// Returns a reference to the last element, reading it through the cache if it is compressed.
+    pub fn peek_back(&mut self) -> Option<&T> where T: for<'a> Deserialize<'a>, C: CacheAccess<T> {
+        self.get_ref(self.len().checked_sub(1)?)
+    }
+    /// Returns a front-to-back iterator over clones of the elements, decompressing one chunk at a time as it is
+    /// reached.
+    pub fn iter(&self) -> DequeIter<'_, T> where T: Clone + for<'a> Deserialize<'a> {
+        DequeIter::new(self)
+    }
+    /// Removes all elements from the deque.
+    pub fn clear(&mut self) {
+        self.uncompressed_buffer_front = VecDeque::new();
+        self.compressed_storage = VecDeque::new();
+        self.uncompressed_buffer_back = VecDeque::new();
+        self.cache.kill_all();
+    }
+
+    fn rotate_left_inner(&mut self, n: usize)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        let head = self.take_front(n);
+        self.append_back(head);
+    }
+    fn rotate_right_inner(&mut self, n: usize)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        let tail = self.take_back(n);
+        self.prepend_front(tail);
+    }
+    /// Removes the first `n` elements, which must be in bounds, into a new deque.
+    ///
+    /// Nothing is compressed, whole chunks are moved over as they are and only the chunk containing the `n`th
+    /// element is decompressed.
+    fn take_front(&mut self, mut n: usize) -> DequeUncached<T, CHUNK_ELEMS, COMPRESSION_LEVEL> where T: for<'a> Deserialize<'a> {
+        let mut taken = DequeInner::default();
+        let k = n.min(self.uncompressed_buffer_front.len());
+        let rest = self.uncompressed_buffer_front.split_off(k);
+        taken.uncompressed_buffer_front = std::mem::replace(&mut self.uncompressed_buffer_front, rest);
+        n -= k;
+        while n >= CHUNK_ELEMS && !self.compressed_storage.is_empty() {
+            let data = self.pop_front_chunk().unwrap();
+            taken.push_back_chunk(data);
+            n -= CHUNK_ELEMS;
+        }
+        if n > 0 {
+            self.uncompressed_buffer_front = match self.pop_front_chunk() {
+                Some(data) => decompress(&data),
+                None => std::mem::take(&mut self.uncompressed_buffer_back),
+            };
+            let rest = self.uncompressed_buffer_front.split_off(n);
+            taken.uncompressed_buffer_back = std::mem::replace(&mut self.uncompressed_buffer_front, rest);
+        }
+        taken
+    }
+    /// Removes the last `n` elements, which must be in bounds, into a new deque, see [`take_front`](Self::take_front).
+    fn take_back(&mut self, mut n: usize) -> DequeUncached<T, CHUNK_ELEMS, COMPRESSION_LEVEL> where T: for<'a> Deserialize<'a> {
+        let mut taken = DequeInner::default();
+        let k = n.min(self.uncompressed_buffer_back.len());
+        taken.uncompressed_buffer_back = self.uncompressed_buffer_back.split_off(self.uncompressed_buffer_back.len() - k);
+        n -= k;
+        while n >= CHUNK_ELEMS && !self.compressed_storage.is_empty() {
+            let data = self.pop_back_chunk().unwrap();
+            taken.push_front_chunk(data);
+            n -= CHUNK_ELEMS;
+        }
+        if n > 0 {
+            self.uncompressed_buffer_back = match self.pop_back_chunk() {
+                Some(data) => decompress(&data),
+                None => std::mem::take(&mut self.uncompressed_buffer_front),
+            };
+            taken.uncompressed_buffer_front = self.uncompressed_buffer_back.split_off(self.uncompressed_buffer_back.len() - n);
+        }
+        taken
+    }
+    /// Removes at least `n` elements, which must be in bounds, from the front into a new deque, rounding up to whole
+    /// chunks rather than decompressing one.
+    fn evict_front(&mut self, mut n: usize) -> DequeUncached<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        let mut evicted = DequeInner::default();
+        let k = n.min(self.uncompressed_buffer_front.len());
+        let rest = self.uncompressed_buffer_front.split_off(k);
+        evicted.uncompressed_buffer_front = std::mem::replace(&mut self.uncompressed_buffer_front, rest);
+        n -= k;
+        while n > 0 {
+            match self.pop_front_chunk() {
+                Some(data) => {
+                    evicted.push_back_chunk(data);
+                    n = n.saturating_sub(CHUNK_ELEMS);
+                }
+                None => {
+                    let rest = self.uncompressed_buffer_back.split_off(n);
+                    evicted.uncompressed_buffer_back = std::mem::replace(&mut self.uncompressed_buffer_back, rest);
+                    break;
+                }
+            }
+        }
+        evicted
+    }
+    /// Removes at least `n` elements from the back into a new deque, see [`evict_front`](Self::evict_front).
+    fn evict_back(&mut self, mut n: usize) -> DequeUncached<T, CHUNK_ELEMS, COMPRESSION_LEVEL> {
+        let mut evicted = DequeInner::default();
+        let k = n.min(self.uncompressed_buffer_back.len());
+        evicted.uncompressed_buffer_back = self.uncompressed_buffer_back.split_off(self.uncompressed_buffer_back.len() - k);
+        n -= k;
+        while n > 0 {
+            match self.pop_back_chunk() {
+                Some(data) => {
+                    evicted.push_front_chunk(data);
+                    n = n.saturating_sub(CHUNK_ELEMS);
+                }
+                None => {
+                    let len = self.uncompressed_buffer_front.len();
+                    evicted.uncompressed_buffer_front = self.uncompressed_buffer_front.split_off(len - n);
+                    break;
+                }
+            }
+        }
+        evicted
+    }
+    /// Moves all elements of `other` onto the back of the deque.
+    ///
+    /// Chunks of `other` are moved over as they are whenever the back buffer lines up with a chunk boundary,
+    /// otherwise they are decompressed and their elements pushed.
+    fn append_back<C2: Cache>(&mut self, mut other: DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C2>)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        loop {
+            if other.uncompressed_buffer_front.is_empty() && self.uncompressed_buffer_back.len().is_multiple_of(CHUNK_ELEMS) {
+                if let Some(data) = other.pop_front_chunk() {
+                    if !self.uncompressed_buffer_back.is_empty() {
+                        let compressed = compress(&self.uncompressed_buffer_back, COMPRESSION_LEVEL);
+                        self.push_back_chunk(compressed);
+                        self.uncompressed_buffer_back.clear();
+                    }
+                    self.push_back_chunk(data);
+                    continue;
+                }
+            }
+            match other.pop_front() {
+                Some(value) => self.push_back(value),
+                None => break,
+            }
+        }
+    }
+    /// Moves all elements of `other` onto the front of the deque, see [`append_back`](Self::append_back).
+    fn prepend_front<C2: Cache>(&mut self, mut other: DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C2>)
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        loop {
+            if other.uncompressed_buffer_back.is_empty() && self.uncompressed_buffer_front.len().is_multiple_of(CHUNK_ELEMS) {
+                if let Some(data) = other.pop_back_chunk() {
+                    if !self.uncompressed_buffer_front.is_empty() {
+                        let compressed = compress(&self.uncompressed_buffer_front, COMPRESSION_LEVEL);
+                        self.push_front_chunk(compressed);
+                        self.uncompressed_buffer_front.clear();
+                    }
+                    self.push_front_chunk(data);
+                    continue;
+                }
+            }
+            match other.pop_back() {
+                Some(value) => self.push_front(value),
+                None => break,
+            }
+        }
+    }
+    fn push_back_chunk(&mut self, data: Box<[u8]>) {
+        self.compressed_storage.push_back(data);
+    }
+    fn push_front_chunk(&mut self, data: Box<[u8]>) {
+        self.compressed_storage.push_front(data);
+        self.front_chunk_id = self.front_chunk_id.wrapping_sub(1);
+    }
+    fn pop_back_chunk(&mut self) -> Option<Box<[u8]>> {
+        let data = self.compressed_storage.pop_back()?;
+        self.cache.kill(self.chunk_id(self.compressed_storage.len()));
+        Some(data)
+    }
+    fn pop_front_chunk(&mut self) -> Option<Box<[u8]>> {
+        let data = self.compressed_storage.pop_front()?;
+        // The id may be handed out again by a later `push_front_chunk`
+        self.cache.kill(self.front_chunk_id);
+        self.front_chunk_id = self.front_chunk_id.wrapping_add(1);
+        Some(data)
+    }
+    /// The id the cache knows the chunk at `chunk_idx` in `compressed_storage` by.
+    fn chunk_id(&self, chunk_idx: usize) -> usize {
+        self.front_chunk_id.wrapping_add(chunk_idx)
+    }
+    fn position(&self, idx: usize) -> Option<Position> {
+        let front_len = self.uncompressed_buffer_front.len();
+        let compressed_len = self.compressed_storage.len() * CHUNK_ELEMS;
+        if idx < front_len {
+            Some(Position::Front(idx))
+        } else if idx - front_len < compressed_len {
+            let idx = idx - front_len;
+            Some(Position::Compressed(idx / CHUNK_ELEMS, idx % CHUNK_ELEMS))
+        } else if idx - front_len - compressed_len < self.uncompressed_buffer_back.len() {
+            Some(Position::Back(idx - front_len - compressed_len))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T, C: Cache, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32> From<DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>> for Vec<T>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    fn from(deque: DequeInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>) -> Self {
+        deque.into_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{*, compression::{compress, decompress}};
+
+    #[test]
+    fn simple_test() {
+        let mut big_vecdeque = std::collections::VecDeque::new();
+        let mut compressed_deque = Deque::new::<{ 1024 * 9 }, 0>();
+        for _ in 0..(1024 * 10) {
+            big_vecdeque.push_back(1);
+            compressed_deque.push_back(1);
+        }
+        assert_eq!(big_vecdeque.len(), compressed_deque.len());
+        loop {
+            let a = big_vecdeque.pop_front();
+            let b = compressed_deque.pop_front();
+            assert!(a == b);
+            if a.is_none() | b.is_none() {
+                break;
+            }
+        }
+        assert!(compressed_deque.is_empty());
+    }
+
+    #[test]
+    fn both_ends_test() {
+        let mut big_vecdeque = std::collections::VecDeque::new();
+        let mut compressed_deque = DequeUncached::new::<16, 0>();
+        for i in 0..200 {
+            big_vecdeque.push_front(i);
+            compressed_deque.push_front(i);
+            big_vecdeque.push_back(-i);
+            compressed_deque.push_back(-i);
+        }
+        // Test `compress` and `decompress`
+        let data = compress(&compressed_deque, 0);
+        compressed_deque = decompress(&data);
+        assert_eq!(big_vecdeque.len(), compressed_deque.len());
+        for i in 0..400 {
+            if i % 3 == 0 {
+                assert_eq!(big_vecdeque.pop_back(), compressed_deque.pop_back());
+            } else {
+                assert_eq!(big_vecdeque.pop_front(), compressed_deque.pop_front());
+            }
+        }
+        assert!(compressed_deque.is_empty());
+        assert_eq!(compressed_deque.pop_front(), None);
+    }
+
+    #[test]
+    fn get_test() {
+        let mut big_vecdeque = std::collections::VecDeque::new();
+        let mut compressed_deque = Deque::new::<16, 0>();
+        for i in 0..100 {
+            big_vecdeque.push_front(i);
+            compressed_deque.push_front(i);
+            big_vecdeque.push_back(-i);
+            compressed_deque.push_back(-i);
+        }
+        for _ in 0..5 {
+            big_vecdeque.pop_front();
+            compressed_deque.pop_front();
+        }
+        for idx in 0..big_vecdeque.len() + 1 {
+            let bv = big_vecdeque.get(idx);
+            assert_eq!(bv, compressed_deque.get_ref(idx));
+            assert_eq!(bv, compressed_deque.get_uncached(idx).as_ref().map(|x| x.as_ref().either(|x| x, |x| *x)));
+        }
+    }
+
+    #[test]
+    fn cache_test() {
+        let mut big_vecdeque = std::collections::VecDeque::new();
+        let mut compressed_deque = DequeRc::new::<16, 0>();
+        for i in 0..100 {
+            big_vecdeque.push_back(i);
+            compressed_deque.push_back(i);
+        }
+        assert_eq!(compressed_deque.get(40), Some(40));
+        // Chunks pushed and popped at the front must not be confused with the cached one
+        for i in 0..40 {
+            big_vecdeque.push_front(-i);
+            compressed_deque.push_front(-i);
+            assert_eq!(big_vecdeque.get(15).copied(), compressed_deque.get(15));
+            assert_eq!(big_vecdeque.get(80).copied(), compressed_deque.get(80));
+        }
+        for _ in 0..60 {
+            big_vecdeque.pop_front();
+            compressed_deque.pop_front();
+            assert_eq!(big_vecdeque.get(15).copied(), compressed_deque.get(15));
+        }
+        for i in 0..40 {
+            big_vecdeque.push_front(i * 2);
+            compressed_deque.push_front(i * 2);
+            assert_eq!(big_vecdeque.get(20).copied(), compressed_deque.get(20));
+        }
+        let mut compressed_deque: Deque<i32, 16> = big_vecdeque.iter().copied().collect();
+        while !big_vecdeque.is_empty() {
+            assert_eq!(big_vecdeque.front(), compressed_deque.peek_front());
+            assert_eq!(big_vecdeque.back(), compressed_deque.peek_back());
+            big_vecdeque.pop_back();
+            compressed_deque.pop_back();
+        }
+        assert_eq!(compressed_deque.peek_front(), None);
+        assert_eq!(compressed_deque.peek_back(), None);
+    }
+
+    #[test]
+    fn boundary_test() {
+        let mut big_vecdeque = std::collections::VecDeque::new();
+        let mut compressed_deque = Deque::new::<16, 0>();
+        for i in 0..64 {
+            big_vecdeque.push_front(i);
+            compressed_deque.push_front(i);
+            big_vecdeque.push_back(-i);
+            compressed_deque.push_back(-i);
+        }
+        while !compressed_deque.uncompressed_buffer_front.is_empty() {
+            assert_eq!(big_vecdeque.pop_front(), compressed_deque.pop_front());
+        }
+        // Popping into the next chunk decompresses it, after which thrashing around the boundary leaves the chunks
+        // alone
+        assert_eq!(big_vecdeque.pop_front(), compressed_deque.pop_front());
+        let chunks = compressed_deque.compressed_storage.clone();
+        for i in 0..100 {
+            for _ in 0..i % 4 {
+                big_vecdeque.push_front(i);
+                compressed_deque.push_front(i);
+            }
+            for _ in 0..i % 4 {
+                assert_eq!(big_vecdeque.pop_front(), compressed_deque.pop_front());
+            }
+            assert_eq!(compressed_deque.compressed_storage, chunks);
+        }
+        assert!(big_vecdeque.iter().copied().eq(&compressed_deque));
+    }
+
+    #[test]
+    fn extend_test() {
+        let mut big_vecdeque = std::collections::VecDeque::new();
+        let mut compressed_deque = Deque::new::<16, 0>();
+        big_vecdeque.extend(0..50);
+        compressed_deque.extend(0..50);
+        (100..170).for_each(|i| big_vecdeque.push_front(i));
+        compressed_deque.extend_front(100..170);
+        big_vecdeque.extend(-40..0);
+        compressed_deque.extend_back(-40..0);
+        assert_eq!(big_vecdeque.len(), compressed_deque.len());
+        assert!(big_vecdeque.iter().copied().eq(&compressed_deque));
+        assert!(compressed_deque.uncompressed_buffer_front.len() < 2 * 16);
+        assert!(compressed_deque.uncompressed_buffer_back.len() < 2 * 16);
+    }
+
+    #[test]
+    fn rotate_test() {
+        for len in [0, 7, 64, 100, 160] {
+            let mut big_vecdeque: std::collections::VecDeque<i32> = (0..len).collect();
+            let mut compressed_deque: Deque<i32, 16> = (0..len).collect();
+            for n in [0, 3, 16, 40, 33, 64, 99, 100, 150] {
+                let n = n % (len as usize + 1);
+                big_vecdeque.rotate_left(n);
+                compressed_deque.rotate_left(n);
+                assert!(big_vecdeque.iter().copied().eq(&compressed_deque));
+                big_vecdeque.rotate_right(n / 2);
+                compressed_deque.rotate_right(n / 2);
+                assert!(big_vecdeque.iter().copied().eq(&compressed_deque));
+            }
+        }
+        // With the buffers lined up chunks are moved as they are
+        let mut compressed_deque: Deque<i32, 16> = (0..160).collect();
+        let mut chunks: Vec<_> = compressed_deque.compressed_storage.iter().cloned().collect();
+        // The back buffer holds exactly one chunk, which is compressed as is
+        chunks.push(compress(&compressed_deque.uncompressed_buffer_back, 0));
+        compressed_deque.rotate_left(48);
+        compressed_deque.rotate_right(64);
+        let mut rotated: Vec<_> = compressed_deque.compressed_storage.iter().cloned().collect();
+        chunks.sort();
+        rotated.sort();
+        assert!(rotated.iter().all(|chunk| chunks.binary_search(chunk).is_ok()));
+        assert!(compressed_deque.iter().eq((144..160).chain(0..144)));
+    }
+
+    #[test]
+    fn drain_test() {
+        for (start, end) in [(0, 0), (0, 50), (3, 70), (20, 180), (100, 190), (130, 200), (0, 200), (64, 128)] {
+            let mut big_vecdeque: std::collections::VecDeque<i32> = (0..200).collect();
+            let mut compressed_deque: Deque<i32, 16> = (0..200).collect();
+            assert!(big_vecdeque.drain(start..end).eq(compressed_deque.drain(start..end)));
+            assert_eq!(big_vecdeque.len(), compressed_deque.len());
+            assert!(big_vecdeque.iter().copied().eq(&compressed_deque));
+            // Dropping the iterator still removes the elements
+            let _ = big_vecdeque.drain(..start / 2);
+            let _ = compressed_deque.drain(..start / 2);
+            assert!(big_vecdeque.iter().copied().eq(&compressed_deque));
+        }
+    }
+
+    #[test]
+    fn bounded_test() {
+        let mut compressed_deque = Deque::<i32, 16>::with_max_len(100);
+        assert_eq!(compressed_deque.max_len(), Some(100));
+        let mut evicted = Vec::new();
+        for i in 0..1000 {
+            evicted.extend(compressed_deque.push_back_evicting(i));
+            assert!(compressed_deque.len() <= 100);
+            assert!(evicted.iter().copied().chain(&compressed_deque).eq(0..=i));
+        }
+        assert!(compressed_deque.len() > 100 - 16);
+        for i in 0..1000 {
+            compressed_deque.push_front(-i);
+            assert!(compressed_deque.len() <= 100);
+        }
+        assert!(compressed_deque.iter().eq((0..compressed_deque.len() as i32).map(|i| i - 999)));
+        let mut compressed_deque = Deque::<i32, 16>::with_max_len(0);
+        assert!(compressed_deque.push_front_evicting(1).into_iter().eq([1]));
+        assert!(compressed_deque.is_empty());
+    }
+
+    #[test]
+    fn into_vec_test() {
+        let mut big_vecdeque = std::collections::VecDeque::new();
+        let mut compressed_deque = Deque::new::<16, 0>();
+        for i in 0..100 {
+            big_vecdeque.push_front(i);
+            compressed_deque.push_front(i);
+            big_vecdeque.push_back(-i);
+            compressed_deque.push_back(-i);
+        }
+        assert_eq!(Vec::from(big_vecdeque.clone()), compressed_deque.clone().into_vec());
+        assert_eq!(Vec::from(big_vecdeque), Vec::from(compressed_deque));
+        assert_eq!(Deque::<u8>::default().into_vec(), Vec::new());
+    }
+
+    #[test]
+    fn iter_test() {
+        let big_vecdeque: std::collections::VecDeque<f64> = (0..(1024 * 10)).map(|i| i as f64).collect();
+        let compressed_deque: Deque<f64, { 1024 * 9 }> = big_vecdeque.iter().copied().collect();
+        assert!(big_vecdeque.iter().copied().eq(compressed_deque.iter()));
+        assert!(big_vecdeque.iter().rev().copied().eq(compressed_deque.iter().rev()));
+        assert!(big_vecdeque.iter().copied().eq(&compressed_deque));
+        let mut iter = compressed_deque.iter();
+        let mut big_iter = big_vecdeque.iter().copied();
+        while iter.len() > 0 {
+            assert_eq!(big_iter.next(), iter.next());
+            assert_eq!(big_iter.next_back(), iter.next_back());
+        }
+        assert_eq!(iter.next(), None);
+        assert!(big_vecdeque.clone().into_iter().eq(compressed_deque.clone()));
+        assert!(big_vecdeque.into_iter().rev().eq(compressed_deque.into_iter().rev()));
+    }
+
+    #[test]
+    fn eq_by_elements_test() {
+        use std::hash::{BuildHasher, RandomState};
+        let mut back: Deque<u32, 100> = Deque::default();
+        back.extend_back(0..1050);
+        let mut front: Deque<u32, 100> = Deque::default();
+        front.extend_front((0..1050).rev());
+        // The same elements, held in different chunks and buffers
+        assert_ne!(back.uncompressed_buffer_back.len(), front.uncompressed_buffer_back.len());
+        assert_eq!(back, front);
+        assert_eq!(back.cmp(&front), std::cmp::Ordering::Equal);
+        let state = RandomState::new();
+        assert_eq!(state.hash_one(&back), state.hash_one(&front));
+        front.push_back(0);
+        assert!(back < front);
+        front.pop_back();
+        front.pop_back();
+        front.push_back(0);
+        assert!(back > front);
+        assert_ne!(state.hash_one(&back), state.hash_one(&front));
+    }
+}
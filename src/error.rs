@@ -0,0 +1,114 @@
+use std::collections::TryReserveError;
+use std::fmt;
+use std::io;
+
+/// An error of one of the fallible (`try_`) methods of a collection
+///
+/// The other methods panic in the same situations: these can only happen with damaged data (e.g. a deserialized
+/// collection whose bytes were corrupted, or which was written for another element type), a `Serialize`
+/// implementation which fails, a memory budget which is exceeded, or a spill file or chunk store which fails. They
+/// abort if an allocation fails, where the fallible methods return [`Error::Alloc`] for the allocations they make
+/// fallibly.
+///
+/// Persistence methods such as `CVec::save` return an [`io::Error`], which converts into [`Error::Io`] so both can
+/// be handled together, and an `Error` converts back into an [`io::Error`] of a matching kind.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Elements could not be serialized, e.g. by a `Serialize` implementation which fails
+    Serialize(postcard::Error),
+    /// A decompressed chunk does not hold elements of the collection's type, e.g. because the collection was
+    /// written for another element type
+    Deserialize(postcard::Error),
+    /// A chunk held in memory is not a valid compressed stream
+    Decompress(io::Error),
+    /// A chunk read back from disk or from a chunk store does not match its checksum, or could not be decrypted
+    Corrupt {
+        /// The index of the chunk, the bottom chunk of the stack being 0
+        chunk: usize,
+    },
+    /// Pushing would exceed the collection's memory budget, see `OverflowPolicy::Reject`
+    BudgetExceeded,
+    /// Growing the uncompressed buffer or the chunk list failed to allocate
+    Alloc(TryReserveError),
+    /// Reading or writing a file (or a chunk store) failed
+    Io(io::Error),
+}
+
+impl Error {
+    /// Whether the error means that the collection's data is damaged (or was written for another element type),
+    /// rather than that an operation failed and could be retried.
+    pub fn is_damage(&self) -> bool {
+        matches!(self, Error::Deserialize(_) | Error::Decompress(_) | Error::Corrupt { .. })
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Serialize(err) => write!(f, "failed to serialize elements: {err}"),
+            Error::Deserialize(err) => write!(f, "failed to deserialize a chunk: {err}"),
+            Error::Decompress(err) => write!(f, "failed to decompress a chunk: {err}"),
+            Error::Corrupt { chunk } => write!(f, "chunk {chunk} does not match its checksum"),
+            Error::BudgetExceeded => f.write_str("the memory budget would be exceeded"),
+            Error::Alloc(err) => write!(f, "failed to allocate: {err}"),
+            Error::Io(err) => write!(f, "I/O error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Serialize(err) | Error::Deserialize(err) => Some(err),
+            Error::Decompress(err) | Error::Io(err) => Some(err),
+            Error::Alloc(err) => Some(err),
+            Error::Corrupt { .. } | Error::BudgetExceeded => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<TryReserveError> for Error {
+    fn from(err: TryReserveError) -> Self {
+        Error::Alloc(err)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        let kind = match err {
+            Error::Io(err) => return err,
+            Error::Serialize(_) => io::ErrorKind::InvalidInput,
+            Error::Deserialize(_) | Error::Decompress(_) | Error::Corrupt { .. } => io::ErrorKind::InvalidData,
+            Error::BudgetExceeded | Error::Alloc(_) => io::ErrorKind::OutOfMemory,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+/// The error of `CVec::try_push`, which hands back the element that was not pushed
+#[derive(Debug)]
+pub struct PushError<T> {
+    /// The element which was not pushed
+    pub value: T,
+    /// Why it was not pushed
+    pub error: Error,
+}
+
+impl<T> fmt::Display for PushError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to push: {}", self.error)
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for PushError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,156 @@
+//! The container format written by [`CVec::save`](crate::CVec::save)
+//!
+//! The format is stable: a reader of a given version reads every file of that version or older, and a change to
+//! the layout bumps the version. All integers are little endian, checksums are CRC-32 (IEEE, as used by zlib and
+//! PNG).
+//!
+//! | Part        | Layout |
+//! |-------------|--------|
+//! | Header      | the 6 magic bytes `CCVEC\0`, then a `u16` format version (currently 1) |
+//! | Chunks      | the encoded bytes of every chunk, back to back, bottom of the stack first |
+//! | Tail        | the elements which were not yet pushed into a chunk, as a postcard encoded sequence |
+//! | Chunk table | for every chunk: `u64` element count, `u64` byte length, `u8` codec id, `u32` checksum of its bytes |
+//! | Footer      | `u64` number of chunks, `u64` byte length of the tail, `u32` checksum of the tail, `u32` checksum of the chunk table, then the header again |
+//!
+//! The chunk table and footer come last so that a file can be written in one pass, a reader finds them from the
+//! end of the file. The chunks start right after the header, each chunk's offset is the sum of the lengths of the
+//! chunks before it, and the tail follows the last chunk.
+//!
+//! Codec ids:
+//! - `1`: the elements as a postcard encoded sequence, compressed with brotli (short sequences are stored in an
+//!   uncompressed meta-block, which any brotli decoder reads)
+//! - `2`: a chunk of codec `1` encrypted with XChaCha20-Poly1305, as the 24 byte nonce followed by the ciphertext
+//!   and its 16 byte tag. The associated data is the chunk's index, its element count and the number of chunks,
+//!   each as a `u64`. A container with encrypted chunks has only encrypted chunks and an empty tail, see
+//!   `CVec::save_encrypted` (with the `encryption` feature).
+//!
+//! Each chunk holds at least one element and at most as many as the collection's chunk size, a reader may reject
+//! files whose chunks do not fit its own chunk size. The tail holds fewer elements than the chunk size.
+//!
+//! A CVec's `Serialize` implementation writes a different, plain structure: the compressed chunks followed by the
+//! uncompressed elements inline, without chunk ends or checksums. This is the structure written by version 0.4,
+//! which is still read, the chunk ends are rebuilt by decoding every chunk. Use this module with
+//! `#[serde(with = ...)]` to serialize a collection as a container instead:
+//!
+//! ```
+//! use compressed_collections::CVec;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Snapshot {
+//!     #[serde(with = "compressed_collections::format")]
+//!     values: CVec<u32>,
+//! }
+//!
+//! let snapshot = Snapshot { values: (0..(16 * 1024)).collect() };
+//! let bytes = postcard::to_stdvec(&snapshot).unwrap();
+//! let read_back: Snapshot = postcard::from_bytes(&bytes).unwrap();
+//! assert_eq!(read_back.values, snapshot.values);
+//! ```
+
+use std::io::Cursor;
+
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::cvec::cache::Cache;
+use crate::cvec::CVecInner;
+
+/// The magic bytes at the start and end of a container
+pub(crate) const MAGIC: [u8; 6] = *b"CCVEC\0";
+/// The current format version, which is written to new containers
+pub(crate) const VERSION: u16 = 1;
+/// The codec id of chunks holding postcard encoded elements compressed with brotli
+pub(crate) const CODEC_BROTLI: u8 = 1;
+/// The codec id of chunks of codec [`CODEC_BROTLI`] sealed with XChaCha20-Poly1305
+pub(crate) const CODEC_SEALED: u8 = 2;
+
+/// Serializes a collection as the bytes of a container, for `#[serde(with = "compressed_collections::format")]`.
+pub fn serialize<T, C, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, S>(
+    cvec: &CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    C: Cache,
+    S: Serializer,
+{
+    let mut bytes = Vec::new();
+    cvec.write_container(&mut bytes).map_err(ser::Error::custom)?;
+    serializer.serialize_bytes(&bytes)
+}
+
+/// Deserializes a collection from the bytes of a container, for
+/// `#[serde(with = "compressed_collections::format")]`.
+pub fn deserialize<'de, T, C, const CHUNK_ELEMS: usize, const COMPRESSION_LEVEL: i32, D>(
+    deserializer: D,
+) -> Result<CVecInner<T, CHUNK_ELEMS, COMPRESSION_LEVEL, C>, D::Error>
+where
+    T: for<'a> Deserialize<'a>,
+    C: Cache,
+    D: Deserializer<'de>,
+{
+    let bytes = serde_bytes::deserialize(deserializer)?;
+    CVecInner::read_container(&mut Cursor::new(bytes), CVecInner::default()).map_err(de::Error::custom)
+}
+
+mod serde_bytes {
+    use serde::de::{Deserializer, Error, SeqAccess, Visitor};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+
+    struct BytesVisitor;
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a byte buffer")
+        }
+        fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+        fn visit_byte_buf<E: Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(b) = seq.next_element()? {
+                bytes.push(b);
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+/// The CRC-32 lookup table, one entry per byte value
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+};
+
+/// The CRC-32 (IEEE) checksum of `bytes`
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc: u32, &byte| CRC_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_test() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
@@ -31,12 +31,23 @@
 //! - [ ] Map
 // #![feature(generic_const_exprs)]
 
+mod channel;
 mod compression;
-// mod deque;
+mod error;
+pub mod format;
+mod deque;
 mod cvec;
 
-// pub use deque::Deque;
-pub use cvec::{CVec, CVecRc, CVecUncached, CVecIntoIter, CVecIntoIterUncached};
+pub use channel::{cchannel, CReceiver, CRecvIter, CSender};
+pub use error::{Error, PushError};
+pub use deque::{Deque, DequeRc, DequeUncached, DequeDrain, DequeIntoIter, DequeIter};
+#[cfg(feature = "tokio")]
+pub use cvec::CVecStream;
+#[cfg(feature = "rayon")]
+pub use cvec::{CVecIntoParIter, CVecParIter};
+#[cfg(feature = "encryption")]
+pub use cvec::EncryptionKey;
+pub use cvec::{on_release_caches, release_caches, CachePool, FrozenCVec, FrozenIter, LogSync, Maintained, OverflowPolicy, Checkpoint, ChunkSize, Clock, EvictionPolicy, Fifo, Lfu, Lru, CVec, CVecAppender, CVecShard, CVecReader, CVecWriter, ChunkDamage, ChunkStore, CorruptChunkPolicy, CVecTryIter, DamagedChunk, VecChunkStore, CVecBudgeted, CVecDyn, CVecIndexed, CVecPooled, DEFAULT_CACHE_BUDGET, CVecMemo, CVecPrefetch, CVecRc, CVecSync, CVecUncached, CVecChunk, CVecChunks, CVecIntoIter, CVecIntoIterUncached, CVecIter, CVecIterMut, CVecPipelinedIter, CVecWindows};
 
 #[cfg(test)]
 mod tests {
@@ -49,6 +60,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::same_item_push)]
     fn simple_test() {
         let mut big_vec = Vec::new();
         let mut compressed_stack = CVec::new::<{ 1024 * 99 }, 2>();
@@ -1,7 +1,9 @@
-// use compressed_collections::Deque;
+use compressed_collections::Deque;
 use compressed_collections::CVec;
+use compressed_collections::{on_release_caches, release_caches, CachePool, CVecPooled};
 
 #[test]
+#[allow(clippy::same_item_push)]
 fn stack_test() {
     let mut big_vec = Vec::new();
     let mut compressed_stack = CVec::new::<{ 1024 * 9 }, 0>();
@@ -19,116 +21,145 @@ fn stack_test() {
     }
 }
 
-// #[test]
-// fn deque_test() {
-//     let mut big_vecdeque = std::collections::VecDeque::new();
-//     let mut compressed_deque = Deque::new_with_options(ChunkSize::SizeElements(1024 * 9), 0);
-//     for _ in 0..(1024 * 10) {
-//         big_vecdeque.push_back(1);
-//         compressed_deque.push_back(1);
-//     }
-//     loop {
-//         let a = big_vecdeque.pop_front();
-//         let b = compressed_deque.pop_front();
-//         assert!(a == b);
-//         if a.is_none() | b.is_none() {
-//             break;
-//         }
-//     }
-// }
+#[test]
+fn deque_test() {
+    let mut big_vecdeque = std::collections::VecDeque::new();
+    let mut compressed_deque = Deque::new::<{ 1024 * 9 }, 0>();
+    for _ in 0..(1024 * 10) {
+        big_vecdeque.push_back(1);
+        compressed_deque.push_back(1);
+    }
+    loop {
+        let a = big_vecdeque.pop_front();
+        let b = compressed_deque.pop_front();
+        assert!(a == b);
+        if a.is_none() | b.is_none() {
+            break;
+        }
+    }
+}
 
-// #[test]
-// fn deque_test_2() {
-//     let mut big_vecdeque = std::collections::VecDeque::new();
-//     let mut compressed_deque = Deque::new_with_options(ChunkSize::SizeElements(1024 * 1), 0);
-//     for _ in 0..(1024 * 10) {
-//         big_vecdeque.push_front(1);
-//         compressed_deque.push_front(1);
-//     }
-//     loop {
-//         let a = big_vecdeque.pop_back();
-//         let b = compressed_deque.pop_back();
-//         assert!(a == b);
-//         if a.is_none() | b.is_none() {
-//             break;
-//         }
-//     }
-// }
+#[test]
+fn deque_test_2() {
+    let mut big_vecdeque = std::collections::VecDeque::new();
+    let mut compressed_deque = Deque::new::<1024, 0>();
+    for _ in 0..(1024 * 10) {
+        big_vecdeque.push_front(1);
+        compressed_deque.push_front(1);
+    }
+    loop {
+        let a = big_vecdeque.pop_back();
+        let b = compressed_deque.pop_back();
+        assert!(a == b);
+        if a.is_none() | b.is_none() {
+            break;
+        }
+    }
+}
 
-// #[test]
-// fn deque_test_3() {
-//     let mut big_vecdeque = std::collections::VecDeque::new();
-//     let mut compressed_deque = Deque::new_with_options(ChunkSize::SizeElements(1024 * 1), 0);
-//     for _ in 0..(1024 * 10) {
-//         big_vecdeque.push_front(1);
-//         compressed_deque.push_front(1);
-//     }
-//     loop {
-//         let a = big_vecdeque.pop_front();
-//         let b = compressed_deque.pop_front();
-//         assert!(a == b);
-//         if a.is_none() | b.is_none() {
-//             break;
-//         }
-//     }
-// }
+#[test]
+fn deque_test_3() {
+    let mut big_vecdeque = std::collections::VecDeque::new();
+    let mut compressed_deque = Deque::new::<1024, 0>();
+    for _ in 0..(1024 * 10) {
+        big_vecdeque.push_front(1);
+        compressed_deque.push_front(1);
+    }
+    loop {
+        let a = big_vecdeque.pop_front();
+        let b = compressed_deque.pop_front();
+        assert!(a == b);
+        if a.is_none() | b.is_none() {
+            break;
+        }
+    }
+}
 
-// #[test]
-// fn deque_test_4() {
-//     let mut big_vecdeque = std::collections::VecDeque::new();
-//     let mut compressed_deque = Deque::new_with_options(ChunkSize::SizeElements(1024 * 1), 0);
-//     for _ in 0..(1024 * 10) {
-//         big_vecdeque.push_back(1);
-//         compressed_deque.push_back(1);
-//     }
-//     loop {
-//         let a = big_vecdeque.pop_back();
-//         let b = compressed_deque.pop_back();
-//         assert!(a == b);
-//         if a.is_none() | b.is_none() {
-//             break;
-//         }
-//     }
-// }
+#[test]
+fn deque_test_4() {
+    let mut big_vecdeque = std::collections::VecDeque::new();
+    let mut compressed_deque = Deque::new::<1024, 0>();
+    for _ in 0..(1024 * 10) {
+        big_vecdeque.push_back(1);
+        compressed_deque.push_back(1);
+    }
+    loop {
+        let a = big_vecdeque.pop_back();
+        let b = compressed_deque.pop_back();
+        assert!(a == b);
+        if a.is_none() | b.is_none() {
+            break;
+        }
+    }
+}
+
+#[test]
+fn deque_test_5() {
+    let mut big_vecdeque = std::collections::VecDeque::new();
+    let mut compressed_deque = Deque::new::<1024, 0>();
+    for _ in 0..(1024 * 10) {
+        big_vecdeque.push_back(1);
+        compressed_deque.push_back(1);
+    }
+    for _ in 0..(1024 * 4) {
+        let a = big_vecdeque.pop_back();
+        let b = compressed_deque.pop_back();
+        assert!(a == b);
+        if a.is_none() | b.is_none() {
+            break;
+        }
+    }
+    for _ in 0..(1024 * 10) {
+        big_vecdeque.push_front(1);
+        compressed_deque.push_front(1);
+    }
+    for _ in 0..(1024 * 4) {
+        let a = big_vecdeque.pop_front();
+        let b = compressed_deque.pop_front();
+        assert!(a == b);
+        if a.is_none() | b.is_none() {
+            break;
+        }
+    }
+    for _ in 0..(1024 * 10) {
+        big_vecdeque.push_back(1);
+        compressed_deque.push_back(1);
+    }
+    loop {
+        let a = big_vecdeque.pop_front();
+        let b = compressed_deque.pop_front();
+        assert!(a == b);
+        if a.is_none() | b.is_none() {
+            break;
+        }
+    }
+}
 
-// #[test]
-// fn deque_test_5() {
-//     let mut big_vecdeque = std::collections::VecDeque::new();
-//     let mut compressed_deque = Deque::new_with_options(ChunkSize::SizeElements(1024 * 1), 0);
-//     for _ in 0..(1024 * 10) {
-//         big_vecdeque.push_back(1);
-//         compressed_deque.push_back(1);
-//     }
-//     for _ in 0..(1024 * 4) {
-//         let a = big_vecdeque.pop_back();
-//         let b = compressed_deque.pop_back();
-//         assert!(a == b);
-//         if a.is_none() | b.is_none() {
-//             break;
-//         }
-//     }
-//     for _ in 0..(1024 * 10) {
-//         big_vecdeque.push_front(1);
-//         compressed_deque.push_front(1);
-//     }
-//     for _ in 0..(1024 * 4) {
-//         let a = big_vecdeque.pop_front();
-//         let b = compressed_deque.pop_front();
-//         assert!(a == b);
-//         if a.is_none() | b.is_none() {
-//             break;
-//         }
-//     }
-//     for _ in 0..(1024 * 10) {
-//         big_vecdeque.push_back(1);
-//         compressed_deque.push_back(1);
-//     }
-//     loop {
-//         let a = big_vecdeque.pop_front();
-//         let b = compressed_deque.pop_front();
-//         assert!(a == b);
-//         if a.is_none() | b.is_none() {
-//             break;
-//         }
-//     }
-// }
+#[test]
+fn release_caches_test() {
+    static HOOK_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    on_release_caches(|| {
+        HOOK_CALLS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    });
+    let pool = CachePool::new(1024 * 1024);
+    let mut stacks: Vec<CVecPooled<u32, 100>> = (0..10).map(|_| CVecPooled::new_in(&pool)).collect();
+    for stack in &mut stacks {
+        stack.extend(0..1000);
+        for i in (0..1000).step_by(100) {
+            assert_eq!(stack.get_ref(i as usize), Some(&i));
+        }
+    }
+    assert_eq!(pool.used(), 10 * 10 * 100 * 4);
+    // Pools on other threads let go of their chunks once they are next used
+    std::thread::scope(|s| {
+        s.spawn(release_caches);
+    });
+    assert_eq!(pool.used(), 0);
+    assert!(HOOK_CALLS.load(std::sync::atomic::Ordering::Relaxed) >= 1);
+    assert_eq!(stacks[3].get_ref(550), Some(&550));
+    assert_eq!(pool.used(), 100 * 4);
+    release_caches();
+    assert_eq!(pool.used(), 0);
+    stacks[0].release_cache();
+    assert_eq!(stacks[0].get_ref(5), Some(&5));
+}